@@ -0,0 +1,94 @@
+//! Times merging a sequence of diffs into a large rendered list.
+//!
+//! Run with `cargo bench --bench merge`.
+
+use std::time::{Duration, Instant};
+
+use liveview_native_core::{
+    diff::fragment::{FragmentMerge, Root, RootDiff},
+    dom::Document,
+};
+use serde_json::{json, Value};
+
+const ROWS: usize = 500;
+const DIFFS: usize = 200;
+const ITERATIONS: u32 = 10;
+
+fn rows(changed: usize) -> Value {
+    (0..ROWS)
+        .map(|row| {
+            let label = if row == changed {
+                format!("changed {row}")
+            } else {
+                format!("row {row}")
+            };
+            json!([row.to_string(), label])
+        })
+        .collect()
+}
+
+fn initial() -> String {
+    json!({
+        "0": "Title",
+        "1": {"d": rows(ROWS), "s": ["<Text id=\"row-", "\">", "</Text>"]},
+        "s": ["<VStack><Text>", "</Text><List>", "</List></VStack>"]
+    })
+    .to_string()
+}
+
+/// A quarter of the diffs only carry events, the rest alternate between the title and a row
+fn diffs() -> Vec<Value> {
+    (0..DIFFS)
+        .map(|i| match i % 4 {
+            0 => json!({"e": [["lvn:focus", {"id": "row-1"}]]}),
+            1 | 3 => json!({"0": format!("Title {i}")}),
+            _ => json!({"1": {"d": rows(i % ROWS)}}),
+        })
+        .collect()
+}
+
+fn time(name: &str, mut run: impl FnMut()) {
+    run();
+
+    let mut total = Duration::ZERO;
+    for _ in 0..ITERATIONS {
+        let start = Instant::now();
+        run();
+        total += start.elapsed();
+    }
+    println!("{name:<32} {:>10.2?} per {DIFFS} diffs", total / ITERATIONS);
+}
+
+fn main() {
+    let initial = initial();
+    let diffs = diffs();
+    let root_diffs: Vec<RootDiff> = diffs
+        .iter()
+        .map(|diff| serde_json::from_value(diff.clone()).expect("invalid diff"))
+        .collect();
+    let root: Root = serde_json::from_str::<RootDiff>(&initial)
+        .expect("invalid fragment")
+        .try_into()
+        .expect("invalid root");
+
+    time("Root::merge of a clone", || {
+        let mut root = root.clone();
+        for diff in &root_diffs {
+            root = root.clone().merge(diff.clone()).expect("merge failed");
+        }
+    });
+
+    time("Root::merge_in_place", || {
+        let mut root = root.clone();
+        for diff in &root_diffs {
+            root.merge_in_place(diff.clone()).expect("merge failed");
+        }
+    });
+
+    time("Document::merge_fragment_json", || {
+        let mut doc = Document::parse_fragment_json(initial.clone()).expect("invalid fragment");
+        for diff in &diffs {
+            doc.merge_fragment_json(diff.clone()).expect("merge failed");
+        }
+    });
+}
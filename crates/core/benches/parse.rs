@@ -0,0 +1,72 @@
+//! Counts the allocations and times parsing a large document with and without reserving
+//! space for its nodes up front.
+//!
+//! Run with `cargo bench --bench parse`.
+
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    sync::atomic::{AtomicUsize, Ordering},
+    time::{Duration, Instant},
+};
+
+use liveview_native_core::dom::{Document, Selector};
+
+const ITERATIONS: u32 = 100;
+
+/// Counts every allocation and reallocation made through the system allocator
+struct Counting;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: Counting = Counting;
+
+fn measure(name: &str, mut run: impl FnMut() -> Document) {
+    let before = ALLOCATIONS.load(Ordering::Relaxed);
+    let document = run();
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed) - before;
+    drop(document);
+
+    let mut total = Duration::ZERO;
+    for _ in 0..ITERATIONS {
+        let start = Instant::now();
+        let document = run();
+        total += start.elapsed();
+        drop(document);
+    }
+    println!(
+        "{name:<32} {allocations:>8} allocations {:>10.2?} per parse",
+        total / ITERATIONS
+    );
+}
+
+fn main() {
+    let input = include_str!("../tests/fixtures/large/from.html");
+    let nodes = Document::parse(input)
+        .expect("failed to parse")
+        .select(Selector::All)
+        .count();
+
+    measure("Document::parse", || {
+        Document::parse(input).expect("failed to parse")
+    });
+    measure("Document::parse_with_capacity", || {
+        Document::parse_with_capacity(input, nodes).expect("failed to parse")
+    });
+}
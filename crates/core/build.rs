@@ -10,17 +10,13 @@ use inflector::Inflector;
 fn main() {
     println!("cargo:rerun-if-changed=build.rs");
     println!("cargo:rerun-if-changed=src/interner/strings.txt");
+    println!("cargo:rerun-if-changed=src/interner/element_names.txt");
 
     let contents = fs::read_to_string("src/interner/strings.txt").unwrap();
 
     let mut symbols = Vec::with_capacity(100);
     symbols.push(("Empty".to_string(), "".to_string()));
-    for line in contents.lines() {
-        let line = line.trim();
-        // Skip blank and comment lines
-        if line.is_empty() || line.starts_with("#") || line.is_empty() {
-            continue;
-        }
+    for line in lines(&contents) {
         match line.split_once(':') {
             None => {
                 let name = derive_name(None, line);
@@ -34,7 +30,31 @@ fn main() {
         }
     }
 
-    let out = PathBuf::from(env::var("OUT_DIR").unwrap()).join("strings.rs");
+    // Element names are interned after the symbols, into a module of their own since they
+    // would collide with the constants of the lowercase html tags
+    let contents = fs::read_to_string("src/interner/element_names.txt").unwrap();
+    let element_names: Vec<_> = lines(&contents)
+        .enumerate()
+        .map(|(i, line)| (symbols.len() + i, line.to_string()))
+        .collect();
+
+    let out_dir = PathBuf::from(env::var("OUT_DIR").unwrap());
+    let mut file = File::create(out_dir.join("element_names.rs")).unwrap();
+    file.write_all(b"use super::Symbol;\n\n").unwrap();
+    for (i, name) in element_names.iter() {
+        write!(
+            &mut file,
+            r#"
+#[allow(non_upper_case_globals)]
+pub const {}: Symbol = Symbol::new({});
+"#,
+            name, i
+        )
+        .unwrap()
+    }
+    file.sync_data().unwrap();
+
+    let out = out_dir.join("strings.rs");
     let mut file = File::create(out).unwrap();
     file.write_all(b"use super::Symbol;\n\n").unwrap();
 
@@ -57,10 +77,21 @@ pub const {}: Symbol = Symbol::new({});
     for (name, value) in symbols.iter() {
         writeln!(&mut file, " ({}, \"{}\"),", name.as_str(), value.as_str()).unwrap();
     }
+    for (i, name) in element_names.iter() {
+        writeln!(&mut file, " (Symbol::new({}), \"{}\"),", i, name.as_str()).unwrap();
+    }
     file.write_all(b"];\n\n").unwrap();
     file.sync_data().unwrap();
 }
 
+/// The lines of a strings file, skipping blank and comment lines
+fn lines(contents: &str) -> impl Iterator<Item = &str> {
+    contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+}
+
 fn derive_name(name: Option<&str>, value: &str) -> String {
     // Use explicitly provided name
     if let Some(name) = name {
@@ -0,0 +1,98 @@
+use super::*;
+
+/// Builds a [RootDiff] from its parts, e.g. `RootDiff::builder().child("0", "hello").build()`
+#[derive(Debug, Clone, Default)]
+pub struct RootDiffBuilder {
+    fragment: FragmentDiffBuilder,
+    components: HashMap<String, ComponentDiff>,
+}
+
+impl RootDiff {
+    pub fn builder() -> RootDiffBuilder {
+        RootDiffBuilder::default()
+    }
+}
+
+impl RootDiffBuilder {
+    /// Sets the child at `index` of the root fragment
+    pub fn child(mut self, index: impl Into<String>, child: impl Into<ChildDiff>) -> Self {
+        self.fragment = self.fragment.child(index, child);
+        self
+    }
+
+    /// Sets the statics of the root fragment, which makes the diff a full render
+    pub fn statics<S: Into<String>>(mut self, statics: impl IntoIterator<Item = S>) -> Self {
+        self.fragment = self.fragment.statics(statics);
+        self
+    }
+
+    /// Sets the diff of the component `cid`
+    pub fn component(mut self, cid: i32, diff: ComponentDiff) -> Self {
+        self.components.insert(cid.to_string(), diff);
+        self
+    }
+
+    pub fn build(self) -> RootDiff {
+        RootDiff {
+            new_render: None,
+            fragment: self.fragment.build(),
+            components: self.components,
+        }
+    }
+}
+
+/// Builds a regular [FragmentDiff] from its children and statics
+#[derive(Debug, Clone, Default)]
+pub struct FragmentDiffBuilder {
+    children: HashMap<String, ChildDiff>,
+    statics: Option<Statics>,
+}
+
+impl FragmentDiff {
+    pub fn builder() -> FragmentDiffBuilder {
+        FragmentDiffBuilder::default()
+    }
+}
+
+impl FragmentDiffBuilder {
+    /// Sets the child at `index`
+    pub fn child(mut self, index: impl Into<String>, child: impl Into<ChildDiff>) -> Self {
+        self.children.insert(index.into(), child.into());
+        self
+    }
+
+    /// Sets the statics, which replace the current fragment when the diff is merged
+    pub fn statics<S: Into<String>>(mut self, statics: impl IntoIterator<Item = S>) -> Self {
+        self.statics = Some(Statics::Statics(
+            statics.into_iter().map(Into::into).collect(),
+        ));
+        self
+    }
+
+    pub fn build(self) -> FragmentDiff {
+        FragmentDiff::UpdateRegular {
+            children: self.children,
+            statics: self.statics,
+            is_root: None,
+            event: None,
+        }
+    }
+}
+
+impl From<FragmentDiff> for ChildDiff {
+    fn from(diff: FragmentDiff) -> Self {
+        ChildDiff::Fragment(diff)
+    }
+}
+
+impl From<&str> for ChildDiff {
+    fn from(value: &str) -> Self {
+        ChildDiff::String(value.to_owned().into())
+    }
+}
+
+impl From<String> for ChildDiff {
+    fn from(value: String) -> Self {
+        ChildDiff::String(value.into())
+    }
+}
@@ -0,0 +1,187 @@
+use super::*;
+
+impl RootDiff {
+    /// Composes `self` with the diff `other` which follows it, such that merging the result is
+    /// equivalent to merging `self` and then `other`.
+    ///
+    /// Later statics replace the earlier fragment entirely, and the events of both diffs are kept
+    /// in order. Stream updates, and components whose statics refer to a component changed by the
+    /// other diff, depend on the state between the two diffs and can not be coalesced.
+    pub fn coalesce(self, other: RootDiff) -> Result<RootDiff, MergeError> {
+        // negative cids refer to the components before the diff, positive ones after it
+        let refers_to = |diff: &HashMap<String, ComponentDiff>,
+                         changed: &HashMap<String, ComponentDiff>,
+                         old: bool| {
+            diff.values()
+                .filter_map(ComponentDiff::component_ref)
+                .any(|cid| (cid < 0) == old && changed.contains_key(&cid.abs().to_string()))
+        };
+        if refers_to(&other.components, &self.components, true)
+            || refers_to(&self.components, &other.components, false)
+        {
+            return Err(MergeError::CoalesceUnsupported);
+        }
+
+        let mut components = self.components;
+        for (cid, diff) in other.components {
+            let diff = match components.remove(&cid) {
+                Some(current) => current.coalesce(diff)?,
+                None => diff,
+            };
+            components.insert(cid, diff);
+        }
+
+        Ok(RootDiff {
+            new_render: other.new_render.or(self.new_render),
+            fragment: self.fragment.coalesce(other.fragment)?,
+            components,
+        })
+    }
+}
+
+impl FragmentDiff {
+    fn coalesce(mut self, mut other: FragmentDiff) -> Result<FragmentDiff, MergeError> {
+        let event = coalesce_events(self.event_mut().take(), other.event_mut().take());
+
+        let mut out = if other.should_replace_current() {
+            other
+        } else {
+            match (self, other) {
+                (
+                    FragmentDiff::UpdateRegular {
+                        children,
+                        statics,
+                        is_root,
+                        ..
+                    },
+                    FragmentDiff::UpdateRegular {
+                        children: new_children,
+                        is_root: new_is_root,
+                        ..
+                    },
+                ) => FragmentDiff::UpdateRegular {
+                    children: coalesce_children(children, new_children)?,
+                    statics,
+                    is_root: new_is_root.or(is_root),
+                    event: None,
+                },
+                (
+                    FragmentDiff::UpdateComprehension {
+                        templates,
+                        statics,
+                        is_root,
+                        stream: None,
+                        ..
+                    },
+                    FragmentDiff::UpdateComprehension {
+                        dynamics,
+                        templates: new_templates,
+                        is_root: new_is_root,
+                        stream: None,
+                        ..
+                    },
+                ) => FragmentDiff::UpdateComprehension {
+                    dynamics,
+                    templates: templates.merge(new_templates)?,
+                    statics,
+                    is_root: new_is_root.or(is_root),
+                    stream: None,
+                    event: None,
+                },
+                (
+                    FragmentDiff::UpdateComprehension { .. },
+                    FragmentDiff::UpdateComprehension { .. },
+                ) => return Err(MergeError::CoalesceUnsupported),
+                _ => return Err(MergeError::FragmentTypeMismatch),
+            }
+        };
+
+        *out.event_mut() = event;
+        Ok(out)
+    }
+
+    fn event_mut(&mut self) -> &mut Option<Value> {
+        match self {
+            FragmentDiff::UpdateRegular { event, .. }
+            | FragmentDiff::UpdateComprehension { event, .. } => event,
+        }
+    }
+}
+
+impl ChildDiff {
+    fn coalesce(self, other: ChildDiff) -> Result<ChildDiff, MergeError> {
+        match (self, other) {
+            (_, other @ (ChildDiff::String(_) | ChildDiff::ComponentID(_))) => Ok(other),
+            (ChildDiff::Fragment(current), ChildDiff::Fragment(diff)) => {
+                Ok(ChildDiff::Fragment(current.coalesce(diff)?))
+            }
+            (_, ChildDiff::Fragment(diff)) if diff.should_replace_current() => {
+                Ok(ChildDiff::Fragment(diff))
+            }
+            // a fragment without statics replacing a string can not be told apart from
+            // an update of the fragment the child held before `self`
+            _ => Err(MergeError::CreateChildFromUpdateFragment),
+        }
+    }
+}
+
+impl ComponentDiff {
+    fn coalesce(self, other: ComponentDiff) -> Result<ComponentDiff, MergeError> {
+        match (self, other) {
+            (_, other @ ComponentDiff::ReplaceCurrent { .. }) => Ok(other),
+            (
+                ComponentDiff::ReplaceCurrent {
+                    children,
+                    statics,
+                    new_render,
+                    is_root,
+                },
+                ComponentDiff::UpdateRegular {
+                    children: new_children,
+                    ..
+                },
+            ) => Ok(ComponentDiff::ReplaceCurrent {
+                children: children.merge(new_children)?,
+                statics,
+                new_render,
+                is_root,
+            }),
+            (
+                ComponentDiff::UpdateRegular { children, is_root },
+                ComponentDiff::UpdateRegular {
+                    children: new_children,
+                    is_root: new_is_root,
+                },
+            ) => Ok(ComponentDiff::UpdateRegular {
+                children: coalesce_children(children, new_children)?,
+                is_root: new_is_root.or(is_root),
+            }),
+        }
+    }
+}
+
+fn coalesce_children(
+    mut children: HashMap<String, ChildDiff>,
+    new_children: HashMap<String, ChildDiff>,
+) -> Result<HashMap<String, ChildDiff>, MergeError> {
+    for (index, diff) in new_children {
+        let diff = match children.remove(&index) {
+            Some(current) => current.coalesce(diff)?,
+            None => diff,
+        };
+        children.insert(index, diff);
+    }
+    Ok(children)
+}
+
+/// Appends the events pushed by a later diff to those of an earlier one
+fn coalesce_events(events: Option<Value>, new_events: Option<Value>) -> Option<Value> {
+    match (events, new_events) {
+        (Some(Value::Array(mut events)), Some(Value::Array(new_events))) => {
+            events.extend(new_events);
+            Some(Value::Array(events))
+        }
+        (events, None) => events,
+        (_, new_events) => new_events,
+    }
+}
@@ -14,6 +14,8 @@ pub enum MergeError {
     AddChildToExisting,
     #[error("There was a id mismatch when merging a stream")]
     StreamIDMismatch,
+    #[error("The diffs depend on the state between them and can not be coalesced")]
+    CoalesceUnsupported,
     #[error("Stream Error {error}")]
     Stream {
         #[from]
@@ -42,8 +44,19 @@ pub enum RenderError {
     CousinNotFound(i32),
     #[error("Serde Error {0}")]
     SerdeError(#[from] serde_json::Error),
+    #[error("MessagePack Error {0}")]
+    MsgpackError(#[from] rmp_serde::decode::Error),
     #[error("Parse Error {0}")]
     ParseError(#[from] crate::parser::ParseError),
+    #[error("The join payload has no rendered fragment")]
+    NoRenderInJoinPayload,
+    #[error("Diff does not convert to a root: {0}")]
+    InvalidDiff(MergeError),
+    #[error("Replay failed at diff {index}: {error}")]
+    Replay {
+        index: usize,
+        error: Box<RenderError>,
+    },
 }
 
 #[derive(Debug, thiserror::Error, uniffi::Error)]
@@ -1,8 +1,24 @@
+use std::collections::HashSet;
+
 use super::*;
 
 pub trait FragmentMerge: Sized {
     type DiffItem;
+
     fn merge(self, diff: Self::DiffItem) -> Result<Self, MergeError>;
+
+    /// Merges `diff` into `self`.
+    ///
+    /// The default implementation merges a clone and leaves `self` untouched on failure.
+    /// The implementations in this crate merge without cloning the existing tree, in which
+    /// case `self` may have been partially updated when an error is returned.
+    fn merge_in_place(&mut self, diff: Self::DiffItem) -> Result<(), MergeError>
+    where
+        Self: Clone,
+    {
+        *self = self.clone().merge(diff)?;
+        Ok(())
+    }
 }
 
 // This is a direct conversion from RootDiff to Root.
@@ -52,12 +68,19 @@ impl TryFrom<ComponentDiff> for Component {
 impl FragmentMerge for Root {
     type DiffItem = RootDiff;
 
-    fn merge(self, diff: Self::DiffItem) -> Result<Self, MergeError> {
-        let old_components = self.components.clone();
-        let fragment = self.fragment.merge(diff.fragment)?;
-        let new_components = self.components.merge(diff.components)?;
+    fn merge(mut self, diff: Self::DiffItem) -> Result<Self, MergeError> {
+        self.merge_in_place(diff)?;
+        Ok(self)
+    }
+
+    fn merge_in_place(&mut self, diff: Self::DiffItem) -> Result<(), MergeError> {
+        let old_components = self.referenced_components(&diff.components);
 
-        Root::new(fragment, old_components, new_components)
+        self.fragment.merge_in_place(diff.fragment)?;
+        self.components.merge_in_place(diff.components)?;
+        self.new_render = None;
+
+        self.resolve_components(old_components)
     }
 }
 
@@ -78,15 +101,77 @@ impl Root {
         Ok(out)
     }
 
+    /// Merges a diff which only updates components without traversing the fragment tree,
+    /// falling back to [FragmentMerge::merge_in_place] for any other diff.
+    ///
+    /// The result is identical to a full merge, see [RootDiff::is_component_only].
+    pub fn merge_components_only(&mut self, diff: RootDiff) -> Result<(), MergeError> {
+        if !diff.is_component_only() {
+            return self.merge_in_place(diff);
+        }
+        let Fragment::Regular {
+            is_root,
+            new_render,
+            ..
+        } = &mut self.fragment
+        else {
+            return self.merge_in_place(diff);
+        };
+
+        // mirrors merging the empty root fragment diff
+        *new_render = is_root.map(|i| i != 0);
+
+        let old_components = self.referenced_components(&diff.components);
+
+        self.components.merge_in_place(diff.components)?;
+        self.new_render = None;
+
+        self.resolve_components(old_components)
+    }
+
+    /// Snapshots the current components which `diff` may reference by a negative cid
+    fn referenced_components(
+        &self,
+        diff: &HashMap<String, ComponentDiff>,
+    ) -> HashMap<String, Component> {
+        // Only the components which may be referenced by a negative cid
+        // need to survive the merge in their original form.
+        let referenced: HashSet<String> = self
+            .components
+            .values()
+            .filter_map(Component::component_ref)
+            .chain(diff.values().filter_map(ComponentDiff::component_ref))
+            .filter(|cid| *cid < 0)
+            .map(|cid| cid.abs().to_string())
+            .collect();
+
+        self.components
+            .iter()
+            .filter(|(cid, _)| referenced.contains(*cid))
+            .map(|(cid, component)| (cid.clone(), component.clone()))
+            .collect()
+    }
+
     fn resolve_components(
         &mut self,
         old_components: HashMap<String, Component>,
     ) -> Result<(), MergeError> {
-        let new_components = &self.components.clone();
+        // Snapshot only the components referenced by another component
+        let new_components: HashMap<String, Component> = self
+            .components
+            .values()
+            .filter_map(Component::component_ref)
+            .filter(|cid| *cid > 0)
+            .filter_map(|cid| {
+                let cid = cid.to_string();
+                let component = self.components.get(&cid)?.clone();
+                Some((cid, component))
+            })
+            .collect();
 
         let ctx = ResolveCtx {
             old_components: &old_components,
-            new_components,
+            new_components: &new_components,
         };
 
         for component in self.components.values_mut() {
@@ -120,6 +205,13 @@ impl ResolveCtx<'_> {
 }
 
 impl Component {
+    fn component_ref(&self) -> Option<i32> {
+        match self.statics {
+            ComponentStatics::ComponentRef(cid) => Some(cid),
+            ComponentStatics::Statics(_) => None,
+        }
+    }
+
     fn resolve_cids(&mut self, ctx: &ResolveCtx) -> Result<(), MergeError> {
         match self.statics {
             ComponentStatics::ComponentRef(id) => {
@@ -200,8 +292,20 @@ impl Child {
     }
 }
 
+impl ComponentDiff {
+    pub(super) fn component_ref(&self) -> Option<i32> {
+        match self {
+            ComponentDiff::ReplaceCurrent {
+                statics: ComponentStatics::ComponentRef(cid),
+                ..
+            } => Some(*cid),
+            _ => None,
+        }
+    }
+}
+
 impl FragmentDiff {
-    fn should_replace_current(&self) -> bool {
+    pub(super) fn should_replace_current(&self) -> bool {
         match self {
             FragmentDiff::UpdateRegular { statics, .. }
             | FragmentDiff::UpdateComprehension { statics, .. } => statics.is_some(),
@@ -247,17 +351,23 @@ impl TryFrom<Vec<StreamAttribute>> for Stream {
 impl FragmentMerge for Fragment {
     type DiffItem = FragmentDiff;
 
-    fn merge(self, diff: FragmentDiff) -> Result<Self, MergeError> {
+    fn merge(mut self, diff: Self::DiffItem) -> Result<Self, MergeError> {
+        self.merge_in_place(diff)?;
+        Ok(self)
+    }
+
+    fn merge_in_place(&mut self, diff: FragmentDiff) -> Result<(), MergeError> {
         if diff.should_replace_current() {
-            return diff.try_into();
+            *self = diff.try_into()?;
+            return Ok(());
         }
 
         match (self, diff) {
             (
                 Fragment::Regular {
                     children: current_children,
-                    statics: current_statics,
                     is_root: current_reply,
+                    new_render,
                     ..
                 },
                 FragmentDiff::UpdateRegular {
@@ -266,24 +376,19 @@ impl FragmentMerge for Fragment {
                     ..
                 },
             ) => {
-                let new_children = current_children.merge(children_diffs)?;
-                let new_reply = new_reply.or(current_reply);
-                let new_render = new_reply.map(|i| i != 0);
+                current_children.merge_in_place(children_diffs)?;
+                *current_reply = new_reply.or(*current_reply);
+                *new_render = current_reply.map(|i| i != 0);
 
-                Ok(Self::Regular {
-                    children: new_children,
-                    statics: current_statics,
-                    is_root: new_reply,
-                    new_render,
-                })
+                Ok(())
             }
             (
                 Fragment::Comprehension {
-                    dynamics: mut current_dynamics,
-                    statics,
+                    dynamics: current_dynamics,
                     templates: current_templates,
                     stream: current_stream,
                     is_root: current_reply,
+                    new_render,
                     ..
                 },
                 FragmentDiff::UpdateComprehension {
@@ -294,8 +399,8 @@ impl FragmentMerge for Fragment {
                     ..
                 },
             ) => {
-                let new_reply = new_reply.or(current_reply);
-                let templates = current_templates.merge(new_templates)?;
+                *current_reply = new_reply.or(*current_reply);
+                current_templates.merge_in_place(new_templates)?;
 
                 let new_dynamics: Vec<Vec<Child>> = new_dynamics
                     .into_iter()
@@ -307,14 +412,15 @@ impl FragmentMerge for Fragment {
                     })
                     .collect::<Result<Vec<Vec<Child>>, MergeError>>()?;
 
-                let stream = match (current_stream, new_stream) {
+                match (current_stream.as_mut(), new_stream) {
                     (None, None) => {
-                        current_dynamics = new_dynamics;
-                        None
+                        *current_dynamics = new_dynamics;
                     }
-                    (None, Some(stream_attrs)) => Some(Stream::try_from(stream_attrs)?),
-                    (Some(stream), None) => Some(stream),
-                    (Some(mut stream), Some(stream_update)) => {
+                    (None, Some(stream_attrs)) => {
+                        *current_stream = Some(Stream::try_from(stream_attrs)?);
+                    }
+                    (Some(_), None) => {}
+                    (Some(stream), Some(stream_update)) => {
                         for stream_attr in &stream_update {
                             match stream_attr {
                                 StreamAttribute::StreamID(stream_id) => {
@@ -362,20 +468,12 @@ impl FragmentMerge for Fragment {
                                 }
                             }
                         }
-                        Some(stream)
                     }
                 };
 
-                let new_render = new_reply.map(|i| i != 0);
+                *new_render = current_reply.map(|i| i != 0);
 
-                Ok(Self::Comprehension {
-                    dynamics: current_dynamics,
-                    statics,
-                    templates,
-                    stream,
-                    is_root: new_reply,
-                    new_render,
-                })
+                Ok(())
             }
             _ => Err(MergeError::FragmentTypeMismatch),
         }
@@ -384,93 +482,115 @@ impl FragmentMerge for Fragment {
 impl FragmentMerge for HashMap<String, Component> {
     type DiffItem = HashMap<String, ComponentDiff>;
 
-    fn merge(self, diff: Self::DiffItem) -> Result<Self, MergeError> {
-        let mut components = self;
+    fn merge(mut self, diff: Self::DiffItem) -> Result<Self, MergeError> {
+        self.merge_in_place(diff)?;
+        Ok(self)
+    }
+
+    fn merge_in_place(&mut self, diff: Self::DiffItem) -> Result<(), MergeError> {
         for (cid, comp_diff) in diff.into_iter() {
-            if let Some(existing) = components.get_mut(&cid) {
-                *existing = existing.clone().merge(comp_diff)?;
+            if let Some(existing) = self.get_mut(&cid) {
+                existing.merge_in_place(comp_diff)?;
             } else {
-                components.insert(cid.clone(), comp_diff.try_into()?);
+                self.insert(cid, comp_diff.try_into()?);
             }
         }
 
-        Ok(components)
+        Ok(())
     }
 }
 
 impl FragmentMerge for Component {
     type DiffItem = ComponentDiff;
 
-    fn merge(self, diff: Self::DiffItem) -> Result<Self, MergeError> {
+    fn merge(mut self, diff: Self::DiffItem) -> Result<Self, MergeError> {
+        self.merge_in_place(diff)?;
+        Ok(self)
+    }
+
+    fn merge_in_place(&mut self, diff: Self::DiffItem) -> Result<(), MergeError> {
         match diff {
             ComponentDiff::UpdateRegular {
                 children: children_diffs,
                 ..
             } => {
-                let new_children = self.children.merge(children_diffs)?;
-                Ok(Self {
-                    children: new_children,
-                    statics: self.statics,
-                    is_root: None,
-                })
+                self.children.merge_in_place(children_diffs)?;
+                self.is_root = None;
             }
             ComponentDiff::ReplaceCurrent {
                 statics, children, ..
-            } => Ok(Self {
-                children,
-                statics,
-                is_root: None,
-            }),
+            } => {
+                *self = Self {
+                    children,
+                    statics,
+                    is_root: None,
+                };
+            }
         }
+        Ok(())
     }
 }
 
 impl FragmentMerge for Templates {
     type DiffItem = Templates;
 
-    fn merge(self, diff: Self::DiffItem) -> Result<Self, MergeError> {
+    fn merge(mut self, diff: Self::DiffItem) -> Result<Self, MergeError> {
+        self.merge_in_place(diff)?;
+        Ok(self)
+    }
+
+    fn merge_in_place(&mut self, diff: Self::DiffItem) -> Result<(), MergeError> {
         match (self, diff) {
-            (None, None) => Ok(None),
-            (None, Some(template)) => Ok(Some(template)),
-            (Some(template), None) => Ok(Some(template)),
-            (Some(mut current), Some(new)) => {
+            (_, None) => {}
+            (current @ None, Some(template)) => *current = Some(template),
+            (Some(current), Some(new)) => {
                 for (key, val) in new.into_iter() {
                     current.insert(key, val);
                 }
-                Ok(Some(current))
             }
         }
+        Ok(())
     }
 }
 impl FragmentMerge for Child {
     type DiffItem = ChildDiff;
 
-    fn merge(self, diff: Self::DiffItem) -> Result<Self, MergeError> {
+    fn merge(mut self, diff: Self::DiffItem) -> Result<Self, MergeError> {
+        self.merge_in_place(diff)?;
+        Ok(self)
+    }
+
+    fn merge_in_place(&mut self, diff: Self::DiffItem) -> Result<(), MergeError> {
         match (self, diff) {
             (Child::Fragment(current_fragment), ChildDiff::Fragment(fragment_diff)) => {
-                Ok(Self::Fragment(current_fragment.merge(fragment_diff)?))
+                current_fragment.merge_in_place(fragment_diff)?
             }
-            (_, ChildDiff::String(s)) => Ok(Self::String(s)),
-            (_, ChildDiff::ComponentID(id)) => Ok(Self::ComponentID(id)),
-            (_, ChildDiff::Fragment(fragment_diff)) => {
-                Ok(Self::Fragment(fragment_diff.try_into()?))
+            (current, ChildDiff::String(s)) => *current = Self::String(s),
+            (current, ChildDiff::ComponentID(id)) => *current = Self::ComponentID(id),
+            (current, ChildDiff::Fragment(fragment_diff)) => {
+                *current = Self::Fragment(fragment_diff.try_into()?)
             }
         }
+        Ok(())
     }
 }
 
 impl FragmentMerge for HashMap<String, Child> {
     type DiffItem = HashMap<String, ChildDiff>;
 
-    fn merge(self, diff: Self::DiffItem) -> Result<Self, MergeError> {
-        let mut new_children = self;
+    fn merge(mut self, diff: Self::DiffItem) -> Result<Self, MergeError> {
+        self.merge_in_place(diff)?;
+        Ok(self)
+    }
+
+    fn merge_in_place(&mut self, diff: Self::DiffItem) -> Result<(), MergeError> {
         for (index, comp_diff) in diff.into_iter() {
-            if let Some(child) = new_children.get_mut(&index) {
-                *child = child.clone().merge(comp_diff)?;
+            if let Some(child) = self.get_mut(&index) {
+                child.merge_in_place(comp_diff)?;
             } else {
-                new_children.insert(index, comp_diff.try_into()?);
+                self.insert(index, comp_diff.try_into()?);
             }
         }
-        Ok(new_children)
+        Ok(())
     }
 }
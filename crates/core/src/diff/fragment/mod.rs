@@ -1,18 +1,31 @@
 use std::collections::HashMap;
 
+mod builder;
+mod coalesce;
 mod error;
 mod merge;
 mod render;
+mod replay;
 mod wasm;
 
 #[cfg(test)]
 mod tests;
 
+pub use builder::{FragmentDiffBuilder, RootDiffBuilder};
 pub use error::*;
 pub use merge::*;
+pub use render::SourceSpan;
+pub(crate) use render::ComponentSpan;
+pub use replay::replay;
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use serde_json::Value;
 
+/// Decodes a MessagePack encoded diff into the JSON value of the same structure, e.g. to log
+/// or report it like a diff sent as JSON. Use [RootDiff::from_msgpack] to merge it.
+pub fn decode_msgpack(bytes: &[u8]) -> Result<Value, RenderError> {
+    Ok(rmp_serde::from_slice(bytes)?)
+}
+
 // This is the diff coming across the wire for an update to the UI. This can be
 // converted directly into a Root or merged into a Root itself.
 #[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
@@ -27,6 +40,25 @@ pub struct RootDiff {
 }
 
 impl RootDiff {
+    /// Decodes a diff sent as MessagePack rather than JSON, without going through JSON
+    pub fn from_msgpack(bytes: &[u8]) -> Result<Self, RenderError> {
+        Ok(rmp_serde::from_slice(bytes)?)
+    }
+
+    /// Returns true if the root fragment carries new statics, so merging the diff replaces the
+    /// whole render rather than updating it. This is `isNewFingerprint` in the wasm bindings.
+    pub fn is_full_render(&self) -> bool {
+        self.fragment.should_replace_current()
+    }
+
+    /// Checks that the diff converts to a [Root] on its own without merging it into any state,
+    /// i.e. that it does not update existing components and all component references resolve
+    pub fn validate(&self) -> Result<(), RenderError> {
+        Root::try_from(self.clone())
+            .map(drop)
+            .map_err(RenderError::InvalidDiff)
+    }
+
     pub fn events<T: DeserializeOwned>(&self) -> Result<Option<T>, serde_json::Error> {
         match &self.fragment {
             FragmentDiff::UpdateComprehension {
@@ -44,6 +76,22 @@ impl RootDiff {
             _ => Ok(None),
         }
     }
+
+    /// Returns true if the diff only updates components, leaving the root fragment untouched.
+    ///
+    /// Such a diff can be merged with [Root::merge_components_only].
+    pub fn is_component_only(&self) -> bool {
+        !self.components.is_empty()
+            && matches!(
+                &self.fragment,
+                FragmentDiff::UpdateRegular {
+                    children,
+                    statics: None,
+                    is_root: None,
+                    ..
+                } if children.is_empty()
+            )
+    }
 }
 
 // This is the struct representation a complete interpolation tree.
@@ -59,6 +107,77 @@ pub struct Root {
     components: HashMap<String, Component>,
 }
 
+impl Root {
+    /// Returns the kind of the top level fragment
+    pub fn fragment_kind(&self) -> FragmentKind {
+        self.fragment.kind()
+    }
+
+    /// Returns true if the top level fragment is a comprehension
+    pub fn is_comprehension(&self) -> bool {
+        self.fragment_kind() == FragmentKind::Comprehension
+    }
+
+    /// Returns the static segments of the top level fragment, if it has any
+    pub fn statics(&self) -> Option<Vec<String>> {
+        match &self.fragment {
+            Fragment::Regular {
+                statics: Some(Statics::Statics(statics)),
+                ..
+            }
+            | Fragment::Comprehension {
+                statics: Some(Statics::Statics(statics)),
+                ..
+            } => Some(statics.clone()),
+            _ => None,
+        }
+    }
+
+    /// Returns the static segments of every fragment in the tree in the order they are rendered,
+    /// followed by those of the components in ascending cid order.
+    ///
+    /// Template references are resolved, and statics shared by several fragments, e.g. by the
+    /// rows of a comprehension, are only returned once.
+    pub fn all_statics(&self) -> Vec<Vec<String>> {
+        let mut out = vec![];
+        self.fragment.collect_statics(None, &mut out);
+
+        let mut components: Vec<(i32, &Component)> = self
+            .components
+            .iter()
+            .filter_map(|(cid, component)| Some((cid.parse().ok()?, component)))
+            .collect();
+        components.sort_by_key(|(cid, _)| *cid);
+
+        for (_, component) in components {
+            if let ComponentStatics::Statics(statics) = &component.statics {
+                push_statics(&mut out, statics);
+            }
+            for child in ordered_children(&component.children) {
+                child.collect_statics(None, &mut out);
+            }
+        }
+        out
+    }
+}
+
+/// Appends `statics` to `out` unless they were already collected
+fn push_statics(out: &mut Vec<Vec<String>>, statics: &[String]) {
+    if !out.iter().any(|collected| collected == statics) {
+        out.push(statics.to_vec());
+    }
+}
+
+/// Returns the children of a fragment in the order of their indices
+fn ordered_children(children: &HashMap<String, Child>) -> Vec<&Child> {
+    let mut ordered: Vec<(usize, &Child)> = children
+        .iter()
+        .filter_map(|(index, child)| Some((index.parse().ok()?, child)))
+        .collect();
+    ordered.sort_by_key(|(index, _)| *index);
+    ordered.into_iter().map(|(_, child)| child).collect()
+}
+
 #[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
 pub struct Component {
     #[serde(flatten)]
@@ -132,6 +251,98 @@ pub enum Fragment {
     },
 }
 
+/// The variant of a [Fragment], without any of its contents.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, uniffi::Enum)]
+pub enum FragmentKind {
+    Comprehension,
+    Regular,
+}
+
+impl Fragment {
+    pub fn kind(&self) -> FragmentKind {
+        match self {
+            Fragment::Comprehension { .. } => FragmentKind::Comprehension,
+            Fragment::Regular { .. } => FragmentKind::Regular,
+        }
+    }
+
+    /// Collects the statics of this fragment and its descendants for [Root::all_statics]
+    fn collect_statics(
+        &self,
+        parent_templates: Option<&HashMap<String, Vec<String>>>,
+        out: &mut Vec<Vec<String>>,
+    ) {
+        let resolve = |statics: &Option<Statics>,
+                       templates: Option<&HashMap<String, Vec<String>>>,
+                       out: &mut Vec<Vec<String>>| {
+            match statics {
+                Some(Statics::Statics(statics)) => push_statics(out, statics),
+                Some(Statics::TemplateRef(id)) => {
+                    if let Some(template) = templates.and_then(|t| t.get(&id.to_string())) {
+                        push_statics(out, template);
+                    }
+                }
+                Some(Statics::String(_)) | None => {}
+            }
+        };
+
+        match self {
+            Fragment::Regular {
+                statics, children, ..
+            } => {
+                resolve(statics, parent_templates, out);
+                for child in ordered_children(children) {
+                    child.collect_statics(parent_templates, out);
+                }
+            }
+            Fragment::Comprehension {
+                statics,
+                dynamics,
+                templates,
+                ..
+            } => {
+                let merged: HashMap<String, Vec<String>>;
+                let templates = match (parent_templates, templates) {
+                    (Some(parent), Some(own)) => {
+                        merged = parent
+                            .iter()
+                            .chain(own)
+                            .map(|(id, template)| (id.clone(), template.clone()))
+                            .collect();
+                        Some(&merged)
+                    }
+                    (parent, own) => own.as_ref().or(parent),
+                };
+
+                resolve(statics, templates, out);
+                for child in dynamics.iter().flatten() {
+                    child.collect_statics(templates, out);
+                }
+            }
+        }
+    }
+
+    /// Returns the ids of the items of a stream in the order they are rendered, or nothing
+    /// if this is not the comprehension of a stream.
+    ///
+    /// As when merging stream updates, an item is identified by the ` id="..."` among its dynamics.
+    pub fn stream_ids(&self) -> Vec<String> {
+        let Fragment::Comprehension {
+            dynamics,
+            stream: Some(_),
+            ..
+        } = self
+        else {
+            return vec![];
+        };
+
+        dynamics
+            .iter()
+            .filter_map(|children| children.iter().find_map(Child::stream_item_id))
+            .collect()
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
 pub struct Stream {
     // This is actually a string wrapped integer.
@@ -247,6 +458,32 @@ pub enum ChildDiff {
     String(OneOrManyStrings),
 }
 
+/// The variant of a [Child] or [ChildDiff], without any of its contents.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, uniffi::Enum)]
+pub enum ChildKind {
+    Fragment,
+    ComponentID,
+    String,
+}
+
+impl ChildDiff {
+    pub fn kind(&self) -> ChildKind {
+        match self {
+            ChildDiff::Fragment(_) => ChildKind::Fragment,
+            ChildDiff::ComponentID(_) => ChildKind::ComponentID,
+            ChildDiff::String(_) => ChildKind::String,
+        }
+    }
+
+    /// Returns the id of the component this child refers to, if it is one
+    pub fn as_component_id(&self) -> Option<i32> {
+        match self {
+            ChildDiff::ComponentID(cid) => Some(*cid),
+            _ => None,
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
 #[serde(untagged)]
 pub enum OneOrManyStrings {
@@ -261,6 +498,43 @@ impl From<String> for OneOrManyStrings {
 }
 
 impl Child {
+    pub fn kind(&self) -> ChildKind {
+        match self {
+            Child::Fragment(_) => ChildKind::Fragment,
+            Child::ComponentID(_) => ChildKind::ComponentID,
+            Child::String(_) => ChildKind::String,
+        }
+    }
+
+    /// Returns the id of the component this child refers to, if it is one
+    pub fn as_component_id(&self) -> Option<i32> {
+        match self {
+            Child::ComponentID(cid) => Some(*cid),
+            _ => None,
+        }
+    }
+
+    fn collect_statics(
+        &self,
+        templates: Option<&HashMap<String, Vec<String>>>,
+        out: &mut Vec<Vec<String>>,
+    ) {
+        if let Child::Fragment(fragment) = self {
+            fragment.collect_statics(templates, out);
+        }
+    }
+
+    /// Returns `id` if this is the ` id="id"` attribute identifying a stream item
+    fn stream_item_id(&self) -> Option<String> {
+        match self {
+            Self::String(OneOrManyStrings::One(attribute)) => attribute
+                .strip_prefix(" id=\"")?
+                .strip_suffix('"')
+                .map(str::to_owned),
+            _ => None,
+        }
+    }
+
     pub fn statics(&self) -> Option<Vec<String>> {
         match self {
             Self::Fragment(Fragment::Regular {
@@ -1,14 +1,199 @@
+use std::{cell::RefCell, cmp::Reverse, ops::Range};
+
 use super::*;
 
+/// Templates shared by reference with every row of a comprehension during render,
+/// rather than being cloned for each child.
+type TemplatesRef<'a> = Option<&'a HashMap<String, Vec<String>>>;
+
+/// A range of the markup rendered by [Root::render_with_source_map] and the dynamic which
+/// produced it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SourceSpan {
+    /// The byte range of the dynamic's output in the markup
+    pub range: Range<usize>,
+    /// The keys leading from the root to the dynamic as they appear in the diff, i.e. child
+    /// indices, `d` followed by the row and child index for comprehensions, and `c` followed
+    /// by the cid for components
+    pub path: Vec<String>,
+}
+
+/// The byte range of the markup rendered for a component, so its root element can be found
+/// once the markup is parsed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct ComponentSpan {
+    pub range: Range<usize>,
+    pub cid: i32,
+}
+
+/// The position of a dynamic below the fragment or component rendering it
+#[derive(Clone, Copy)]
+enum DynamicKey {
+    Child(usize),
+    Row(usize, usize),
+    Component(i32),
+}
+
+impl DynamicKey {
+    fn segments(self) -> Vec<String> {
+        match self {
+            DynamicKey::Child(index) => vec![index.to_string()],
+            DynamicKey::Row(row, index) => vec!["d".into(), row.to_string(), index.to_string()],
+            DynamicKey::Component(cid) => vec!["c".into(), cid.to_string()],
+        }
+    }
+}
+
+/// Collects the [ComponentSpan] of every component during a render, and the [SourceSpan] of
+/// every dynamic if `paths` is set
+#[derive(Default)]
+struct SourceMap {
+    /// Whether the [SourceSpan] of every dynamic is recorded
+    paths: bool,
+    /// The path to the dynamic being rendered
+    path: Vec<String>,
+    /// The offset of the output of every dynamic being rendered, innermost last
+    starts: Vec<usize>,
+    spans: Vec<SourceSpan>,
+    components: Vec<ComponentSpan>,
+}
+
+/// The state shared by every fragment of a render
+#[derive(Clone, Copy)]
+struct RenderCtx<'a> {
+    components: &'a HashMap<String, Component>,
+    /// Where the spans are recorded if the render builds a source map
+    source_map: Option<&'a RefCell<SourceMap>>,
+}
+
+impl<'a> RenderCtx<'a> {
+    fn new(components: &'a HashMap<String, Component>) -> Self {
+        Self {
+            components,
+            source_map: None,
+        }
+    }
+
+    /// Appends the output of `render` for the dynamic at `key` to `out`, the output of the
+    /// fragment or component being rendered, recording its span if a source map is built
+    fn push_dynamic(
+        self,
+        out: &mut String,
+        key: DynamicKey,
+        render: impl FnOnce() -> Result<String, RenderError>,
+    ) -> Result<(), RenderError> {
+        let Some(source_map) = self.source_map else {
+            out.push_str(&render()?);
+            return Ok(());
+        };
+
+        let (start, depth) = {
+            let mut map = source_map.borrow_mut();
+            let start = map.starts.last().copied().unwrap_or_default() + out.len();
+            let depth = if map.paths {
+                let segments = key.segments();
+                let depth = segments.len();
+                map.path.extend(segments);
+                depth
+            } else {
+                0
+            };
+            map.starts.push(start);
+            (start, depth)
+        };
+
+        let rendered = render();
+
+        let mut map = source_map.borrow_mut();
+        map.starts.pop();
+        let path = map.paths.then(|| map.path.clone());
+        let parent_len = map.path.len() - depth;
+        map.path.truncate(parent_len);
+
+        let rendered = rendered?;
+        let range = start..start + rendered.len();
+        if let DynamicKey::Component(cid) = key {
+            map.components.push(ComponentSpan {
+                range: range.clone(),
+                cid,
+            });
+        }
+        if let Some(path) = path {
+            map.spans.push(SourceSpan { range, path });
+        }
+        out.push_str(&rendered);
+        Ok(())
+    }
+}
+
 // This is to render the Root as an XML tree in String form.
 impl TryInto<String> for Root {
     type Error = RenderError;
 
     fn try_into(self) -> Result<String, Self::Error> {
-        let mut out = String::new();
-        let inner = self.fragment.render(&self.components, None, None)?;
-        out.push_str(&inner);
-        Ok(out)
+        self.render()
+    }
+}
+
+impl Root {
+    /// Renders the tree to markup without consuming it
+    pub fn render(&self) -> Result<String, RenderError> {
+        self.fragment.render_ref(&self.components, None, None)
+    }
+
+    /// Like [Root::render], also returning the [SourceSpan] of every rendered dynamic, e.g. to
+    /// map an element of the markup back to the template which produced it.
+    ///
+    /// The spans are ordered by their start, a span comes before the spans nested in it.
+    pub fn render_with_source_map(&self) -> Result<(String, Vec<SourceSpan>), RenderError> {
+        let source_map = RefCell::new(SourceMap {
+            paths: true,
+            ..Default::default()
+        });
+        let ctx = RenderCtx {
+            source_map: Some(&source_map),
+            ..RenderCtx::new(&self.components)
+        };
+        let out = self.fragment.render_with(ctx, None, None)?;
+
+        let mut spans = source_map.into_inner().spans;
+        spans.sort_by_key(|span| (span.range.start, Reverse(span.range.end), span.path.len()));
+        Ok((out, spans))
+    }
+
+    /// Like [Root::render], also returning the [ComponentSpan] of every rendered component so
+    /// the rendered nodes can be traced back to their component.
+    ///
+    /// The spans are ordered by their start, an enclosing component before the ones nested in it.
+    pub(crate) fn render_with_component_spans(
+        &self,
+    ) -> Result<(String, Vec<ComponentSpan>), RenderError> {
+        let source_map = RefCell::default();
+        let ctx = RenderCtx {
+            source_map: Some(&source_map),
+            ..RenderCtx::new(&self.components)
+        };
+        let out = self.fragment.render_with(ctx, None, None)?;
+
+        let mut components = source_map.into_inner().components;
+        components.sort_by_key(|span| (span.range.start, Reverse(span.range.end)));
+        Ok((out, components))
+    }
+
+    /// Renders the statics of the top level fragment with `placeholder` in place of every
+    /// dynamic, whether or not it is resolved, e.g. for previews before the dynamics are known.
+    pub fn render_skeleton(&self, placeholder: &str) -> Result<String, RenderError> {
+        match &self.fragment {
+            Fragment::Regular {
+                statics: Some(Statics::Statics(statics)),
+                ..
+            } => Ok(statics.join(placeholder)),
+            Fragment::Regular {
+                statics: Some(Statics::TemplateRef(_)),
+                ..
+            } => Err(RenderError::NoTemplates),
+            _ => Ok(String::new()),
+        }
     }
 }
 
@@ -18,6 +203,29 @@ impl Fragment {
         components: &HashMap<String, Component>,
         cousin_statics: Option<Vec<String>>,
         parent_templates: Templates,
+    ) -> Result<String, RenderError> {
+        self.render_ref(
+            components,
+            cousin_statics.as_deref(),
+            parent_templates.as_ref(),
+        )
+    }
+
+    /// Same as [Fragment::render], borrowing the statics and templates instead
+    pub(crate) fn render_ref(
+        &self,
+        components: &HashMap<String, Component>,
+        cousin_statics: Option<&[String]>,
+        parent_templates: TemplatesRef<'_>,
+    ) -> Result<String, RenderError> {
+        self.render_with(RenderCtx::new(components), cousin_statics, parent_templates)
+    }
+
+    fn render_with(
+        &self,
+        ctx: RenderCtx<'_>,
+        cousin_statics: Option<&[String]>,
+        parent_templates: TemplatesRef<'_>,
     ) -> Result<String, RenderError> {
         let mut out = String::new();
         match &self {
@@ -34,12 +242,9 @@ impl Fragment {
                         // contents of the children.
                         for (i, static_item) in statics.iter().enumerate().skip(1) {
                             if let Some(child) = children.get(&(i - 1).to_string()) {
-                                let val = child.render(
-                                    components,
-                                    cousin_statics.clone(),
-                                    parent_templates.clone(),
-                                )?;
-                                out.push_str(&val);
+                                ctx.push_dynamic(&mut out, DynamicKey::Child(i - 1), || {
+                                    child.render_with(ctx, cousin_statics, parent_templates)
+                                })?;
                             }
                             out.push_str(static_item);
                         }
@@ -58,12 +263,9 @@ impl Fragment {
                             let child = children
                                 .get(&child_id.to_string())
                                 .ok_or(RenderError::ChildNotFoundForTemplate(child_id as i32))?;
-                            let val = child.render(
-                                components,
-                                cousin_statics.clone(),
-                                Some(templates.clone()),
-                            )?;
-                            out.push_str(&val);
+                            ctx.push_dynamic(&mut out, DynamicKey::Child(child_id), || {
+                                child.render_with(ctx, cousin_statics, Some(templates))
+                            })?;
                             out.push_str(template_item);
                         }
                     }
@@ -75,23 +277,28 @@ impl Fragment {
                 templates,
                 ..
             } => {
-                let templates: Templates = match (parent_templates, templates) {
+                let merged: Templates;
+                let templates: TemplatesRef<'_> = match (parent_templates, templates) {
                     (None, None) => None,
-                    (None, Some(t)) => Some(t.clone()),
+                    (None, Some(t)) => Some(t),
                     (Some(t), None) => Some(t),
-                    (Some(parent), Some(child)) => Some(parent).merge(Some(child.clone()))?,
+                    (Some(parent), Some(child)) => {
+                        merged = Some(parent.clone()).merge(Some(child.clone()))?;
+                        merged.as_ref()
+                    }
                 };
                 match (statics, cousin_statics) {
                     (None, None) => {
-                        for children in dynamics.iter() {
-                            for child in children.iter() {
-                                let val = child.render(components, None, templates.clone())?;
-                                out.push_str(&val);
+                        for (row, children) in dynamics.iter().enumerate() {
+                            for (i, child) in children.iter().enumerate() {
+                                ctx.push_dynamic(&mut out, DynamicKey::Row(row, i), || {
+                                    child.render_with(ctx, None, templates)
+                                })?;
                             }
                         }
                     }
                     (None, Some(statics)) => {
-                        for children in dynamics.iter() {
+                        for (row, children) in dynamics.iter().enumerate() {
                             out.push_str(&statics[0]);
                             // We start at index 1 rather than zero here because
                             // templates and statics are suppose to wrap the inner
@@ -99,8 +306,9 @@ impl Fragment {
                             for i in 1..statics.len() {
                                 let child = &children[i - 1];
 
-                                let val = child.render(components, None, templates.clone())?;
-                                out.push_str(&val);
+                                ctx.push_dynamic(&mut out, DynamicKey::Row(row, i - 1), || {
+                                    child.render_with(ctx, None, templates)
+                                })?;
                                 out.push_str(&statics[i]);
                             }
                         }
@@ -109,7 +317,7 @@ impl Fragment {
                         match statics {
                             Statics::String(_) => {}
                             Statics::Statics(statics) => {
-                                for children in dynamics.iter() {
+                                for (row, children) in dynamics.iter().enumerate() {
                                     out.push_str(&statics[0]);
                                     // We start at index 1 rather than zero here because
                                     // templates and statics are suppose to wrap the inner
@@ -117,19 +325,21 @@ impl Fragment {
                                     for i in 1..statics.len() {
                                         let child = &children[i - 1];
 
-                                        let val =
-                                            child.render(components, None, templates.clone())?;
-                                        out.push_str(&val);
+                                        ctx.push_dynamic(
+                                            &mut out,
+                                            DynamicKey::Row(row, i - 1),
+                                            || child.render_with(ctx, None, templates),
+                                        )?;
                                         out.push_str(&statics[i]);
                                     }
                                 }
                             }
                             Statics::TemplateRef(template_id) => {
-                                if let Some(ref this_template) = templates {
+                                if let Some(this_template) = templates {
                                     if let Some(template_statics) =
                                         this_template.get(&template_id.to_string())
                                     {
-                                        for children in dynamics.iter() {
+                                        for (row, children) in dynamics.iter().enumerate() {
                                             out.push_str(&template_statics[0]);
 
                                             // We start at index 1 rather than zero here because
@@ -138,12 +348,11 @@ impl Fragment {
                                             for i in 1..template_statics.len() {
                                                 let child = &children[i - 1];
 
-                                                let val = child.render(
-                                                    components,
-                                                    None,
-                                                    templates.clone(),
+                                                ctx.push_dynamic(
+                                                    &mut out,
+                                                    DynamicKey::Row(row, i - 1),
+                                                    || child.render_with(ctx, None, templates),
                                                 )?;
-                                                out.push_str(&val);
                                                 out.push_str(&template_statics[i]);
                                             }
                                         }
@@ -172,12 +381,35 @@ impl Child {
         components: &HashMap<String, Component>,
         statics: Option<Vec<String>>,
         templates: Templates,
+    ) -> Result<String, RenderError> {
+        self.render_ref(components, statics.as_deref(), templates.as_ref())
+    }
+
+    /// Same as [Child::render], borrowing the statics and templates instead
+    pub(crate) fn render_ref(
+        &self,
+        components: &HashMap<String, Component>,
+        statics: Option<&[String]>,
+        templates: TemplatesRef<'_>,
+    ) -> Result<String, RenderError> {
+        self.render_with(RenderCtx::new(components), statics, templates)
+    }
+
+    fn render_with(
+        &self,
+        ctx: RenderCtx<'_>,
+        statics: Option<&[String]>,
+        templates: TemplatesRef<'_>,
     ) -> Result<String, RenderError> {
         match self {
-            Child::Fragment(fragment) => fragment.render(components, statics, templates),
+            Child::Fragment(fragment) => fragment.render_with(ctx, statics, templates),
             Child::ComponentID(cid) => {
-                if let Some(component) = components.get(&cid.to_string()) {
-                    component.render(components)
+                if let Some(component) = ctx.components.get(&cid.to_string()) {
+                    let mut out = String::new();
+                    ctx.push_dynamic(&mut out, DynamicKey::Component(*cid), || {
+                        component.render_with(ctx)
+                    })?;
+                    Ok(out)
                 } else {
                     Err(RenderError::ComponentNotFound(*cid))
                 }
@@ -190,6 +422,10 @@ impl Child {
 
 impl Component {
     pub fn render(&self, components: &HashMap<String, Component>) -> Result<String, RenderError> {
+        self.render_with(RenderCtx::new(components))
+    }
+
+    fn render_with(&self, ctx: RenderCtx<'_>) -> Result<String, RenderError> {
         match &self.statics {
             ComponentStatics::Statics(statics) => {
                 let mut out = String::new();
@@ -203,22 +439,23 @@ impl Component {
                         .children
                         .get(&(i - 1).to_string())
                         .ok_or(RenderError::ChildNotFoundForStatic((i - 1) as i32))?;
-                    let val = inner.render(components, None, None)?;
-                    out.push_str(&val);
+                    ctx.push_dynamic(&mut out, DynamicKey::Child(i - 1), || {
+                        inner.render_with(ctx, None, None)
+                    })?;
                     out.push_str(static_item);
                 }
                 Ok(out)
             }
 
             ComponentStatics::ComponentRef(mut cid) => {
-                let outer_statics: Vec<String>;
-                let cousin_component: Component;
+                let outer_statics: &[String];
+                let cousin_component: &Component;
                 loop {
-                    if let Some(component) = components.get(&cid.to_string()) {
+                    if let Some(component) = ctx.components.get(&cid.to_string()) {
                         match &component.statics {
                             ComponentStatics::Statics(s) => {
-                                outer_statics = s.to_vec();
-                                cousin_component = component.clone();
+                                outer_statics = s;
+                                cousin_component = component;
                                 break;
                             }
                             ComponentStatics::ComponentRef(bread_crumb_cid) => {
@@ -246,8 +483,9 @@ impl Component {
                         .get(&(i - 1).to_string())
                         .ok_or(RenderError::CousinNotFound((i - 1) as i32))?;
 
-                    let val = child.render(components, cousin.statics(), None)?;
-                    out.push_str(&val);
+                    ctx.push_dynamic(&mut out, DynamicKey::Child(i - 1), || {
+                        child.render_with(ctx, cousin.statics().as_deref(), None)
+                    })?;
                     out.push_str(outer_static_item);
                 }
                 Ok(out)
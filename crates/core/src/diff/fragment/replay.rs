@@ -0,0 +1,25 @@
+use super::*;
+
+/// Merges each of `diffs` in order into the fragment described by `initial_json`, returning the
+/// final rendered markup.
+///
+/// This is intended for replaying a recorded diff stream against a golden render. Replay stops at
+/// the first diff which fails to parse or merge, and the returned error carries that diff's index.
+pub fn replay<S: AsRef<str>>(initial_json: &str, diffs: &[S]) -> Result<String, RenderError> {
+    let initial: RootDiff = serde_json::from_str(initial_json)?;
+    let mut root: Root = initial.try_into()?;
+
+    for (index, diff) in diffs.iter().enumerate() {
+        let merge = |root: &mut Root| -> Result<(), RenderError> {
+            let diff: RootDiff = serde_json::from_str(diff.as_ref())?;
+            root.merge_in_place(diff)?;
+            Ok(())
+        };
+        merge(&mut root).map_err(|error| RenderError::Replay {
+            index,
+            error: Box::new(error),
+        })?;
+    }
+
+    root.try_into()
+}
@@ -13,7 +13,11 @@ macro_rules! assert_doc_eq {
 }
 
 use super::*;
+mod reference;
 mod stream;
+
+use reference::{CheckedMerge, ReferenceMerge};
+
 #[test]
 fn stream_parsing() {
     let initial = r#"
@@ -78,7 +82,7 @@ fn considers_links() {
         }
     });
 
-    let result = diff1.merge(diff2.clone()).expect("Merge error");
+    let result = diff1.checked_merge(diff2.clone()).expect("Merge error");
 
     // The reference should be resolved
     let expected: Root = json_struct!({
@@ -122,7 +126,7 @@ fn considers_links_old_and_new() {
         }
     });
 
-    let result = diff1.merge(diff2.clone()).expect("Merge error");
+    let result = diff1.checked_merge(diff2.clone()).expect("Merge error");
 
     let expected: Root = json_struct!({
         "c": {
@@ -171,7 +175,10 @@ fn considers_links_whole_tree() {
         }
     });
 
-    let result = diff1.clone().merge(diff2.clone()).expect("Merge error");
+    let result = diff1
+        .clone()
+        .checked_merge(diff2.clone())
+        .expect("Merge error");
 
     let expected1: Root = json_struct!({
         "c": {
@@ -225,7 +232,7 @@ fn considers_links_whole_tree() {
         }
     });
 
-    let result2 = diff1.merge(diff3.clone()).expect("Merge error");
+    let result2 = diff1.checked_merge(diff3.clone()).expect("Merge error");
 
     let expected2: Root = json_struct!({
         "c": {
@@ -273,7 +280,9 @@ fn simple_diff_js_mirror() {
         "2": "07:15:04 PM"
     });
 
-    let simple_result = simple_diff1.merge(simple_diff2).expect("Merge error");
+    let simple_result = simple_diff1
+        .checked_merge(simple_diff2)
+        .expect("Merge error");
 
     let simple_expected: Root = json_struct!({
         "0": "cooling",
@@ -326,7 +335,7 @@ fn deep_diff_js_mirror() {
         }
     });
 
-    let deep_result = deep_diff1.merge(deep_diff2).expect("Merge error");
+    let deep_result = deep_diff1.checked_merge(deep_diff2).expect("Merge error");
 
     let deep_expected: Root = json_struct!({
         "0": {
@@ -506,7 +515,7 @@ fn jetpack_show_dialog() {
 "#;
     let diff: RootDiff = serde_json::from_str(increment).expect("Failed to deserialize fragment");
 
-    let root = root.merge(diff).expect("Failed to merge diff");
+    let root = root.checked_merge(diff).expect("Failed to merge diff");
     let out: String = root
         .clone()
         .try_into()
@@ -688,7 +697,9 @@ fn jetpack_more_edge_cases() {
   }
 }"#;
     let diff: RootDiff = serde_json::from_str(increment).expect("Failed to deserialize fragment");
-    let root = root.merge(diff).expect("Failed to merge diff into root");
+    let root = root
+        .checked_merge(diff)
+        .expect("Failed to merge diff into root");
     let _out: String = root
         .clone()
         .try_into()
@@ -732,7 +743,9 @@ fn jetpack_more_edge_cases() {
   }
 }"#;
     let diff: RootDiff = serde_json::from_str(increment).expect("Failed to deserialize fragment");
-    let root = root.merge(diff).expect("Failed to merge diff into root");
+    let root = root
+        .checked_merge(diff)
+        .expect("Failed to merge diff into root");
     let _out: String = root
         .clone()
         .try_into()
@@ -783,7 +796,7 @@ fn expands_shared_static_from_cids() {
         "title": "Listing Posts"
     });
 
-    let root = root.merge(mount_diff).expect("merge failed");
+    let root = root.checked_merge(mount_diff).expect("merge failed");
 
     let component1_static = root.components.get("1").expect("C1 Missing");
     let component2_static = root.components.get("2").expect("C2 Missing");
@@ -815,7 +828,7 @@ fn expands_shared_static_from_cids() {
         }
     });
 
-    let root = root.merge(update_diff).expect("Merge error");
+    let root = root.checked_merge(update_diff).expect("Merge error");
     let _ = root.components.get("2").expect("C2 Post Merge Missing");
     let _ = root.components.get("3").expect("C3 Post Merge Missing");
 
@@ -833,6 +846,269 @@ fn expands_shared_static_from_cids() {
     );
 }
 
+#[test]
+fn merge_in_place_resolves_old_cids() {
+    let mount: RootDiff = json_struct!({
+        "0": {"0": 1, "1": 2, "2": "", "s": ["", "", "", ""]},
+        "c": {
+            "1": {"0": "one", "s": ["<a>", "</a>"]},
+            "2": {"0": "two", "s": 1}
+        },
+        "s": ["<div>", "</div>"]
+    });
+    let mut root: Root = mount.try_into().expect("conversion failed");
+    let expected = root.clone();
+
+    // "1" is replaced in the same diff that references its old statics
+    let diff: RootDiff = json_struct!({
+        "0": {"2": 3},
+        "c": {
+            "1": {"0": "uno", "s": ["<b>", "</b>"]},
+            "3": {"0": "three", "s": -1}
+        }
+    });
+
+    let reference = expected
+        .reference_merge(diff.clone())
+        .expect("reference merge failed");
+    root.merge_in_place(diff).expect("merge in place failed");
+    assert_eq!(reference, root);
+
+    let component = root.components.get("3").expect("C3 missing");
+    assert_eq!(
+        component.statics,
+        ComponentStatics::Statics(vec!["<a>".into(), "</a>".into()])
+    );
+
+    let out: String = root.try_into().expect("render failed");
+    assert_eq!(out, "<div><b>uno</b><a>two</a><a>three</a></div>");
+}
+
+#[test]
+fn root_fragment_kind() {
+    let regular: RootDiff = serde_json::from_str(include_str!("flow-1-change-0.json"))
+        .expect("Failed to deserialize fragment");
+    let regular: Root = regular.try_into().expect("conversion failed");
+    assert_eq!(regular.fragment_kind(), FragmentKind::Regular);
+    assert!(!regular.is_comprehension());
+
+    let comprehension: Root = json_struct!({
+        "d": [["foo"], ["bar"]],
+        "s": ["<li>", "</li>"]
+    });
+    assert_eq!(comprehension.fragment_kind(), FragmentKind::Comprehension);
+    assert!(comprehension.is_comprehension());
+}
+
+#[test]
+fn child_kinds() {
+    let diff: RootDiff = serde_json::from_str(include_str!("flow-1-change-0.json"))
+        .expect("Failed to deserialize fragment");
+    let FragmentDiff::UpdateRegular { children, .. } = &diff.fragment else {
+        panic!("expected a regular fragment");
+    };
+    assert_eq!(children["0"].kind(), ChildKind::String);
+    assert_eq!(children["2"].kind(), ChildKind::Fragment);
+    assert_eq!(children["2"].as_component_id(), None);
+
+    let root: Root = diff.try_into().expect("conversion failed");
+    let Fragment::Regular { children, .. } = &root.fragment else {
+        panic!("expected a regular fragment");
+    };
+    assert_eq!(children["0"].kind(), ChildKind::String);
+    assert_eq!(children["2"].kind(), ChildKind::Fragment);
+
+    let component: ChildDiff = serde_json::from_value(json!(1)).expect("invalid child");
+    assert_eq!(component.kind(), ChildKind::ComponentID);
+    assert_eq!(component.as_component_id(), Some(1));
+
+    let component: Child = serde_json::from_value(json!(2)).expect("invalid child");
+    assert_eq!(component.kind(), ChildKind::ComponentID);
+    assert_eq!(component.as_component_id(), Some(2));
+    assert_eq!(children["0"].as_component_id(), None);
+}
+
+#[test]
+fn conditional_toggle_statics_replacement() {
+    let mount: RootDiff = json_struct!({
+        "0": "Title",
+        "1": {"0": "yes", "s": ["<Text>on: ", "</Text>"]},
+        "s": ["<VStack><Text>", "</Text>", "<Text>footer</Text></VStack>"]
+    });
+    let root: Root = mount.try_into().expect("conversion failed");
+    let on: String = root.clone().try_into().expect("render failed");
+    assert_eq!(
+        on,
+        "<VStack><Text>Title</Text><Text>on: yes</Text><Text>footer</Text></VStack>"
+    );
+
+    // switch to the else branch, which has a different static shape
+    let off_diff: RootDiff = json_struct!({
+        "1": {"0": "no", "1": "!", "s": ["<Image name=\"", "\" />", "<Spacer />"]}
+    });
+    // and back to the first branch
+    let on_diff: RootDiff = json_struct!({
+        "1": {"0": "yes", "s": ["<Text>on: ", "</Text>"]}
+    });
+
+    let off_expected =
+        r#"<VStack><Text>Title</Text><Image name="no" />!<Spacer /><Text>footer</Text></VStack>"#;
+    let mut root = root;
+    for _ in 0..2 {
+        root = root.checked_merge(off_diff.clone()).expect("merge failed");
+        let off: String = root.clone().try_into().expect("render failed");
+        assert_eq!(off, off_expected);
+
+        root = root.checked_merge(on_diff.clone()).expect("merge failed");
+        let rendered: String = root.clone().try_into().expect("render failed");
+        assert_eq!(rendered, on);
+    }
+}
+
+/// Asserts that merging `a` and then `b` into `root` matches merging `a.coalesce(b)`
+fn assert_coalesces(root: Root, a: RootDiff, b: RootDiff) {
+    let sequential = root
+        .clone()
+        .checked_merge(a.clone())
+        .and_then(|root| root.checked_merge(b.clone()))
+        .expect("sequential merge failed");
+    let coalesced = a.coalesce(b).expect("coalesce failed");
+    let coalesced = root
+        .checked_merge(coalesced)
+        .expect("coalesced merge failed");
+    assert_eq!(sequential, coalesced);
+}
+
+#[test]
+fn coalesce_thermostat() {
+    let root: Root = json_struct!({
+        "0": "cooling",
+        "1": "cooling",
+        "2": "07:15:03 PM",
+        "s": [
+            "<div class=\"thermostat\">\n  <div class=\"bar ",
+            "\">\n    <a href=\"#\" phx-click=\"toggle-mode\">",
+            "</a>\n    <span>",
+            "</span>\n  </div>\n</div>\n"
+        ],
+    });
+    let a: RootDiff = json_struct!({"2": "07:15:04 PM"});
+    let b: RootDiff = json_struct!({"0": "heating", "2": "07:15:05 PM"});
+    assert_coalesces(root.clone(), a.clone(), b.clone());
+    assert_coalesces(root, b, a);
+}
+
+#[test]
+fn coalesce_nested_comprehensions() {
+    let root: Root = json_struct!({
+        "0": {
+            "0": {
+                "d": [["user1058", "1"], ["user99", "1"]],
+                "s": ["<tr>\n<td>", " (", ")</td>\n</tr>\n"],
+                "r": 1
+            },
+            "s": ["  <table>\n", "  </table>\n"],
+            "r": 1
+        },
+        "1": {
+            "d": [["asdf_asdf"]],
+            "s": ["<tr>\n<td>", "</td>\n<td>"],
+            "r": 1
+        }
+    });
+    let a: RootDiff = json_struct!({
+        "0": {"0": {"d": [["user1058", "2"]]}}
+    });
+    let b: RootDiff = json_struct!({
+        "0": {"0": {"d": [["user1058", "3"], ["user7", "1"]]}},
+        "1": {"d": [["qwer"]], "p": {"0": ["<b>", "</b>"]}}
+    });
+    assert_coalesces(root, a, b);
+}
+
+#[test]
+fn coalesce_later_statics_override() {
+    let root: Root = json_struct!({
+        "0": "Title",
+        "1": {"0": "yes", "s": ["<Text>on: ", "</Text>"]},
+        "s": ["<VStack><Text>", "</Text>", "<Text>footer</Text></VStack>"]
+    });
+    let off: RootDiff = json_struct!({
+        "1": {"0": "no", "1": "!", "s": ["<Image name=\"", "\" />", "<Spacer />"]}
+    });
+    let update: RootDiff = json_struct!({"1": {"1": "?"}});
+    let on: RootDiff = json_struct!({
+        "1": {"0": "yes", "s": ["<Text>on: ", "</Text>"]}
+    });
+
+    assert_coalesces(root.clone(), off.clone(), update.clone());
+    assert_coalesces(root.clone(), update.clone(), off.clone());
+    assert_coalesces(root.clone(), off.clone(), on.clone());
+
+    let coalesced = off.coalesce(update).expect("coalesce failed");
+    assert_coalesces(root, coalesced, on);
+}
+
+#[test]
+fn coalesce_components() {
+    let mount: RootDiff = json_struct!({
+        "0": {"0": 1, "1": 2, "2": "", "s": ["", "", "", ""]},
+        "c": {
+            "1": {"0": "one", "s": ["<a>", "</a>"]},
+            "2": {"0": "two", "s": 1}
+        },
+        "s": ["<div>", "</div>"]
+    });
+    let root: Root = mount.try_into().expect("conversion failed");
+
+    let a: RootDiff = json_struct!({
+        "0": {"2": 3},
+        "c": {
+            "1": {"0": "uno", "s": ["<b>", "</b>"]},
+            "3": {"0": "three", "s": -1}
+        }
+    });
+    let b: RootDiff = json_struct!({
+        "c": {
+            "2": {"0": "dos"},
+            "3": {"0": "tres"}
+        }
+    });
+    assert_coalesces(root.clone(), a.clone(), b);
+
+    // the old statics of "1" are those replaced by `a`, not the ones before it
+    let refers_to_replaced: RootDiff = json_struct!({
+        "0": {"2": 4},
+        "c": {"4": {"0": "four", "s": -1}}
+    });
+    assert!(matches!(
+        a.coalesce(refers_to_replaced),
+        Err(MergeError::CoalesceUnsupported)
+    ));
+}
+
+#[test]
+fn coalesce_keeps_events() {
+    let a: RootDiff = json_struct!({"0": "a", "e": [["first", {}]]});
+    let b: RootDiff = json_struct!({"0": "b", "e": [["second", {"id": 1}]]});
+    let coalesced = a.coalesce(b).expect("coalesce failed");
+
+    let events: Option<Value> = coalesced.events().expect("invalid events");
+    assert_eq!(events, Some(json!([["first", {}], ["second", {"id": 1}]])));
+}
+
+#[test]
+fn coalesce_streams_unsupported() {
+    let a: RootDiff = serde_json::from_str(include_str!("flow-1-change-1.json"))
+        .expect("Failed to deserialize fragment");
+    let b: RootDiff = serde_json::from_str(include_str!("flow-1-change-2.json"))
+        .expect("Failed to deserialize fragment");
+    assert!(matches!(
+        a.coalesce(b),
+        Err(MergeError::CoalesceUnsupported)
+    ));
+}
+
 #[test]
 fn reuses_statics() {
     let static_reuse_diff: RootDiff = json_struct!({
@@ -871,45 +1147,40 @@ bar
     assert_doc_eq!(doc, expected);
 }
 
-#[test]
-fn jetpack_complex() {
-    /*
-
-    The incremental diffs for this test came from this template:
-    @impl true
-    def render(%{platform_id: :jetpack} = assigns) do
-      ~JETPACK"""
-      <Column>
-        <Button phx-click="inc">
-          <Text>Increment</Text>
-        </Button>
-        <Button phx-click="dec">
-          <Text>Decrement</Text>
-        </Button>
-        <Text>Static Text </Text>
-        <Text>Counter 1: <%= @val %> </Text>
-        <Text>Counter 2: <%= @val %> </Text>
-        <%= if @val > 0 do %>
-          <%= for x <- 1..@val do %>
-            <Text fontWeight="W600" fontSize="24">Item <%= x %>!!!</Text>
-            <%= if rem(x+3,2) == 0 do %>
-              <Text color="#FFFF0000">Number = <%= x %> + 3 is even</Text>
-            <% else %>
-              <Text color="#FF0000FF">Number = <%= x %> + 3 is odd</Text>
-            <% end %>
-            <%= if rem(x+4,2) == 0 do %>
-              <Text>Number + 4 = <%= x+4 %> is even</Text>
-            <% else %>
-              <Text>Number + 4 = <%= x+4 %> is odd</Text>
-            <% end %>
-          <% end %>
-          <Text>Number + 100 is <%= @val+100 %></Text>
-        <% end %>
-      </Column>
-      """
-    end
-       */
-    let initial = r#"{
+// The incremental diffs for the jetpack_complex tests came from this template:
+// @impl true
+// def render(%{platform_id: :jetpack} = assigns) do
+//   ~JETPACK"""
+//   <Column>
+//     <Button phx-click="inc">
+//       <Text>Increment</Text>
+//     </Button>
+//     <Button phx-click="dec">
+//       <Text>Decrement</Text>
+//     </Button>
+//     <Text>Static Text </Text>
+//     <Text>Counter 1: <%= @val %> </Text>
+//     <Text>Counter 2: <%= @val %> </Text>
+//     <%= if @val > 0 do %>
+//       <%= for x <- 1..@val do %>
+//         <Text fontWeight="W600" fontSize="24">Item <%= x %>!!!</Text>
+//         <%= if rem(x+3,2) == 0 do %>
+//           <Text color="#FFFF0000">Number = <%= x %> + 3 is even</Text>
+//         <% else %>
+//           <Text color="#FF0000FF">Number = <%= x %> + 3 is odd</Text>
+//         <% end %>
+//         <%= if rem(x+4,2) == 0 do %>
+//           <Text>Number + 4 = <%= x+4 %> is even</Text>
+//         <% else %>
+//           <Text>Number + 4 = <%= x+4 %> is odd</Text>
+//         <% end %>
+//       <% end %>
+//       <Text>Number + 100 is <%= @val+100 %></Text>
+//     <% end %>
+//   </Column>
+//   """
+// end
+const JETPACK_COMPLEX_INITIAL: &str = r#"{
   "0":"0",
   "1":"0",
   "2":"",
@@ -921,13 +1192,9 @@ fn jetpack_complex() {
   ]
 }
 "#;
-    let root: RootDiff = serde_json::from_str(initial).expect("Failed to deserialize fragment");
-    let root: Root = root.try_into().expect("Failed to convert RootDiff to Root");
-    let _out: String = root
-        .clone()
-        .try_into()
-        .expect("Failed to convert root to string");
-    let increment = r#"{
+
+const JETPACK_COMPLEX_INCREMENTS: [&str; 2] = [
+    r#"{
   "0":"1",
   "1":"1",
   "2":{
@@ -957,15 +1224,8 @@ fn jetpack_complex() {
     ]
   }
 }
-    "#;
-    let new_diff: RootDiff =
-        serde_json::from_str(increment).expect("Failed to deserialize diff fragment");
-    let root = root.merge(new_diff).expect("Failed to merge new root in");
-    let _out: String = root
-        .clone()
-        .try_into()
-        .expect("Failed to convert root to string");
-    let increment = r#"{
+    "#,
+    r#"{
   "0":"2",
   "1":"2",
   "2":{
@@ -995,12 +1255,10 @@ fn jetpack_complex() {
     },
     "1":"102"
   }
-}"#;
-    let new_diff: RootDiff =
-        serde_json::from_str(increment).expect("Failed to deserialize diff fragment");
-    let root = root.merge(new_diff).expect("Failed to merge new root in");
-    let out: String = root.try_into().expect("Failed to convert root to string");
-    let expected = r#"
+}"#,
+];
+
+const JETPACK_COMPLEX_EXPECTED: &str = r#"
   <Column>
   <Button phx-click="inc">
     <Text>Increment</Text>
@@ -1019,8 +1277,49 @@ fn jetpack_complex() {
         <Text>Number + 4 = 6 is even</Text>
     <Text>Number + 100 is 102</Text>
 </Column>"#;
+
+#[test]
+fn jetpack_complex() {
+    let initial = JETPACK_COMPLEX_INITIAL;
+    let root: RootDiff = serde_json::from_str(initial).expect("Failed to deserialize fragment");
+    let root: Root = root.try_into().expect("Failed to convert RootDiff to Root");
+    let _out: String = root
+        .clone()
+        .try_into()
+        .expect("Failed to convert root to string");
+    let increment = JETPACK_COMPLEX_INCREMENTS[0];
+    let new_diff: RootDiff =
+        serde_json::from_str(increment).expect("Failed to deserialize diff fragment");
+    let root = root
+        .checked_merge(new_diff)
+        .expect("Failed to merge new root in");
+    let _out: String = root
+        .clone()
+        .try_into()
+        .expect("Failed to convert root to string");
+    let increment = JETPACK_COMPLEX_INCREMENTS[1];
+    let new_diff: RootDiff =
+        serde_json::from_str(increment).expect("Failed to deserialize diff fragment");
+    let root = root
+        .checked_merge(new_diff)
+        .expect("Failed to merge new root in");
+    let out: String = root.try_into().expect("Failed to convert root to string");
+    let expected = JETPACK_COMPLEX_EXPECTED;
     assert_doc_eq!(out, expected);
 }
+
+#[test]
+fn jetpack_complex_replay() {
+    let out = replay(JETPACK_COMPLEX_INITIAL, &JETPACK_COMPLEX_INCREMENTS).expect("replay failed");
+    assert_doc_eq!(out, JETPACK_COMPLEX_EXPECTED);
+
+    let broken = [JETPACK_COMPLEX_INCREMENTS[0], r#"{"0": "#];
+    let err = replay(JETPACK_COMPLEX_INITIAL, &broken).expect_err("replay should fail");
+    assert!(
+        matches!(err, RenderError::Replay { index: 1, ref error } if matches!(**error, RenderError::SerdeError(_))),
+        "unexpected error {err:?}"
+    );
+}
 #[test]
 fn jetpack_simple_counter() {
     let initial_json = r#"{
@@ -1032,7 +1331,9 @@ fn jetpack_simple_counter() {
     let increment_diff = r#"{"0": "1"}"#;
     let other_root: RootDiff =
         serde_json::from_str(increment_diff).expect("Failed to deserialize diff fragment");
-    let new_root = root.merge(other_root).expect("Failed to merge new root in");
+    let new_root = root
+        .checked_merge(other_root)
+        .expect("Failed to merge new root in");
     let expected_root = r#"{
         "0":"1",
         "s":["<Scaffold>\n  <TopAppBar>\n    <Title><Text>Hello</Text></Title>\n  </TopAppBar>\n  <Column width=\"fill\" verticalArrangement=\"center\" horizontalAlignment=\"center\">\n    <Text style=\"headlineLarge\">Title</Text>\n    <Card shape=\"8\" padding=\"16\" width=\"140\" height=\"120\" elevation=\"{'defaultElevation': '10', 'pressedElevation': '2'}\" phx-click=\"dec\">\n      <Text padding=\"16\">Hello Jetpack!</Text>\n    </Card>\n    <Spacer height=\"8\"></Spacer>\n    <Card padding=\"16\">\n      <Text padding=\"16\">Simple card</Text>\n    </Card>\n    <Button phx-click=\"navigate\" contentPadding=\"50\" elevation=\"{'defaultElevation': '20', 'pressedElevation': '10'}\">\n      <Text>Navigate to counter</Text>\n    </Button>\n    <Button phx-click=\"redirect\"><Text>Redirect to counter</Text></Button>\n    <IconButton phx-click=\"inc\" colors=\"{'containerColor': '#FFFF0000', 'contentColor': '#FFFFFFFF'}\">\n      <Icon imageVector=\"filled:Add\"></Icon>\n    </IconButton>\n    <Row verticalAlignment=\"center\">\n      <Button phx-click=\"dec\" shape=\"circle\" size=\"60\">\n        <Text>-</Text>\n      </Button>\n      <Text>This counter: ","</Text>\n      <Button phx-click=\"inc\" shape=\"circle\" size=\"60\"><Text>+</Text></Button>\n    </Row>\n  </Column>\n</Scaffold>"]}"#;
@@ -1077,7 +1378,7 @@ fn test_replace() {
         new
     );
 
-    let merge = current.merge(diff).expect("Failed to merge diff");
+    let merge = current.checked_merge(diff).expect("Failed to merge diff");
     assert_eq!(merge, new);
 }
 
@@ -1104,7 +1405,7 @@ fn test_mutate() {
         new_render: None,
     };
 
-    let merge = current.merge(diff).expect("Failed to merge diff");
+    let merge = current.checked_merge(diff).expect("Failed to merge diff");
     assert_eq!(merge, new);
 }
 
@@ -1163,6 +1464,76 @@ fn simple_diff_render() {
     assert_eq!(out, expected);
 }
 
+#[test]
+fn thermostat_render_skeleton() {
+    let root: Root = json_struct!({
+        "s": [
+            "<div class=\"thermostat\">\n  <div class=\"bar ",
+            "\">\n    <a href=\"#\" phx-click=\"toggle-mode\">",
+            "</a>\n    <span>",
+            "</span>\n  </div>\n</div>\n"
+        ],
+    });
+
+    let expected = r##"<div class="thermostat">
+  <div class="bar ...">
+    <a href="#" phx-click="toggle-mode">...</a>
+    <span>...</span>
+  </div>
+</div>
+"##;
+    let out = root
+        .render_skeleton("...")
+        .expect("Failed to render skeleton");
+    assert_eq!(out, expected);
+}
+
+#[test]
+fn thermostat_statics() {
+    let root: Root = json_struct!({
+        "0": "cooling",
+        "1": "cooling",
+        "2": "07:15:03 PM",
+        "s": [
+            "<div class=\"thermostat\">\n  <div class=\"bar ",
+            "\">\n    <a href=\"#\" phx-click=\"toggle-mode\">",
+            "</a>\n    <span>",
+            "</span>\n  </div>\n</div>\n"
+        ],
+    });
+    let expected = vec![
+        "<div class=\"thermostat\">\n  <div class=\"bar ".to_owned(),
+        "\">\n    <a href=\"#\" phx-click=\"toggle-mode\">".to_owned(),
+        "</a>\n    <span>".to_owned(),
+        "</span>\n  </div>\n</div>\n".to_owned(),
+    ];
+    assert_eq!(root.statics(), Some(expected.clone()));
+    assert_eq!(root.all_statics(), vec![expected]);
+
+    let nested: Root = json_struct!({
+        "0": {
+            "d": [[{"0": "cooling", "s": 0}], [{"0": "heating", "s": 0}]],
+            "p": {"0": ["<Text>", "</Text>"]},
+            "s": ["<li>", "</li>"]
+        },
+        "1": 1,
+        "c": {"1": {"0": {"0": "off", "s": ["<b>", "</b>"]}, "s": ["<Toggle>", "</Toggle>"]}},
+        "s": ["<List>", "", "</List>"]
+    });
+    let statics = |segments: &[&str]| segments.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+    assert_eq!(nested.statics(), Some(statics(&["<List>", "", "</List>"])));
+    assert_eq!(
+        nested.all_statics(),
+        vec![
+            statics(&["<List>", "", "</List>"]),
+            statics(&["<li>", "</li>"]),
+            statics(&["<Text>", "</Text>"]),
+            statics(&["<Toggle>", "</Toggle>"]),
+            statics(&["<b>", "</b>"]),
+        ]
+    );
+}
+
 #[test]
 fn simple_diff_merge_and_render() {
     let simple_diff1 = r#"{
@@ -1183,7 +1554,7 @@ fn simple_diff_merge_and_render() {
     let root_diff: RootDiff =
         serde_json::from_str(simple_diff2).expect("Failed to deserialize fragment");
     let root = root
-        .merge(root_diff)
+        .checked_merge(root_diff)
         .expect("Failed to merge diff into root");
     let out: String = root.try_into().expect("Failed to convert Root into string");
     let expected = r#"<div class="thermostat">
@@ -1459,7 +1830,7 @@ fn deep_diff_merging() {
 }"#;
     let root_diff: RootDiff =
         serde_json::from_str(deep_diff2).expect("Failed to deserialize fragment");
-    let root = root.merge(root_diff).expect("Failed to merge root");
+    let root = root.checked_merge(root_diff).expect("Failed to merge root");
     let deep_diff_result = r#" {
   "0": {
     "0": {
@@ -1579,7 +1950,7 @@ fn swift_bug_repro() {
 
     let diff = serde_json::from_value(first_increment).expect("invalid diff");
 
-    let root = root.merge(diff).expect("merge failed");
+    let root = root.checked_merge(diff).expect("merge failed");
 
     let expected = r#"<VStack>
     <Text>
@@ -1601,7 +1972,14 @@ fn swift_bug_repro() {
     });
 
     let diff = serde_json::from_value(second_increment).expect("invalid diff");
-    let root = root.merge(diff).expect("merge failed");
+    let root = root.checked_merge(diff).expect("merge failed");
+
+    let expected_empty = r#"<VStack>
+    <Button phx-click="inc_temperature"> Increment Temperature </Button>
+    <Button phx-click="dec_temperature"> Decrement Temperature </Button>
+</VStack>"#;
+    let out: String = root.clone().try_into().expect("bad root");
+    assert_doc_eq!(expected_empty, out);
 
     let third_increment = json!({ "0" : {
         "0" : { "d" : [ ["Increment"] ]  }
@@ -1609,7 +1987,101 @@ fn swift_bug_repro() {
     });
 
     let diff = serde_json::from_value(third_increment).expect("invalid diff");
-    let _root = root.merge(diff).expect("merge failed");
+    let root = root.checked_merge(diff).expect("merge failed");
+    let out: String = root.try_into().expect("bad root");
+    assert_doc_eq!(expected, out);
+}
+
+#[test]
+fn empty_dynamics_comprehension() {
+    let initial: RootDiff = json_struct!({
+        "0": {
+            "d": [["a"], ["b"]],
+            "s": ["<Text>", "</Text>"]
+        },
+        "s": ["<List>", "</List>"]
+    });
+    let root: Root = initial.try_into().expect("conversion failed");
+    let out: String = root.clone().try_into().expect("render failed");
+    assert_eq!(out, "<List><Text>a</Text><Text>b</Text></List>");
+
+    let empty: RootDiff = json_struct!({"0": {"d": []}});
+    let rows: RootDiff = json_struct!({"0": {"d": [["c"], ["d"], ["e"]]}});
+
+    // empty -> rows -> empty, twice, must never leave stale rows behind
+    let mut root = root;
+    for _ in 0..2 {
+        root = root.checked_merge(empty.clone()).expect("merge failed");
+        let out: String = root.clone().try_into().expect("render failed");
+        assert_eq!(out, "<List></List>");
+
+        root = root.checked_merge(rows.clone()).expect("merge failed");
+        let out: String = root.clone().try_into().expect("render failed");
+        assert_eq!(
+            out,
+            "<List><Text>c</Text><Text>d</Text><Text>e</Text></List>"
+        );
+    }
+
+    root.merge_in_place(empty).expect("merge failed");
+    let out: String = root.try_into().expect("render failed");
+    assert_eq!(out, "<List></List>");
+}
+
+#[test]
+fn empty_dynamics_comprehension_with_templates() {
+    // a comprehension which is empty on mount still carries its statics
+    let initial: RootDiff = json_struct!({
+        "0": {
+            "d": [],
+            "s": ["<Row>", "</Row>"]
+        },
+        "s": ["<List>", "</List>"]
+    });
+    let root: Root = initial.try_into().expect("conversion failed");
+    let out: String = root.clone().try_into().expect("render failed");
+    assert_eq!(out, "<List></List>");
+
+    let rows: RootDiff = json_struct!({
+        "0": {
+            "d": [[{"0": "x", "s": 0}], [{"0": "y", "s": 0}]],
+            "p": {"0": ["<Text>", "</Text>"]}
+        }
+    });
+    let root = root.checked_merge(rows).expect("merge failed");
+    let out: String = root.clone().try_into().expect("render failed");
+    assert_eq!(
+        out,
+        "<List><Row><Text>x</Text></Row><Row><Text>y</Text></Row></List>"
+    );
+
+    let empty: RootDiff = json_struct!({"0": {"d": []}});
+    let root = root.checked_merge(empty).expect("merge failed");
+    let out: String = root.try_into().expect("render failed");
+    assert_eq!(out, "<List></List>");
+}
+
+#[test]
+fn shared_template_many_rows() {
+    let rows: Vec<_> = (0..500)
+        .map(|i| json!([{"0": i.to_string(), "s": 0}]))
+        .collect();
+    let diff: RootDiff = serde_json::from_value(json!({
+        "0": {
+            "d": rows,
+            "p": {"0": ["<Text>Row ", "</Text>"]},
+            "s": ["<Item>", "</Item>"]
+        },
+        "s": ["<List>", "</List>"]
+    }))
+    .expect("invalid diff");
+    let root: Root = diff.try_into().expect("conversion failed");
+    let out: String = root.try_into().expect("render failed");
+
+    let expected: String = (0..500)
+        .map(|i| format!("<Item><Text>Row {i}</Text></Item>"))
+        .collect();
+    assert_eq!(out, format!("<List>{expected}</List>"));
 }
 
 #[test]
@@ -1800,24 +2272,14 @@ fn test_decode_root_diff() {
     let out: Result<RootDiff, _> = serde_json::from_str(data);
     assert!(out.is_ok());
     let out = out.expect("Failed to deserialize");
-    let expected = RootDiff {
-        fragment: FragmentDiff::UpdateRegular {
-            children: HashMap::from([(
-                "0".into(),
-                ChildDiff::Fragment(FragmentDiff::UpdateRegular {
-                    children: HashMap::from([("0".into(), ChildDiff::ComponentID(1))]),
-                    statics: None,
-                    is_root: None,
-                    event: None,
-                }),
-            )]),
-            statics: None,
-            is_root: None,
-            event: None,
-        },
-        components: HashMap::new(),
-        new_render: None,
-    };
+    let expected = RootDiff::builder()
+        .child(
+            "0",
+            FragmentDiff::builder()
+                .child("0", ChildDiff::ComponentID(1))
+                .build(),
+        )
+        .build();
     assert_eq!(out, expected);
 }
 #[test]
@@ -1911,3 +2373,185 @@ fn test_decode_component_with_dynamics_iterated() {
         }"#;
     let _root: RootDiff = serde_json::from_str(input).expect("Failed to deserialize fragment");
 }
+
+#[test]
+fn full_render_diffs() {
+    let values_only = RootDiff::builder().child("0", "on").build();
+    assert!(!values_only.is_full_render());
+
+    let nested_statics = RootDiff::builder()
+        .child(
+            "0",
+            FragmentDiff::builder()
+                .statics(["<Text>", "</Text>"])
+                .build(),
+        )
+        .build();
+    assert!(!nested_statics.is_full_render());
+
+    let full: RootDiff = serde_json::from_value(json!({
+        "0": "on",
+        "s": ["<Toggle state=\"", "\" />"],
+    }))
+    .expect("invalid diff");
+    assert!(full.is_full_render());
+}
+
+#[test]
+fn merge_components_only() {
+    let mount: RootDiff = json_struct!({
+        "0": {"0": 1, "1": 2, "s": ["", "", ""]},
+        "c": {
+            "1": {"0": "one", "s": ["<a>", "</a>"]},
+            "2": {"0": "two", "s": 1}
+        },
+        "s": ["<div>", "</div>"]
+    });
+    let root: Root = mount.try_into().expect("conversion failed");
+
+    let diff: RootDiff = json_struct!({
+        "c": {
+            "1": {"0": "uno"},
+            "2": {"0": "dos", "s": -1}
+        }
+    });
+    assert!(diff.is_component_only());
+
+    let merged = root
+        .clone()
+        .checked_merge(diff.clone())
+        .expect("merge failed");
+    let mut fast = root.clone();
+    fast.merge_components_only(diff)
+        .expect("component merge failed");
+    assert_eq!(merged, fast);
+
+    let out: String = fast.try_into().expect("render failed");
+    assert_eq!(out, "<div><a>uno</a><a>dos</a></div>");
+
+    // diffs touching the root fragment take the full merge
+    let diff: RootDiff = json_struct!({
+        "0": {"0": 2, "1": 1},
+        "c": {"1": {"0": "eins"}}
+    });
+    assert!(!diff.is_component_only());
+
+    let merged = root
+        .clone()
+        .checked_merge(diff.clone())
+        .expect("merge failed");
+    let mut fast = root;
+    fast.merge_components_only(diff)
+        .expect("component merge failed");
+    assert_eq!(merged, fast);
+}
+
+#[test]
+fn validate_root_diffs() {
+    let mount: RootDiff = json_struct!({
+        "0": 1,
+        "1": 2,
+        "c": {
+            "1": {"0": "one", "s": ["<Text>", "</Text>"]},
+            "2": {"0": "two", "s": 1}
+        },
+        "s": ["<VStack>", "", "</VStack>"]
+    });
+    mount.validate().expect("valid diff");
+
+    let component_update: RootDiff = json_struct!({"c": {"1": {"0": "uno"}}});
+    assert!(matches!(
+        component_update.validate(),
+        Err(RenderError::InvalidDiff(
+            MergeError::CreateComponentFromUpdate
+        ))
+    ));
+
+    let old_component_ref: RootDiff = json_struct!({
+        "0": 3,
+        "c": {"3": {"0": "three", "s": -1}},
+        "s": ["<VStack>", "</VStack>"]
+    });
+    let error = old_component_ref.validate().expect_err("invalid diff");
+    assert!(matches!(
+        error,
+        RenderError::InvalidDiff(MergeError::MissingComponent(-1))
+    ));
+    assert_eq!(
+        error.to_string(),
+        "Diff does not convert to a root: Missing component id -1"
+    );
+}
+
+#[test]
+fn msgpack_diffs_decode_like_json() {
+    let mount = include_str!("flow-1-change-0.json");
+    let update = include_str!("flow-1-change-1.json");
+    let msgpack = |json: &str| {
+        let value: serde_json::Value = serde_json::from_str(json).expect("invalid json");
+        rmp_serde::to_vec(&value).expect("failed to encode")
+    };
+
+    let from_json: Root = serde_json::from_str::<RootDiff>(mount)
+        .expect("invalid diff")
+        .try_into()
+        .expect("invalid root");
+    let from_msgpack: Root = RootDiff::from_msgpack(&msgpack(mount))
+        .expect("failed to decode")
+        .try_into()
+        .expect("invalid root");
+    assert_eq!(from_msgpack, from_json);
+
+    let from_json = from_json
+        .checked_merge(serde_json::from_str(update).expect("invalid diff"))
+        .expect("merge failed");
+    let from_msgpack = from_msgpack
+        .checked_merge(RootDiff::from_msgpack(&msgpack(update)).expect("failed to decode"))
+        .expect("merge failed");
+    assert_eq!(from_msgpack, from_json);
+
+    assert!(matches!(
+        RootDiff::from_msgpack(&[0xc1]),
+        Err(RenderError::MsgpackError(_))
+    ));
+}
+
+#[test]
+fn render_with_source_map_tracks_dynamics() {
+    let diff: RootDiff = json_struct!({
+        "0": "Alice",
+        "1": {
+            "d": [["a"], ["b"]],
+            "s": ["<Item>", "</Item>"]
+        },
+        "2": 1,
+        "c": {"1": {"0": "card", "s": ["<Card>", "</Card>"]}},
+        "s": ["<Text>", "</Text><List>", "</List>", ""]
+    });
+    let root: Root = diff.try_into().expect("invalid root");
+
+    let (out, spans) = root.render_with_source_map().expect("render failed");
+    assert_eq!(out, root.render().expect("render failed"));
+    assert_eq!(
+        out,
+        "<Text>Alice</Text><List><Item>a</Item><Item>b</Item></List><Card>card</Card>"
+    );
+
+    let path_of = |text: &str| {
+        let start = out.find(text).expect("missing text");
+        spans
+            .iter()
+            .find(|span| span.range == (start..start + text.len()))
+            .map(|span| span.path.join("."))
+    };
+    assert_eq!(path_of("Alice"), Some("0".to_string()));
+    assert_eq!(path_of("b"), Some("1.d.1.0".to_string()));
+    assert_eq!(path_of("card"), Some("2.c.1.0".to_string()));
+
+    // outer spans come before the spans nested in them
+    let paths: Vec<_> = spans.iter().map(|span| span.path.join(".")).collect();
+    assert_eq!(
+        paths,
+        ["0", "1", "1.d.0.0", "1.d.1.0", "2", "2.c.1", "2.c.1.0"]
+    );
+}
@@ -0,0 +1,283 @@
+//! The cloning merge which predates [FragmentMerge::merge_in_place], kept as a
+//! reference implementation so the in place merge can be checked against it.
+
+use std::fmt::Debug;
+
+use pretty_assertions::assert_eq;
+
+use super::*;
+
+pub trait ReferenceMerge: Sized {
+    type Diff;
+
+    fn reference_merge(self, diff: Self::Diff) -> Result<Self, MergeError>;
+}
+
+/// Merges with [FragmentMerge::merge_in_place] and asserts that the result,
+/// or the error, matches the reference merge.
+pub trait CheckedMerge: FragmentMerge {
+    fn checked_merge(self, diff: Self::DiffItem) -> Result<Self, MergeError>;
+}
+
+impl<T> CheckedMerge for T
+where
+    T: FragmentMerge + ReferenceMerge<Diff = <T as FragmentMerge>::DiffItem>,
+    T: Clone + PartialEq + Debug,
+    <T as FragmentMerge>::DiffItem: Clone,
+{
+    fn checked_merge(self, diff: Self::DiffItem) -> Result<Self, MergeError> {
+        let reference = self.clone().reference_merge(diff.clone());
+
+        let mut merged = self;
+        let result = merged.merge_in_place(diff).map(|_| merged);
+
+        match (&reference, &result) {
+            (Ok(reference), Ok(merged)) => assert_eq!(reference, merged),
+            (Err(reference), Err(error)) => assert_eq!(reference.to_string(), error.to_string()),
+            _ => panic!("in place merge returned {result:?}, reference returned {reference:?}"),
+        }
+
+        result
+    }
+}
+
+impl ReferenceMerge for Root {
+    type Diff = RootDiff;
+
+    fn reference_merge(self, diff: RootDiff) -> Result<Self, MergeError> {
+        let old_components = self.components.clone();
+        let fragment = self.fragment.reference_merge(diff.fragment)?;
+        let new_components = self.components.reference_merge(diff.components)?;
+
+        Root::new(fragment, old_components, new_components)
+    }
+}
+
+impl ReferenceMerge for Fragment {
+    type Diff = FragmentDiff;
+
+    fn reference_merge(self, diff: FragmentDiff) -> Result<Self, MergeError> {
+        if diff.should_replace_current() {
+            return diff.try_into();
+        }
+
+        match (self, diff) {
+            (
+                Fragment::Regular {
+                    children: current_children,
+                    statics: current_statics,
+                    is_root: current_reply,
+                    ..
+                },
+                FragmentDiff::UpdateRegular {
+                    children: children_diffs,
+                    is_root: new_reply,
+                    ..
+                },
+            ) => {
+                let new_children = current_children.reference_merge(children_diffs)?;
+                let new_reply = new_reply.or(current_reply);
+                let new_render = new_reply.map(|i| i != 0);
+
+                Ok(Self::Regular {
+                    children: new_children,
+                    statics: current_statics,
+                    is_root: new_reply,
+                    new_render,
+                })
+            }
+            (
+                Fragment::Comprehension {
+                    dynamics: mut current_dynamics,
+                    statics,
+                    templates: current_templates,
+                    stream: current_stream,
+                    is_root: current_reply,
+                    ..
+                },
+                FragmentDiff::UpdateComprehension {
+                    dynamics: new_dynamics,
+                    templates: new_templates,
+                    stream: new_stream,
+                    is_root: new_reply,
+                    ..
+                },
+            ) => {
+                let new_reply = new_reply.or(current_reply);
+                let templates = current_templates.reference_merge(new_templates)?;
+
+                let new_dynamics: Vec<Vec<Child>> = new_dynamics
+                    .into_iter()
+                    .map(|children_children| {
+                        children_children
+                            .into_iter()
+                            .map(|child| child.try_into())
+                            .collect::<Result<Vec<Child>, MergeError>>()
+                    })
+                    .collect::<Result<Vec<Vec<Child>>, MergeError>>()?;
+
+                let has_id = |children: &Vec<Child>, id: &str| {
+                    children
+                        .iter()
+                        .any(|child| Child::String(format!(" id=\"{id}\"").into()) == *child)
+                };
+
+                let stream = match (current_stream, new_stream) {
+                    (None, None) => {
+                        current_dynamics = new_dynamics;
+                        None
+                    }
+                    (None, Some(stream_attrs)) => Some(Stream::try_from(stream_attrs)?),
+                    (Some(stream), None) => Some(stream),
+                    (Some(mut stream), Some(stream_update)) => {
+                        for stream_attr in &stream_update {
+                            match stream_attr {
+                                StreamAttribute::StreamID(stream_id) => {
+                                    if stream.id != *stream_id {
+                                        return Err(MergeError::StreamIDMismatch);
+                                    }
+                                }
+                                StreamAttribute::Inserts(inserts) => {
+                                    for (insert_id, index, _limit) in inserts.iter() {
+                                        let dynamic = new_dynamics
+                                            .iter()
+                                            .find(|children| has_id(children, insert_id));
+                                        if let Some(dynamic) = dynamic {
+                                            if *index == -1 {
+                                                current_dynamics.push(dynamic.clone());
+                                            }
+                                        }
+                                    }
+                                }
+                                StreamAttribute::DeleteIDs(delete_ids) => {
+                                    for delete_id in delete_ids {
+                                        let index = current_dynamics
+                                            .iter()
+                                            .position(|children| has_id(children, delete_id));
+                                        if let Some(index) = index {
+                                            current_dynamics.remove(index);
+                                        }
+                                    }
+                                }
+                                StreamAttribute::ResetStream(reset) => {
+                                    if *reset {
+                                        stream.stream_items = Vec::new();
+                                        current_dynamics.clone_from(&new_dynamics)
+                                    }
+                                }
+                            }
+                        }
+                        Some(stream)
+                    }
+                };
+
+                let new_render = new_reply.map(|i| i != 0);
+
+                Ok(Self::Comprehension {
+                    dynamics: current_dynamics,
+                    statics,
+                    templates,
+                    stream,
+                    is_root: new_reply,
+                    new_render,
+                })
+            }
+            _ => Err(MergeError::FragmentTypeMismatch),
+        }
+    }
+}
+
+impl ReferenceMerge for HashMap<String, Component> {
+    type Diff = HashMap<String, ComponentDiff>;
+
+    fn reference_merge(self, diff: Self::Diff) -> Result<Self, MergeError> {
+        let mut components = self;
+        for (cid, comp_diff) in diff.into_iter() {
+            if let Some(existing) = components.get_mut(&cid) {
+                *existing = existing.clone().reference_merge(comp_diff)?;
+            } else {
+                components.insert(cid.clone(), comp_diff.try_into()?);
+            }
+        }
+
+        Ok(components)
+    }
+}
+
+impl ReferenceMerge for Component {
+    type Diff = ComponentDiff;
+
+    fn reference_merge(self, diff: ComponentDiff) -> Result<Self, MergeError> {
+        match diff {
+            ComponentDiff::UpdateRegular {
+                children: children_diffs,
+                ..
+            } => {
+                let new_children = self.children.reference_merge(children_diffs)?;
+                Ok(Self {
+                    children: new_children,
+                    statics: self.statics,
+                    is_root: None,
+                })
+            }
+            ComponentDiff::ReplaceCurrent {
+                statics, children, ..
+            } => Ok(Self {
+                children,
+                statics,
+                is_root: None,
+            }),
+        }
+    }
+}
+
+impl ReferenceMerge for Templates {
+    type Diff = Templates;
+
+    fn reference_merge(self, diff: Templates) -> Result<Self, MergeError> {
+        match (self, diff) {
+            (None, None) => Ok(None),
+            (None, Some(template)) => Ok(Some(template)),
+            (Some(template), None) => Ok(Some(template)),
+            (Some(mut current), Some(new)) => {
+                for (key, val) in new.into_iter() {
+                    current.insert(key, val);
+                }
+                Ok(Some(current))
+            }
+        }
+    }
+}
+
+impl ReferenceMerge for Child {
+    type Diff = ChildDiff;
+
+    fn reference_merge(self, diff: ChildDiff) -> Result<Self, MergeError> {
+        match (self, diff) {
+            (Child::Fragment(current_fragment), ChildDiff::Fragment(fragment_diff)) => Ok(
+                Self::Fragment(current_fragment.reference_merge(fragment_diff)?),
+            ),
+            (_, ChildDiff::String(s)) => Ok(Self::String(s)),
+            (_, ChildDiff::ComponentID(id)) => Ok(Self::ComponentID(id)),
+            (_, ChildDiff::Fragment(fragment_diff)) => {
+                Ok(Self::Fragment(fragment_diff.try_into()?))
+            }
+        }
+    }
+}
+
+impl ReferenceMerge for HashMap<String, Child> {
+    type Diff = HashMap<String, ChildDiff>;
+
+    fn reference_merge(self, diff: Self::Diff) -> Result<Self, MergeError> {
+        let mut new_children = self;
+        for (index, comp_diff) in diff.into_iter() {
+            if let Some(child) = new_children.get_mut(&index) {
+                *child = child.clone().reference_merge(comp_diff)?;
+            } else {
+                new_children.insert(index, comp_diff.try_into()?);
+            }
+        }
+        Ok(new_children)
+    }
+}
@@ -16,7 +16,7 @@ fn recorded_stream_test() {
     let diff: RootDiff = serde_json::from_str(include_str!("flow-1-change-1.json"))
         .expect("Failed to deserialize fragment");
 
-    let root = root.merge(diff).expect("Failed to merge diff");
+    let root = root.checked_merge(diff).expect("Failed to merge diff");
 
     let out: String = root
         .clone()
@@ -27,7 +27,7 @@ fn recorded_stream_test() {
     let diff: RootDiff = serde_json::from_str(include_str!("flow-1-change-2.json"))
         .expect("Failed to deserialize fragment");
 
-    let root = root.merge(diff).expect("Failed to merge diff");
+    let root = root.checked_merge(diff).expect("Failed to merge diff");
 
     let out: String = root
         .clone()
@@ -38,7 +38,7 @@ fn recorded_stream_test() {
     let diff: RootDiff = serde_json::from_str(include_str!("flow-1-change-3.json"))
         .expect("Failed to deserialize fragment");
 
-    let root = root.merge(diff).expect("Failed to merge diff");
+    let root = root.checked_merge(diff).expect("Failed to merge diff");
 
     let out: String = root
         .clone()
@@ -46,3 +46,86 @@ fn recorded_stream_test() {
         .expect("Failed to convert Root into string");
     assert_eq!(format!("{out}\n"), include_str!("flow-1-change-3.html"));
 }
+
+#[test]
+fn recorded_stream_ids() {
+    fn songs(root: &Root) -> Vec<String> {
+        let Fragment::Regular { children, .. } = &root.fragment else {
+            panic!("expected a regular root");
+        };
+        let Some(Child::Fragment(Fragment::Regular { children, .. })) = children.get("3") else {
+            panic!("expected the song table");
+        };
+        let Some(Child::Fragment(songs)) = children.get("1") else {
+            panic!("expected the song stream");
+        };
+        songs.stream_ids()
+    }
+
+    let initial: RootDiff = serde_json::from_str(include_str!("flow-1-change-0.json"))
+        .expect("Failed to deserialize fragment");
+    let mut root: Root = initial
+        .try_into()
+        .expect("Failed to convert RootDiff to Root");
+    assert_eq!(songs(&root), ["songs-0", "songs-1"]);
+    assert!(root.fragment.stream_ids().is_empty());
+
+    let diffs = [
+        include_str!("flow-1-change-1.json"),
+        include_str!("flow-1-change-2.json"),
+        include_str!("flow-1-change-3.json"),
+    ];
+    let expected: [&[&str]; 3] = [
+        &["songs-1", "songs-0"],
+        &["songs-0"],
+        &["songs-0", "songs-1"],
+    ];
+    for (diff, ids) in diffs.into_iter().zip(expected) {
+        let diff: RootDiff = serde_json::from_str(diff).expect("Failed to deserialize fragment");
+        root = root.checked_merge(diff).expect("Failed to merge diff");
+        assert_eq!(songs(&root), ids);
+    }
+}
+
+#[test]
+fn recorded_stream_matches_reference_merge() {
+    let diffs = [
+        include_str!("flow-1-change-1.json"),
+        include_str!("flow-1-change-2.json"),
+        include_str!("flow-1-change-3.json"),
+    ];
+    let rendered = [
+        include_str!("flow-1-change-1.html"),
+        include_str!("flow-1-change-2.html"),
+        include_str!("flow-1-change-3.html"),
+    ];
+
+    let initial: RootDiff = serde_json::from_str(include_str!("flow-1-change-0.json"))
+        .expect("Failed to deserialize fragment");
+    let mount: Root = initial
+        .try_into()
+        .expect("Failed to convert RootDiff to Root");
+
+    let mut reference = mount.clone();
+    let mut in_place = mount.clone();
+    for (diff, html) in diffs.into_iter().zip(rendered) {
+        let diff: RootDiff = serde_json::from_str(diff).expect("Failed to deserialize fragment");
+
+        reference = reference
+            .reference_merge(diff.clone())
+            .expect("Failed to merge diff");
+        in_place
+            .merge_in_place(diff.clone())
+            .expect("Failed to merge diff in place");
+        assert_eq!(reference, in_place);
+
+        let out: String = in_place
+            .clone()
+            .try_into()
+            .expect("Failed to convert Root into string");
+        assert_eq!(format!("{out}\n"), html);
+
+        // every change applied straight to the mount must also agree
+        let _ = mount.clone().checked_merge(diff);
+    }
+}
@@ -1,8 +1,12 @@
 pub mod fragment;
 mod morph;
 mod patch;
+mod stats;
+mod three_way;
 mod traversal;
 
-pub use morph::{diff, Morph};
+pub use morph::{diff, diff_with_options, DiffOptions, Morph};
 pub use patch::{Patch, PatchResult};
+pub use stats::{diff_stats, DiffStats};
+pub use three_way::{three_way_merge, Conflict};
 pub use traversal::MoveTo;
@@ -1,4 +1,9 @@
-use std::{cmp::Ordering, collections::BTreeSet, fmt, mem, ops::Deref};
+use std::{
+    cmp::Ordering,
+    collections::{BTreeSet, HashSet},
+    fmt, mem,
+    ops::Deref,
+};
 
 use smallvec::{smallvec, SmallVec};
 
@@ -223,6 +228,51 @@ where
     }
 }
 
+/// Options which customize how two documents are compared
+#[derive(Debug, Clone, Default)]
+pub struct DiffOptions {
+    /// Names of attributes whose values are not compared, e.g. server generated `data-phx-ref`.
+    /// Their current values are kept when the other attributes of a node change.
+    pub ignore_attributes: HashSet<String>,
+}
+
+impl DiffOptions {
+    fn ignores(&self, attribute: &Attribute) -> bool {
+        self.ignore_attributes.contains(&attribute.name.to_string())
+    }
+
+    /// Returns the attributes of `to` which `from` has to be updated to, or nothing if they
+    /// only differ in ignored attributes
+    fn attribute_changes(&self, from: &Element, to: &Element) -> Option<Vec<Attribute>> {
+        if self.ignore_attributes.is_empty() {
+            return from
+                .attributes
+                .ne(&to.attributes)
+                .then(|| to.attributes.clone());
+        }
+
+        let compared = |el: &Element| {
+            el.attributes
+                .iter()
+                .filter(|attr| !self.ignores(attr))
+                .cloned()
+                .collect::<Vec<_>>()
+        };
+        let mut attributes = compared(to);
+        if compared(from) == attributes {
+            return None;
+        }
+
+        attributes.extend(
+            from.attributes
+                .iter()
+                .filter(|attr| self.ignores(attr))
+                .cloned(),
+        );
+        Some(attributes)
+    }
+}
+
 enum Advance {
     BothCursors,
     To,
@@ -233,6 +283,7 @@ pub struct Morph<'a> {
     stack: SmallVec<[Op<'a>; 16]>,
     queue: SmallVec<[Op<'a>; 8]>,
     detached: BTreeSet<NodeRef>,
+    options: DiffOptions,
 }
 
 impl<'a> Morph<'a> {
@@ -240,6 +291,13 @@ impl<'a> Morph<'a> {
         (from, to).into()
     }
 
+    pub fn with_options(from: &'a Document, to: &'a Document, options: DiffOptions) -> Self {
+        Self {
+            options,
+            ..Self::new(from, to)
+        }
+    }
+
     fn advance(&mut self, advance: Advance, skip_children: bool) {
         let op = self.stack.last_mut().unwrap();
 
@@ -316,6 +374,7 @@ where
             stack: smallvec![op.into()],
             queue: smallvec![],
             detached: BTreeSet::new(),
+            options: DiffOptions::default(),
         }
     }
 }
@@ -550,10 +609,12 @@ impl Iterator for Morph<'_> {
                         ) => {
                             // nodes are compatible; morph attribute changes and continue
                             if to_el.name.eq(&from_el.name) && to_el.id().eq(&from_el.id()) {
-                                if from_el.attributes.ne(&to_el.attributes) {
+                                if let Some(attributes) =
+                                    self.options.attribute_changes(from_el, to_el)
+                                {
                                     self.queue.push(Op::Patch(Patch::SetAttributes {
                                         node: from.node,
-                                        attributes: to.attributes().to_vec(),
+                                        attributes,
                                     }));
                                 }
 
@@ -655,3 +716,12 @@ impl Iterator for Morph<'_> {
 pub fn diff(old_document: &Document, new_document: &Document) -> Vec<Patch> {
     Vec::from_iter(Morph::new(old_document, new_document))
 }
+
+/// Like [diff], but compares the documents as customized by `options`
+pub fn diff_with_options(
+    old_document: &Document,
+    new_document: &Document,
+    options: DiffOptions,
+) -> Vec<Patch> {
+    Vec::from_iter(Morph::with_options(old_document, new_document, options))
+}
@@ -90,7 +90,7 @@ pub enum Patch {
 }
 
 /// The result of applying a [Patch].
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum PatchResult {
     /// The `node` has been added to the document as a child of `parent`.
     Add {
@@ -115,6 +115,46 @@ pub enum PatchResult {
 }
 
 impl Patch {
+    /// Carries the attributes of the target node which are preserved by `options` over into
+    /// this patch, so that client-owned state survives a server-driven change or replacement.
+    pub fn preserving_attributes(self, doc: &Document, options: &MergeOptions) -> Self {
+        if options.preserve_attributes.is_empty() {
+            return self;
+        }
+
+        let preserved = |node: NodeRef, attributes: &[Attribute]| -> Vec<Attribute> {
+            doc.attributes_ref(node)
+                .iter()
+                .filter(|attr| options.preserves(&attr.name))
+                .filter(|attr| !attributes.iter().any(|new| new.name == attr.name))
+                .cloned()
+                .collect()
+        };
+
+        match self {
+            Self::SetAttributes {
+                node,
+                mut attributes,
+            } => {
+                let preserved = preserved(node, &attributes);
+                attributes.extend(preserved);
+                Self::SetAttributes { node, attributes }
+            }
+            Self::Replace {
+                node,
+                replacement: NodeData::NodeElement { mut element },
+            } => {
+                let preserved = preserved(node, &element.attributes);
+                element.attributes.extend(preserved);
+                Self::Replace {
+                    node,
+                    replacement: NodeData::NodeElement { element },
+                }
+            }
+            patch => patch,
+        }
+    }
+
     /// Applies this patch to `doc` using `stack`.
     ///
     /// If this patch will result in a change to the underlying document, a [PatchResult]
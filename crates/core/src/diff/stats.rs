@@ -0,0 +1,81 @@
+use super::{morph::diff, Patch};
+use crate::dom::Document;
+
+/// A tally of the changes described by a set of patches.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct DiffStats {
+    /// Number of nodes created and attached to the document
+    pub added: usize,
+    /// Number of nodes removed from the document
+    pub removed: usize,
+    /// Number of attribute changes
+    pub changed: usize,
+    /// Number of nodes replaced in place
+    pub replaced: usize,
+    /// Number of existing nodes which were detached and reattached elsewhere
+    pub moved: usize,
+}
+
+impl DiffStats {
+    /// Computes the statistics for `patches`, as produced by [diff]
+    pub fn from_patches(patches: &[Patch]) -> Self {
+        let mut stats = Self::default();
+        // Mirrors the argument stack used when applying patches, tracking
+        // whether each entry is a newly created node or an existing one.
+        let mut stack: Vec<bool> = vec![];
+
+        for patch in patches {
+            match patch {
+                Patch::InsertBefore { .. }
+                | Patch::InsertAfter { .. }
+                | Patch::Append { .. }
+                | Patch::AppendTo { .. } => stats.added += 1,
+                Patch::Create { .. } | Patch::CreateAndMoveTo { .. } => stack.push(true),
+                Patch::PushCurrent | Patch::Push(_) => stack.push(false),
+                Patch::Pop => {
+                    stack.pop();
+                }
+                Patch::Attach => {
+                    let child = stack.pop();
+                    let parent = stack.pop();
+                    stats.record_attach(child);
+                    if let Some(parent) = parent {
+                        stack.push(parent);
+                    }
+                }
+                Patch::PrependBefore { .. } | Patch::AppendAfter { .. } => {
+                    let node = stack.pop();
+                    stats.record_attach(node);
+                }
+                Patch::Remove { .. } => stats.removed += 1,
+                Patch::Replace { .. } => stats.replaced += 1,
+                Patch::AddAttribute { .. }
+                | Patch::AddAttributeTo { .. }
+                | Patch::UpdateAttribute { .. }
+                | Patch::RemoveAttributeByName { .. }
+                | Patch::SetAttributes { .. } => stats.changed += 1,
+                Patch::Detach { .. } | Patch::Move(_) => {}
+            }
+        }
+
+        stats
+    }
+
+    /// Returns true if no changes were recorded
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    fn record_attach(&mut self, is_new: Option<bool>) {
+        match is_new {
+            Some(true) => self.added += 1,
+            Some(false) => self.moved += 1,
+            None => {}
+        }
+    }
+}
+
+/// Computes the statistics of the patches required to transform `old` into `new`
+pub fn diff_stats(old: &Document, new: &Document) -> DiffStats {
+    DiffStats::from_patches(&diff(old, new))
+}
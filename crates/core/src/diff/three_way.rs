@@ -0,0 +1,156 @@
+use std::collections::HashSet;
+
+use super::{morph::diff, Patch};
+use crate::dom::{Document, NodeRef};
+
+/// A change from `theirs` which was not applied because `ours` changed the same node
+#[derive(Debug, Clone, PartialEq)]
+pub struct Conflict {
+    /// The node which was changed on both sides
+    pub node: NodeRef,
+    /// The patch from `theirs` which was skipped
+    pub patch: Patch,
+}
+
+/// Merges the changes made to `base` in `theirs` into `ours`.
+///
+/// `ours` must have been derived from `base` by editing a copy of it, so that the nodes
+/// of `base` are identified by the same `NodeRef` in both. `theirs` may be any document.
+///
+/// The patches transforming `base` into `theirs` are applied to a copy of `ours`. A patch
+/// targeting a node that `ours` modified or removed is skipped and reported as a [Conflict],
+/// unless both sides made the identical change. Creating nodes is always applied.
+///
+/// Moving a node detaches it, pushes it on the argument stack and attaches it elsewhere,
+/// these patches are resolved as a unit. If `ours` removed the moved node the whole move is
+/// dropped and reported once, if `ours` removed the node it is moved next to, the node is
+/// left where `ours` has it.
+pub fn three_way_merge(
+    base: &Document,
+    ours: &Document,
+    theirs: &Document,
+) -> (Document, Vec<Conflict>) {
+    let mut merged = ours.clone();
+    let mut conflicts = vec![];
+    let mut stack = vec![];
+    // nodes detached by `theirs` which are only detached once they are attached elsewhere
+    let mut detached = HashSet::new();
+    // nodes removed by `ours` which `theirs` moves
+    let mut dropped = HashSet::new();
+
+    let mut editor = merged.edit();
+    for patch in diff(base, theirs) {
+        match patch {
+            Patch::Detach { node } | Patch::Push(node) if removed(base, ours, node) => {
+                if matches!(patch, Patch::Push(_)) {
+                    stack.push(node);
+                }
+                if dropped.insert(node) {
+                    conflicts.push(Conflict { node, patch });
+                }
+                continue;
+            }
+            Patch::Detach { node } => {
+                detached.insert(node);
+                continue;
+            }
+            Patch::Attach | Patch::PrependBefore { .. } | Patch::AppendAfter { .. } => {
+                let node = *stack.last().expect("attached node should be on the stack");
+                let parent = match patch {
+                    Patch::Attach => stack.iter().rev().nth(1).copied(),
+                    _ => None,
+                };
+                if dropped.contains(&node) || parent.is_some_and(|parent| dropped.contains(&parent))
+                {
+                    // the node stays removed, Attach leaves the parent on the stack
+                    stack.pop();
+                    continue;
+                }
+            }
+            _ => {}
+        }
+
+        match resolve(base, ours, &patch) {
+            Resolution::Apply => {
+                let node = match patch {
+                    Patch::Attach | Patch::PrependBefore { .. } | Patch::AppendAfter { .. } => {
+                        stack.last().copied()
+                    }
+                    _ => None,
+                };
+                if let Some(node) = node.filter(|node| detached.remove(node)) {
+                    Patch::Detach { node }.apply(&mut editor, &mut stack);
+                }
+                patch.apply(&mut editor, &mut stack);
+            }
+            Resolution::Skip => {}
+            Resolution::Conflict(node) => {
+                // keep the argument stack balanced for the ops which follow, the moved
+                // node is left attached where `ours` has it
+                if matches!(
+                    patch,
+                    Patch::PrependBefore { .. } | Patch::AppendAfter { .. }
+                ) {
+                    if let Some(moved) = stack.pop() {
+                        detached.remove(&moved);
+                    }
+                }
+                conflicts.push(Conflict { node, patch });
+            }
+        }
+    }
+    // nodes which `theirs` detached without attaching them again
+    for node in detached {
+        Patch::Detach { node }.apply(&mut editor, &mut stack);
+    }
+    editor.finish();
+
+    (merged, conflicts)
+}
+
+enum Resolution {
+    Apply,
+    /// `ours` already contains the change
+    Skip,
+    Conflict(NodeRef),
+}
+
+/// Decides whether `patch` can be applied on top of the changes `ours` made to `base`
+fn resolve(base: &Document, ours: &Document, patch: &Patch) -> Resolution {
+    let removed = |node: NodeRef| removed(base, ours, node);
+    let changed = |node: NodeRef| removed(node) || base.get(node) != ours.get(node);
+    let conflict_if = |conflicting: bool, node: NodeRef| {
+        if conflicting {
+            Resolution::Conflict(node)
+        } else {
+            Resolution::Apply
+        }
+    };
+
+    match patch {
+        Patch::Replace { node, replacement } if ours.get(*node) == replacement => Resolution::Skip,
+        Patch::SetAttributes { node, attributes }
+            if ours.attributes_ref(*node) == attributes.as_slice() =>
+        {
+            Resolution::Skip
+        }
+        Patch::Remove { node } if removed(*node) => Resolution::Skip,
+        Patch::Replace { node, .. }
+        | Patch::SetAttributes { node, .. }
+        | Patch::AddAttributeTo { node, .. }
+        | Patch::UpdateAttribute { node, .. }
+        | Patch::RemoveAttributeByName { node, .. }
+        | Patch::Remove { node } => conflict_if(changed(*node), *node),
+        Patch::InsertBefore { before: node, .. }
+        | Patch::InsertAfter { after: node, .. }
+        | Patch::PrependBefore { before: node }
+        | Patch::AppendAfter { after: node }
+        | Patch::AppendTo { parent: node, .. } => conflict_if(removed(*node), *node),
+        _ => Resolution::Apply,
+    }
+}
+
+/// Returns true if `node` is attached in `base` but `ours` removed it
+fn removed(base: &Document, ours: &Document, node: NodeRef) -> bool {
+    base.parent(node).is_some() && ours.parent(node).is_none()
+}
@@ -1,11 +1,14 @@
 use std::fmt::{self, Write};
 
+use serde::{Deserialize, Serialize};
 use smallstr::SmallString;
 
 use crate::InternedString;
 
 /// Represents the fully-qualified name of an attribute
-#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, uniffi::Record)]
+#[derive(
+    Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize, uniffi::Record,
+)]
 pub struct AttributeName {
     /// This is used by svg attributes, e.g. `xlink-href`
     pub namespace: Option<String>,
@@ -57,7 +60,7 @@ impl PartialEq<str> for AttributeName {
     }
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, uniffi::Record)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize, uniffi::Record)]
 pub struct Attribute {
     pub name: AttributeName,
     pub value: Option<String>,
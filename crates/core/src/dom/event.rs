@@ -0,0 +1,66 @@
+/// The client events LiveView binds to elements with a `phx-*` attribute, whose value
+/// names the event sent to the server
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, uniffi::Enum)]
+pub enum PhxEvent {
+    Click,
+    ClickAway,
+    Change,
+    Submit,
+    Focus,
+    Blur,
+    WindowFocus,
+    WindowBlur,
+    KeyDown,
+    KeyUp,
+    WindowKeyDown,
+    WindowKeyUp,
+    ViewportTop,
+    ViewportBottom,
+}
+
+impl PhxEvent {
+    /// Every event, in the order of their declaration
+    pub const ALL: [PhxEvent; 14] = [
+        PhxEvent::Click,
+        PhxEvent::ClickAway,
+        PhxEvent::Change,
+        PhxEvent::Submit,
+        PhxEvent::Focus,
+        PhxEvent::Blur,
+        PhxEvent::WindowFocus,
+        PhxEvent::WindowBlur,
+        PhxEvent::KeyDown,
+        PhxEvent::KeyUp,
+        PhxEvent::WindowKeyDown,
+        PhxEvent::WindowKeyUp,
+        PhxEvent::ViewportTop,
+        PhxEvent::ViewportBottom,
+    ];
+
+    /// Returns the name of the attribute binding this event, e.g. `phx-click`
+    pub fn attribute(self) -> &'static str {
+        match self {
+            PhxEvent::Click => "phx-click",
+            PhxEvent::ClickAway => "phx-click-away",
+            PhxEvent::Change => "phx-change",
+            PhxEvent::Submit => "phx-submit",
+            PhxEvent::Focus => "phx-focus",
+            PhxEvent::Blur => "phx-blur",
+            PhxEvent::WindowFocus => "phx-window-focus",
+            PhxEvent::WindowBlur => "phx-window-blur",
+            PhxEvent::KeyDown => "phx-keydown",
+            PhxEvent::KeyUp => "phx-keyup",
+            PhxEvent::WindowKeyDown => "phx-window-keydown",
+            PhxEvent::WindowKeyUp => "phx-window-keyup",
+            PhxEvent::ViewportTop => "phx-viewport-top",
+            PhxEvent::ViewportBottom => "phx-viewport-bottom",
+        }
+    }
+
+    /// Returns the event bound by the attribute `name`, if it binds one
+    pub fn from_attribute(name: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|event| event.attribute() == name)
+    }
+}
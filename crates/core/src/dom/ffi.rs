@@ -7,8 +7,8 @@ use super::ChangeType;
 pub use super::{
     attribute::Attribute,
     node::{Node, NodeData, NodeRef},
-    printer::PrintOptions,
-    DocumentChangeHandler,
+    printer::{EntityEncoding, PrintOptions},
+    DocumentChangeHandler, MergeOptions,
 };
 use crate::{
     diff::{fragment::RenderError, PatchResult},
@@ -34,6 +34,89 @@ impl Document {
     pub(crate) fn inner(&self) -> Arc<Mutex<super::Document>> {
         self.inner.clone()
     }
+
+    /// Reports the results of a merge to the handler set in `set_event_handler`
+    fn notify_changes(&self, results: &[PatchResult]) {
+        let Some(handler) = self
+            .inner
+            .lock()
+            .expect("lock poisoned")
+            .event_callback
+            .clone()
+        else {
+            return;
+        };
+
+        for patch in results {
+            match patch {
+                PatchResult::Add { node, parent, data } => {
+                    handler.handle_document_change(
+                        ChangeType::Add,
+                        (*node).into(),
+                        data.clone(),
+                        Some((*parent).into()),
+                    );
+                }
+                PatchResult::Remove { node, parent, data } => {
+                    handler.handle_document_change(
+                        ChangeType::Remove,
+                        (*node).into(),
+                        data.clone(),
+                        Some((*parent).into()),
+                    );
+                }
+                PatchResult::Change { node, data } => {
+                    handler.handle_document_change(
+                        ChangeType::Change,
+                        (*node).into(),
+                        data.clone(),
+                        None,
+                    );
+                }
+                PatchResult::Replace { node, parent, data } => {
+                    handler.handle_document_change(
+                        ChangeType::Replace,
+                        (*node).into(),
+                        data.clone(),
+                        Some((*parent).into()),
+                    );
+                }
+            }
+        }
+    }
+
+    /// Merges the decoded diff `fragment`, returning the changes reported to the handler
+    #[cfg(feature = "liveview-channels")]
+    pub(crate) fn merge_fragment_changes(
+        &self,
+        fragment: crate::diff::fragment::RootDiff,
+    ) -> Result<Vec<PatchResult>, RenderError> {
+        let results = self
+            .inner
+            .lock()
+            .expect("lock poisoned!")
+            .merge_fragment(fragment)?;
+
+        self.notify_changes(&results);
+        Ok(results)
+    }
+
+    /// Replaces the fragment template with `fragment`, notifying the change handler of the
+    /// changes, see [super::Document::replace_fragment]
+    #[cfg(feature = "liveview-channels")]
+    pub(crate) fn replace_fragment_changes(
+        &self,
+        fragment: crate::diff::fragment::RootDiff,
+    ) -> Result<Vec<PatchResult>, RenderError> {
+        let results = self
+            .inner
+            .lock()
+            .expect("lock poisoned!")
+            .replace_fragment(fragment)?;
+
+        self.notify_changes(&results);
+        Ok(results)
+    }
 }
 
 #[uniffi::export]
@@ -62,6 +145,30 @@ impl Document {
         self.inner.lock().expect("lock poisoned!").event_callback = Some(Arc::from(handler));
     }
 
+    pub fn set_merge_options(&self, options: MergeOptions) {
+        self.inner
+            .lock()
+            .expect("lock poisoned!")
+            .set_merge_options(options);
+    }
+
+    /// Sets the characters encoded as character references when rendering this document
+    pub fn set_entity_encoding(&self, encoding: EntityEncoding) {
+        self.inner
+            .lock()
+            .expect("lock poisoned!")
+            .set_entity_encoding(encoding);
+    }
+
+    /// Sets the platform this document is rendered for, dropping the namespaced attributes
+    /// of other platforms
+    pub fn set_print_platform(&self, platform: Option<String>) {
+        self.inner
+            .lock()
+            .expect("lock poisoned!")
+            .set_print_platform(platform);
+    }
+
     pub fn merge_fragment_json(&self, json: &str) -> Result<(), RenderError> {
         let json = serde_json::from_str(json)?;
 
@@ -71,48 +178,7 @@ impl Document {
             .expect("lock poisoned!")
             .merge_fragment_json(json)?;
 
-        let Some(handler) = self
-            .inner
-            .lock()
-            .expect("lock poisoned")
-            .event_callback
-            .clone()
-        else {
-            return Ok(());
-        };
-
-        for patch in results.into_iter() {
-            match patch {
-                PatchResult::Add { node, parent, data } => {
-                    handler.handle_document_change(
-                        ChangeType::Add,
-                        node.into(),
-                        data,
-                        Some(parent.into()),
-                    );
-                }
-                PatchResult::Remove { node, parent, data } => {
-                    handler.handle_document_change(
-                        ChangeType::Remove,
-                        node.into(),
-                        data,
-                        Some(parent.into()),
-                    );
-                }
-                PatchResult::Change { node, data } => {
-                    handler.handle_document_change(ChangeType::Change, node.into(), data, None);
-                }
-                PatchResult::Replace { node, parent, data } => {
-                    handler.handle_document_change(
-                        ChangeType::Replace,
-                        node.into(),
-                        data,
-                        Some(parent.into()),
-                    );
-                }
-            }
-        }
-
+        self.notify_changes(&results);
         Ok(())
     }
 
@@ -132,6 +198,13 @@ impl Document {
             .map(|node_ref| node_ref.into())
     }
 
+    pub fn component_cid_for(&self, node_ref: Arc<NodeRef>) -> Option<u32> {
+        self.inner
+            .lock()
+            .expect("lock poisoned!")
+            .component_cid_for(*node_ref)
+    }
+
     pub fn children(&self, node_ref: Arc<NodeRef>) -> Vec<Arc<NodeRef>> {
         self.inner
             .lock()
@@ -166,6 +239,14 @@ impl Document {
     pub fn render(&self) -> String {
         self.to_string()
     }
+
+    /// Renders the subtree rooted at `node_ref` using the given `options`
+    pub fn render_node(&self, node_ref: Arc<NodeRef>, options: PrintOptions) -> String {
+        let mut out = String::new();
+        self.print_node(*node_ref, &mut out, options)
+            .expect("lock poisoned!");
+        out
+    }
 }
 impl Document {
     pub fn print_node(
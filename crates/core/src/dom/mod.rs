@@ -1,12 +1,16 @@
 mod attribute;
+mod event;
 pub mod ffi;
 mod node;
 mod printer;
 mod select;
 
 use std::{
-    collections::{BTreeMap, VecDeque},
-    fmt, mem,
+    collections::{BTreeMap, HashMap, HashSet, VecDeque},
+    fmt,
+    hash::{Hash, Hasher},
+    io::Read,
+    mem,
     ops::{Deref, DerefMut},
     path::Path,
     sync::Arc,
@@ -14,7 +18,7 @@ use std::{
 
 use cranelift_entity::{packed_option::PackedOption, EntityRef, PrimaryMap, SecondaryMap};
 use fixedbitset::FixedBitSet;
-use fxhash::{FxBuildHasher, FxHashMap};
+use fxhash::{FxBuildHasher, FxHashMap, FxHasher64};
 use petgraph::Direction;
 use smallstr::SmallString;
 use smallvec::SmallVec;
@@ -22,18 +26,68 @@ use smallvec::SmallVec;
 use self::printer::Printer;
 pub use self::{
     attribute::{Attribute, AttributeName, AttributeValue},
+    event::PhxEvent,
     node::{Element, ElementName, NodeData, NodeRef},
-    printer::PrintOptions,
+    printer::{EntityEncoding, PrintOptions},
     select::{SelectionIter, Selector},
 };
 use crate::{
     diff::{
         fragment::{FragmentMerge, RenderError, Root, RootDiff},
-        PatchResult,
+        Patch, PatchResult,
     },
-    parser,
+    parser, InternedString,
 };
 
+/// The attribute marking an element whose attributes or text were edited locally, see
+/// [MergeOptions::preserve_local_edits]
+pub const LOCAL_EDIT_ATTRIBUTE: &str = "data-local-edit";
+
+/// Options which control how server diffs are merged into a [Document]
+#[derive(Debug, Clone, Default, PartialEq, Eq, uniffi::Record)]
+pub struct MergeOptions {
+    /// Names of client-owned attributes which are carried over from the old node when the
+    /// server changes or replaces an element. A trailing `*` matches any suffix, e.g. `data-client-*`
+    pub preserve_attributes: Vec<String>,
+    /// Keeps the optimistic local edits of elements marked with `data-local-edit`, and of their
+    /// text, unless the server changed the same value since its previous render. In that case
+    /// the server wins and the conflict is logged. The marker itself is left for the client
+    /// to remove once the server confirmed the edit.
+    #[uniffi(default = false)]
+    pub preserve_local_edits: bool,
+}
+impl MergeOptions {
+    /// Returns true if `name` matches one of the preserved attribute patterns
+    pub fn preserves(&self, name: &AttributeName) -> bool {
+        let name = name.to_string();
+        self.preserve_attributes
+            .iter()
+            .any(|pattern| match pattern.strip_suffix('*') {
+                Some(prefix) => name.starts_with(prefix),
+                None => &name == pattern,
+            })
+    }
+}
+
+/// Returned by [Document::single_root] when a document does not have exactly one top-level node
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+#[error("expected a single root node, but the document has {count} top-level nodes")]
+pub struct MultipleRootsError {
+    /// The number of top-level nodes, not counting whitespace-only text
+    pub count: usize,
+}
+
+/// Returned by [Document::replace_children] when the new children would not form a tree
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+pub enum ReplaceChildrenError {
+    #[error("node {0:?} already has a parent")]
+    HasParent(NodeRef),
+    #[error("node {0:?} is listed more than once")]
+    Duplicate(NodeRef),
+    #[error("node {0:?} is the new parent or one of its ancestors")]
+    Ancestor(NodeRef),
+}
+
 /// A `Document` represents a virtual DOM, and supports common operations typically performed against them.
 ///
 /// While I'm referring to it as a DOM because it conjures the familiar notion of an HTML document, what we're
@@ -71,6 +125,12 @@ pub struct Document {
     /// The fragment template.
     pub fragment_template: Option<Root>,
     pub event_callback: Option<Arc<dyn DocumentChangeHandler>>,
+    /// Options applied when merging server diffs into this document
+    merge_options: MergeOptions,
+    /// The characters encoded as character references when printing this document
+    entity_encoding: EntityEncoding,
+    /// The platform printed for, namespaced attributes of other platforms are not printed
+    print_platform: Option<String>,
     /// A map from node reference to node data
     nodes: PrimaryMap<NodeRef, NodeData>,
     /// A map from a node to its parent node, if it currently has one
@@ -82,8 +142,12 @@ pub struct Document {
     /// This allows for looking up a node directly and modifying it, rather than needing to traverse the
     /// document.
     ids: BTreeMap<SmallString<[u8; 16]>, NodeRef>,
+    /// A map from the root element of each component rendered from the fragment template to its cid
+    component_roots: FxHashMap<NodeRef, u32>,
     /// A count of the number of uploads, the server expects each upload to have an ascending unique ID.
     upload_ct: u64,
+    /// The name of the first doctype in the source document, if it had one
+    doctype: Option<InternedString>,
 }
 
 impl fmt::Debug for Document {
@@ -129,12 +193,17 @@ impl Document {
         Self {
             root,
             nodes,
-            parents: SecondaryMap::new(),
-            children: SecondaryMap::new(),
+            parents: SecondaryMap::with_capacity(cap),
+            children: SecondaryMap::with_capacity(cap),
             ids: Default::default(),
+            component_roots: Default::default(),
             fragment_template: None,
             event_callback: None,
+            merge_options: MergeOptions::default(),
+            entity_encoding: EntityEncoding::default(),
+            print_platform: None,
             upload_ct: 0,
+            doctype: None,
         }
     }
 
@@ -142,19 +211,92 @@ impl Document {
         self.event_callback.clone()
     }
 
+    /// Sets the handler notified of the changes applied when merging server diffs
+    pub fn set_change_handler(&mut self, handler: Arc<dyn DocumentChangeHandler>) {
+        self.event_callback = Some(handler);
+    }
+
+    /// Removes the handler set with [Document::set_change_handler]
+    pub fn clear_change_handler(&mut self) {
+        self.event_callback = None;
+    }
+
+    /// Returns the options used when merging server diffs into this document
+    pub fn merge_options(&self) -> &MergeOptions {
+        &self.merge_options
+    }
+
+    /// Sets the options used when merging server diffs into this document
+    pub fn set_merge_options(&mut self, options: MergeOptions) {
+        self.merge_options = options;
+    }
+
+    /// Returns the characters encoded as character references when printing this document
+    pub fn entity_encoding(&self) -> EntityEncoding {
+        self.entity_encoding
+    }
+
+    /// Sets the characters encoded as character references when printing this document
+    pub fn set_entity_encoding(&mut self, encoding: EntityEncoding) {
+        self.entity_encoding = encoding;
+    }
+
+    /// Returns the platform this document is printed for, see [Document::set_print_platform]
+    pub fn print_platform(&self) -> Option<&str> {
+        self.print_platform.as_deref()
+    }
+
+    /// Sets the platform this document is printed for, e.g. `swiftui`.
+    ///
+    /// Namespaced attributes are treated as platform specific: when set, `swiftui:foo` is printed
+    /// but `jetpack:foo` is dropped. Attributes without a namespace are always printed.
+    pub fn set_print_platform(&mut self, platform: Option<String>) {
+        self.print_platform = platform;
+    }
+
     /// Parses a `Document` from a string
     pub fn parse<S: AsRef<str>>(input: S) -> Result<Self, parser::ParseError> {
         parser::parse(input.as_ref())
     }
 
+    /// Parses a `Document` from a string, reserving space for `cap` nodes up front
+    ///
+    /// This avoids repeatedly growing the node maps when the approximate size of the
+    /// rendered document is known ahead of time, e.g. for long stream lists.
+    pub fn parse_with_capacity<S: AsRef<str>>(
+        input: S,
+        cap: usize,
+    ) -> Result<Self, parser::ParseError> {
+        parser::parse_into(
+            Self::with_capacity(cap),
+            input.as_ref(),
+            Default::default(),
+            &[],
+        )
+    }
+
+    /// Parses a `Document` from a string, using the provided `ParseOptions`
+    pub fn parse_with_options<S: AsRef<str>>(
+        input: S,
+        options: parser::ParseOptions,
+    ) -> Result<Self, parser::ParseError> {
+        parser::parse_with_options(input.as_ref(), options)
+    }
+
     /// Parses a `Document` from raw bytes
     pub fn parse_bytes<B: AsRef<[u8]>>(input: B) -> Result<Self, parser::ParseError> {
         parser::parse(input.as_ref())
     }
 
+    /// Parses a `Document` from a reader, e.g. a streamed response body, without first
+    /// buffering the whole input into a `String`
+    pub fn parse_reader<R: Read>(reader: R) -> Result<Self, parser::ParseError> {
+        parser::parse(html5gum::IoReader::new(reader))
+    }
+
     /// Parses a `Document` from a file at the given path
     pub fn parse_file<P: AsRef<Path>>(path: P) -> Result<Self, parser::ParseError> {
-        parser::parse(std::fs::File::open(path)?)
+        Self::parse_reader(std::fs::File::open(path)?)
     }
 
     /// Returns an ascending `ref` id
@@ -179,6 +321,8 @@ impl Document {
         self.parents.clear();
         self.children.clear();
         self.ids.clear();
+        self.component_roots.clear();
+        self.doctype = None;
     }
 
     /// Returns true if this document is empty (contains no nodes)
@@ -186,6 +330,37 @@ impl Document {
         self.children[self.root].is_empty()
     }
 
+    /// Returns true if this document renders nothing of substance.
+    ///
+    /// This is the case when the document is empty, or when its only content is an empty
+    /// element with the id `flash-group`, which is the initial render of a typical LiveView.
+    pub fn is_blank(&self) -> bool {
+        match self.children[self.root].as_slice() {
+            [] => true,
+            [node] => {
+                self.children[*node].is_empty()
+                    && self.nodes[*node].id().is_some_and(|id| id == "flash-group")
+            }
+            _ => false,
+        }
+    }
+
+    /// Returns the only top-level node of the document, i.e. the single child of the implicit root.
+    ///
+    /// Whitespace-only text between top-level nodes is ignored, so a trailing newline does not count
+    /// as a second root. Fails if the document is empty or has more than one top-level node.
+    pub fn single_root(&self) -> Result<NodeRef, MultipleRootsError> {
+        let mut roots = self.children[self.root].iter().copied().filter(|node| {
+            !matches!(&self.nodes[*node], NodeData::Leaf { value } if value.trim().is_empty())
+        });
+        match (roots.next(), roots.next()) {
+            (Some(root), None) => Ok(root),
+            (first, _) => Err(MultipleRootsError {
+                count: first.map_or(0, |_| 2 + roots.count()),
+            }),
+        }
+    }
+
     /// Returns the root node of the document
     ///
     /// The root node can be used in insertion operations, but can not have attributes applied to it
@@ -224,6 +399,14 @@ impl Document {
         }
     }
 
+    /// Returns the attributes of `node` without cloning them, empty if `node` is not an element
+    pub fn attributes_ref(&self, node: NodeRef) -> &[Attribute] {
+        match &self.nodes[node] {
+            NodeData::NodeElement { element: ref elem } => elem.attributes.as_slice(),
+            _ => &[],
+        }
+    }
+
     /// Returns the attribute `name` on `node`, otherwise `None`
     pub fn get_attribute_by_name<N: Into<AttributeName>>(
         &self,
@@ -231,7 +414,7 @@ impl Document {
         name: N,
     ) -> Option<Attribute> {
         let name = name.into();
-        self.attributes(node).iter().find_map(|attr| {
+        self.attributes_ref(node).iter().find_map(|attr| {
             if attr.name == name {
                 Some(attr.clone())
             } else {
@@ -240,18 +423,129 @@ impl Document {
         })
     }
 
+    /// Returns the `phx-value-*` attributes of `node` keyed by the name following the prefix,
+    /// as sent in the value of an event triggered by `node`
+    pub fn phx_values(&self, node: NodeRef) -> HashMap<String, String> {
+        self.attributes_ref(node)
+            .iter()
+            .filter(|attr| attr.name.namespace.is_none())
+            .filter_map(|attr| {
+                let key = attr.name.name.strip_prefix("phx-value-")?;
+                Some((key.to_owned(), attr.value.clone().unwrap_or_default()))
+            })
+            .collect()
+    }
+
+    /// Returns every attribute in the document along with the element carrying it, in document order,
+    /// e.g. to report which `phx-*` bindings a template uses
+    pub fn all_attributes(&self) -> impl Iterator<Item = (NodeRef, &Attribute)> + '_ {
+        self.select(Selector::All).flat_map(|node| {
+            self.attributes_ref(node)
+                .iter()
+                .map(move |attr| (node, attr))
+        })
+    }
+
+    /// Returns every event bound in the document as the bound node, the event and the name of the
+    /// event sent to the server, in document order, e.g. to build a dispatch table for a new render
+    pub fn event_bindings(&self) -> Vec<(NodeRef, PhxEvent, String)> {
+        self.select(Selector::All)
+            .flat_map(|node| {
+                self.attributes_ref(node).iter().filter_map(move |attr| {
+                    if attr.name.namespace.is_some() {
+                        return None;
+                    }
+                    let event = PhxEvent::from_attribute(&attr.name.name)?;
+                    Some((node, event, attr.value.clone().unwrap_or_default()))
+                })
+            })
+            .collect()
+    }
+
     /// Returns the parent of `node`, if it has one
     #[inline]
     pub fn parent(&self, node: NodeRef) -> Option<NodeRef> {
         self.parents[node].expand()
     }
 
+    /// Returns the cid of the LiveComponent which rendered `node`, i.e. of the closest component
+    /// root among `node` and its ancestors, if the node was rendered from the fragment template
+    pub fn component_cid_for(&self, node: NodeRef) -> Option<u32> {
+        let mut current = Some(node);
+        while let Some(node) = current {
+            if let Some(cid) = self.component_roots.get(&node) {
+                return Some(*cid);
+            }
+            current = self.parent(node);
+        }
+        None
+    }
+
+    /// Records `node` as the root element of the component `cid`, unless it is already the root
+    /// of an enclosing component
+    pub(crate) fn add_component_root(&mut self, node: NodeRef, cid: u32) {
+        self.component_roots.entry(node).or_insert(cid);
+    }
+
+    /// Copies the component roots of `rendered`, which must have the same shape as this document
+    fn adopt_component_roots(&mut self, rendered: &Document) {
+        self.component_roots.clear();
+        let mut stack = vec![(self.root, rendered.root)];
+        while let Some((node, rendered_node)) = stack.pop() {
+            if let Some(cid) = rendered.component_roots.get(&rendered_node) {
+                self.component_roots.insert(node, *cid);
+            }
+            stack.extend(
+                self.children(node)
+                    .iter()
+                    .copied()
+                    .zip(rendered.children(rendered_node).iter().copied()),
+            );
+        }
+    }
+
     /// Returns the children of `node` as a slice
     #[inline]
     pub fn children(&self, node: NodeRef) -> &[NodeRef] {
         self.children[node].as_slice()
     }
 
+    /// Returns the first child of `node`, if it has any children
+    #[inline]
+    pub fn first_child(&self, node: NodeRef) -> Option<NodeRef> {
+        self.children[node].first().copied()
+    }
+
+    /// Returns the last child of `node`, if it has any children
+    #[inline]
+    pub fn last_child(&self, node: NodeRef) -> Option<NodeRef> {
+        self.children[node].last().copied()
+    }
+
+    /// Returns the `n`th child of `node`, or `None` if `n` is out of range
+    #[inline]
+    pub fn nth_child(&self, node: NodeRef, n: usize) -> Option<NodeRef> {
+        self.children[node].get(n).copied()
+    }
+
+    /// Returns true if `node` has children and every one of them is a text leaf
+    pub fn is_text_only(&self, node: NodeRef) -> bool {
+        let children = &self.children[node];
+        !children.is_empty()
+            && children
+                .iter()
+                .all(|child| matches!(self.nodes[*child], NodeData::Leaf { .. }))
+    }
+
+    /// Returns the doctype declared by the source document, e.g. `html` for `<!DOCTYPE html>`
+    pub fn doctype(&self) -> Option<InternedString> {
+        self.doctype
+    }
+
+    pub(crate) fn set_doctype(&mut self, doctype: InternedString) {
+        self.doctype = Some(doctype);
+    }
+
     /// Returns the `NodeRef` associated with the given unique identifier
     pub fn get_by_id<S: AsRef<str>>(&self, id: S) -> Option<NodeRef> {
         self.ids.get(id.as_ref()).copied()
@@ -278,6 +572,137 @@ impl Document {
         SelectionIter::new(self, selector, node)
     }
 
+    /// Returns the nearest ancestor of `node` which matches `selector`, including `node` itself
+    ///
+    /// This mirrors `Element.closest()` in the browser DOM
+    pub fn closest(&self, node: NodeRef, selector: Selector<'_>) -> Option<NodeRef> {
+        let mut current = Some(node);
+        while let Some(node) = current {
+            if selector.matches(node, self) {
+                return Some(node);
+            }
+            current = self.parent(node);
+        }
+        None
+    }
+
+    /// Checks that every element attached to the document is named in `allowed`
+    ///
+    /// Returns the offending elements in breadth-first order if any are not, e.g. to reject
+    /// markup from the server before rendering it.
+    pub fn validate_tags(&self, allowed: &HashSet<ElementName>) -> Result<(), Vec<NodeRef>> {
+        use petgraph::visit::IntoNodeReferences;
+
+        let disallowed: Vec<NodeRef> = self
+            .node_references()
+            .filter_map(|(node, data)| match data {
+                NodeData::NodeElement { element } if !allowed.contains(&element.name) => Some(node),
+                _ => None,
+            })
+            .collect();
+
+        if disallowed.is_empty() {
+            Ok(())
+        } else {
+            Err(disallowed)
+        }
+    }
+
+    /// Returns the number of nodes reachable from the root, including the root itself.
+    ///
+    /// Unlike [petgraph::visit::NodeCount::node_count], detached nodes are not counted.
+    pub fn attached_node_count(&self) -> usize {
+        NodeIdentifiers::new(self).count()
+    }
+
+    /// Returns the number of elements attached to the document
+    pub fn element_count(&self) -> usize {
+        NodeReferences::new(self)
+            .filter(|(_, data)| matches!(data, NodeData::NodeElement { .. }))
+            .count()
+    }
+
+    /// Returns the number of leaf (text) nodes attached to the document
+    pub fn leaf_count(&self) -> usize {
+        NodeReferences::new(self)
+            .filter(|(_, data)| matches!(data, NodeData::Leaf { .. }))
+            .count()
+    }
+
+    /// Returns a hash of the tree attached to the document, e.g. to skip re-rendering when nothing changed
+    ///
+    /// This hashes the data and number of children of each node rather than printing the markup,
+    /// so documents which render the same hash the same.
+    pub fn render_hash(&self) -> u64 {
+        let mut hasher = FxHasher64::default();
+        for (node, data) in NodeReferences::new(self) {
+            data.hash(&mut hasher);
+            self.children(node).len().hash(&mut hasher);
+        }
+        hasher.finish()
+    }
+
+    /// Serializes `node` and its descendants to a compact binary form, see [Document::deserialize_subtree]
+    ///
+    /// Unlike the printed markup, this preserves the attributes, namespaces and structure exactly.
+    pub fn serialize_subtree(&self, node: NodeRef) -> Vec<u8> {
+        // each node is followed by its descendants, and stored with its number of children
+        let mut nodes: Vec<(&NodeData, usize)> = vec![];
+        let mut stack = vec![node];
+        while let Some(node) = stack.pop() {
+            let children = self.children(node);
+            nodes.push((self.get(node), children.len()));
+            stack.extend(children.iter().rev());
+        }
+        bincode::serialize(&nodes).expect("nodes are always serializable")
+    }
+
+    /// Builds a document from a subtree serialized with [Document::serialize_subtree]
+    ///
+    /// The subtree becomes the only child of the new document's root, unless the root of a
+    /// document was serialized.
+    pub fn deserialize_subtree(bytes: &[u8]) -> Result<Self, bincode::Error> {
+        let nodes: Vec<(NodeData, usize)> = bincode::deserialize(bytes)?;
+        let malformed = |reason: &str| bincode::ErrorKind::Custom(reason.to_owned()).into();
+
+        let mut doc = Self::empty();
+        // the nodes still missing children, with the number they are missing
+        let mut open = vec![(doc.root, 1)];
+        for (index, (data, children)) in nodes.into_iter().enumerate() {
+            if index == 0 && data == NodeData::Root {
+                // an empty root has no children left to read
+                open.clear();
+                if children > 0 {
+                    open.push((doc.root, children));
+                }
+                continue;
+            }
+
+            let Some((parent, missing)) = open.pop() else {
+                return Err(malformed("more nodes than the subtree holds"));
+            };
+            if missing > 1 {
+                open.push((parent, missing - 1));
+            }
+
+            let id = data.id();
+            let node = doc.push_node(data);
+            doc.append_child(parent, node);
+            if let Some(id) = id {
+                doc.register_id(node, id);
+            }
+            if children > 0 {
+                open.push((node, children));
+            }
+        }
+
+        if open.is_empty() {
+            Ok(doc)
+        } else {
+            Err(malformed("fewer nodes than the subtree holds"))
+        }
+    }
+
     /// Attaches `doc` to this document, with `parent` as the parent of the new subtree.
     pub fn attach_document(&mut self, parent: NodeRef, mut doc: Document) {
         // Copy over nodes, ignoring the root element
@@ -395,6 +820,27 @@ impl Document {
         children.insert(position, node);
     }
 
+    /// Moves `node` to position `new_index` amongst its siblings, keeping its subtree and data
+    ///
+    /// `new_index` is the position of `node` once moved, so the other children keep their relative order.
+    ///
+    /// This function will panic if:
+    ///
+    /// * `node` has no parent
+    /// * `new_index` is not less than the number of children of `node`'s parent
+    pub fn move_child(&mut self, node: NodeRef, new_index: usize) {
+        let parent = self.parents[node].expand().unwrap();
+        let children = &mut self.children[parent];
+        assert!(
+            new_index < children.len(),
+            "index {new_index} out of bounds for {} children",
+            children.len()
+        );
+        let position = children.iter().copied().position(|n| n == node).unwrap();
+        let node = children.remove(position);
+        children.insert(new_index, node);
+    }
+
     /// Detaches a node from the document, but preserves the subtree of the node
     ///
     /// The data associated with detached nodes remains stored in the document; see `delete` if you require that behavior.
@@ -408,14 +854,59 @@ impl Document {
         }
     }
 
+    /// Replaces the children of `parent` with `new_children`, in the given order
+    ///
+    /// The previous children are detached, not deleted, so their data remains stored in the document.
+    ///
+    /// Fails without changing the document if any of `new_children` already has a parent, is listed
+    /// more than once, or is `parent` itself or one of its ancestors.
+    pub fn replace_children(
+        &mut self,
+        parent: NodeRef,
+        new_children: Vec<NodeRef>,
+    ) -> Result<(), ReplaceChildrenError> {
+        let mut ancestors = HashSet::new();
+        let mut ancestor = Some(parent);
+        while let Some(node) = ancestor {
+            ancestors.insert(node);
+            ancestor = self.parent(node);
+        }
+
+        let mut seen = HashSet::new();
+        for child in new_children.iter().copied() {
+            if ancestors.contains(&child) {
+                return Err(ReplaceChildrenError::Ancestor(child));
+            }
+            if self.parents[child].is_some() {
+                return Err(ReplaceChildrenError::HasParent(child));
+            }
+            if !seen.insert(child) {
+                return Err(ReplaceChildrenError::Duplicate(child));
+            }
+        }
+
+        for child in self.children[parent].drain(..) {
+            self.parents[child] = None.into();
+        }
+
+        for child in new_children.iter().copied() {
+            self.parents[child] = parent.into();
+        }
+        self.children[parent] = SmallVec::from_vec(new_children);
+        Ok(())
+    }
+
     /// Deletes a node from the document, along with all of its children and associated data
     ///
     /// This operation cannot be undone; once deleted, the node tree rooted at `node` cannot be recovered.
     pub fn delete(&mut self, node: NodeRef) {
         let mut stack = VecDeque::<NodeRef>::with_capacity(4);
         stack.push_back(node);
+        let mut deleted = Vec::new();
 
         while let Some(node) = stack.pop_front() {
+            deleted.push(node);
+
             // Detach node from its parent
             self.detach(node);
 
@@ -436,6 +927,70 @@ impl Document {
                 }
             }
         }
+
+        // Deleted nodes can no longer be found by their id
+        if !self.ids.is_empty() {
+            deleted.sort_unstable();
+            self.ids
+                .retain(|_, node| deleted.binary_search(node).is_err());
+        }
+    }
+
+    /// Normalizes the text of every leaf node, so that documents differing only in insignificant
+    /// whitespace become structurally equal.
+    ///
+    /// Leading and trailing whitespace is trimmed, like the parser does, and inner runs of
+    /// whitespace are collapsed to a single space. Leaves left empty are deleted.
+    pub fn normalize_text(&mut self) {
+        let leaves = NodeReferences::new(self)
+            .filter(|(_, data)| data.is_leaf())
+            .map(|(node, _)| node)
+            .collect::<Vec<_>>();
+        for node in leaves {
+            let NodeData::Leaf { value } = &mut self.nodes[node] else {
+                continue;
+            };
+            let normalized = value.split_whitespace().collect::<Vec<_>>().join(" ");
+            if normalized.is_empty() {
+                self.delete(node);
+            } else if *value != normalized {
+                *value = normalized;
+            }
+        }
+    }
+
+    /// Frees the data of every node which is no longer reachable from the root, such as the nodes
+    /// left behind by [Document::detach] and [Document::delete], returning the number freed.
+    ///
+    /// Existing `NodeRef`s stay valid, a freed node remains in the document as a detached, empty
+    /// leaf. Detached nodes which are meant to be attached again must be attached before calling this.
+    pub fn gc(&mut self) -> usize {
+        let mut reachable = FixedBitSet::with_capacity(self.nodes.len());
+        for node in NodeIdentifiers::new(self) {
+            reachable.insert(node.index());
+        }
+
+        let mut freed = 0;
+        for (node, data) in self.nodes.iter_mut() {
+            if reachable.contains(node.index()) {
+                continue;
+            }
+            let is_free = matches!(data, NodeData::Leaf { value } if value.is_empty())
+                && self.children[node].is_empty();
+            if !is_free {
+                *data = NodeData::Leaf {
+                    value: String::new(),
+                };
+                self.children[node] = SmallVec::new();
+                freed += 1;
+            }
+            self.parents[node] = Default::default();
+        }
+
+        self.ids.retain(|_, node| reachable.contains(node.index()));
+        self.component_roots
+            .retain(|node, _| reachable.contains(node.index()));
+        freed
     }
 
     /// Adds a node to this document, returning the corresponding NodeRef.
@@ -468,6 +1023,31 @@ impl Document {
         }
     }
 
+    /// Sets the attribute `name` on `node` with `value` like [Document::set_attribute], then reports
+    /// the change to the `event_callback` as if it had been applied by a diff from the server.
+    ///
+    /// Returns true if `node` was an element and the attribute could be set, otherwise false.
+    pub fn set_attribute_notifying<K: Into<AttributeName>, V: Into<Option<String>>>(
+        &mut self,
+        node: NodeRef,
+        name: K,
+        value: V,
+    ) -> bool {
+        if !self.set_attribute(node, name, value) {
+            return false;
+        }
+
+        if let Some(handler) = self.event_callback.clone() {
+            handler.handle_document_change(
+                ChangeType::Change,
+                node.into(),
+                self.nodes[node].clone(),
+                None,
+            );
+        }
+        true
+    }
+
     /// Removes the attribute `name` from `node`.
     pub fn remove_attribute<K: Into<AttributeName>>(&mut self, node: NodeRef, name: K) {
         if let NodeData::NodeElement {
@@ -479,6 +1059,80 @@ impl Document {
         }
     }
 
+    /// Returns the space separated tokens of the attribute `name` on `node` in order, without duplicates
+    pub fn tokens<K: Into<AttributeName>>(&self, node: NodeRef, name: K) -> Vec<String> {
+        let value = self
+            .get_attribute_by_name(node, name)
+            .and_then(|attr| attr.value)
+            .unwrap_or_default();
+        let mut tokens: Vec<String> = vec![];
+        for token in value.split_ascii_whitespace() {
+            if !tokens.iter().any(|t| t == token) {
+                tokens.push(token.to_owned());
+            }
+        }
+        tokens
+    }
+
+    /// Returns true if `token` is one of the space separated tokens of the attribute `name` on `node`
+    pub fn has_token<K: Into<AttributeName>>(&self, node: NodeRef, name: K, token: &str) -> bool {
+        self.tokens(node, name).iter().any(|t| t == token)
+    }
+
+    /// Appends `token` to the space separated tokens of the attribute `name` on `node`, setting the
+    /// attribute if it is missing. Duplicate tokens already in the value are removed.
+    ///
+    /// Returns false if `node` is not an element, `token` is empty or contains whitespace,
+    /// or it already was one of the tokens.
+    pub fn add_token<K: Into<AttributeName>>(
+        &mut self,
+        node: NodeRef,
+        name: K,
+        token: &str,
+    ) -> bool {
+        let name = name.into();
+        let mut tokens = self.tokens(node, name.clone());
+        if !is_token(token) || tokens.iter().any(|t| t == token) {
+            return false;
+        }
+        tokens.push(token.to_owned());
+        self.set_attribute(node, name, tokens.join(" "))
+    }
+
+    /// Removes `token` from the space separated tokens of the attribute `name` on `node`, keeping the
+    /// order of the remaining tokens. The attribute is kept even if no tokens remain.
+    ///
+    /// Returns false if `token` was not one of the tokens.
+    pub fn remove_token<K: Into<AttributeName>>(
+        &mut self,
+        node: NodeRef,
+        name: K,
+        token: &str,
+    ) -> bool {
+        let name = name.into();
+        let mut tokens = self.tokens(node, name.clone());
+        let Some(index) = tokens.iter().position(|t| t == token) else {
+            return false;
+        };
+        tokens.remove(index);
+        self.set_attribute(node, name, tokens.join(" "))
+    }
+
+    /// Returns true if `node` has the class `token`, see [Document::has_token]
+    pub fn has_class(&self, node: NodeRef, token: &str) -> bool {
+        self.has_token(node, "class", token)
+    }
+
+    /// Adds the class `token` to `node`, see [Document::add_token]
+    pub fn add_class(&mut self, node: NodeRef, token: &str) -> bool {
+        self.add_token(node, "class", token)
+    }
+
+    /// Removes the class `token` from `node`, see [Document::remove_token]
+    pub fn remove_class(&mut self, node: NodeRef, token: &str) -> bool {
+        self.remove_token(node, "class", token)
+    }
+
     /// If node is an element, replace attributes and return previous
     pub fn replace_attributes(
         &mut self,
@@ -524,12 +1178,31 @@ impl Document {
         printer.print(writer)
     }
 
+    /// Returns the markup of `node` and its descendants, like `outerHTML` on the web
+    pub fn outer_markup(&self, node: NodeRef, options: PrintOptions) -> String {
+        let mut out = String::new();
+        self.print_node(node, &mut out, options)
+            .expect("printing to a string can't fail");
+        out
+    }
+
+    /// Returns the markup of the descendants of `node`, like `innerHTML` on the web
+    ///
+    /// This is the [Document::outer_markup] of each child, separated by newlines when pretty printing.
+    pub fn inner_markup(&self, node: NodeRef, options: PrintOptions) -> String {
+        let separator = if options.pretty() { "\n" } else { "" };
+        self.children(node)
+            .iter()
+            .map(|child| self.outer_markup(*child, options))
+            .collect::<Vec<_>>()
+            .join(separator)
+    }
+
     /// Parses a `RootDiff` and returns a `Document`
     pub fn parse_fragment_json(input: String) -> Result<Self, RenderError> {
         let fragment: RootDiff = serde_json::from_str(&input).map_err(RenderError::from)?;
         let root: Root = fragment.try_into()?;
-        let rendered: String = root.clone().try_into()?;
-        let mut document = crate::parser::parse(&rendered)?;
+        let mut document = Self::render_template(&root)?;
         document.fragment_template = Some(root);
         Ok(document)
     }
@@ -539,31 +1212,280 @@ impl Document {
         value: serde_json::Value,
     ) -> Result<Vec<PatchResult>, RenderError> {
         let fragment: RootDiff = serde_json::from_value(value).map_err(RenderError::from)?;
+        self.merge_fragment(fragment)
+    }
 
-        let root = if let Some(root) = &self.fragment_template {
-            root.clone().merge(fragment)?
-        } else {
-            fragment.try_into()?
+    /// Like [Document::merge_fragment_json], for a diff which was already decoded, e.g. with
+    /// [RootDiff::from_msgpack]
+    ///
+    /// If the merge fails both the stored fragment template and the document are left untouched.
+    pub fn merge_fragment(&mut self, fragment: RootDiff) -> Result<Vec<PatchResult>, RenderError> {
+        #[cfg(feature = "tracing")]
+        let _span =
+            tracing::info_span!("merge_diff", patch_count = tracing::field::Empty).entered();
+
+        let previous = self.previous_render()?;
+        let rendered = self.merge_template(fragment)?;
+        Ok(self.render_patches(rendered, previous).collect())
+    }
+
+    /// Replaces the stored fragment template with the full render `fragment`, e.g. after
+    /// rejoining a channel.
+    ///
+    /// Rather than swapping in a new document, this document is patched to match the render, so
+    /// the returned patches describe every change and the document keeps its options. Local
+    /// edits are not preserved.
+    pub fn replace_fragment(
+        &mut self,
+        fragment: RootDiff,
+    ) -> Result<Vec<PatchResult>, RenderError> {
+        #[cfg(feature = "tracing")]
+        let _span =
+            tracing::info_span!("merge_diff", patch_count = tracing::field::Empty).entered();
+
+        let root: Root = fragment.try_into()?;
+        let rendered = Self::render_template(&root)?;
+        self.fragment_template = Some(root);
+        Ok(self.render_patches(rendered, None).collect())
+    }
+
+    /// Like [Document::merge_fragment_json], but the patches are applied one by one as the
+    /// returned iterator is consumed, so a client can interleave rendering with patching.
+    ///
+    /// The patches are still computed up front. Dropping the iterator applies any remaining
+    /// patches, so the document always ends up matching the merged template.
+    pub fn merge_fragment_json_iter(
+        &mut self,
+        value: serde_json::Value,
+    ) -> Result<MergePatches<'_>, RenderError> {
+        // the span covers computing the patches, not applying them
+        #[cfg(feature = "tracing")]
+        let _span =
+            tracing::info_span!("merge_diff", patch_count = tracing::field::Empty).entered();
+
+        let fragment: RootDiff = serde_json::from_value(value).map_err(RenderError::from)?;
+        let previous = self.previous_render()?;
+        let rendered = self.merge_template(fragment)?;
+        Ok(self.render_patches(rendered, previous))
+    }
+
+    /// Merges `fragment` into a copy of the stored fragment template, returning its new render.
+    ///
+    /// The copy only replaces the stored template once it rendered, since an in place merge
+    /// may leave the template partially updated when it fails.
+    fn merge_template(&mut self, fragment: RootDiff) -> Result<Document, RenderError> {
+        let root = match &self.fragment_template {
+            Some(root) => {
+                let mut root = root.clone();
+                root.merge_in_place(fragment)?;
+                root
+            }
+            None => fragment.try_into()?,
         };
-        self.fragment_template = Some(root.clone());
 
-        let rendered_root: String = root.clone().try_into()?;
-        let new_doc = Self::parse(rendered_root)?;
+        let rendered = Self::render_template(&root)?;
+        self.fragment_template = Some(root);
+        Ok(rendered)
+    }
+
+    /// Renders `root` and parses the result, recording the root element of every component
+    fn render_template(root: &Root) -> Result<Document, RenderError> {
+        let (rendered, components) = root.render_with_component_spans()?;
+        Ok(parser::parse_into(
+            Self::empty(),
+            rendered.as_str(),
+            Default::default(),
+            &components,
+        )?)
+    }
 
-        let patches = crate::diff::diff(self, &new_doc);
-        if patches.is_empty() {
-            return Ok(vec![]);
+    /// Renders the stored fragment template before a diff is merged into it, if the render
+    /// is needed to tell local edits from server changes
+    fn previous_render(&self) -> Result<Option<Document>, RenderError> {
+        match &self.fragment_template {
+            Some(root) if self.merge_options.preserve_local_edits => {
+                Ok(Some(Self::render_template(root)?))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// Returns the patches to this document to match the `rendered` template, which are applied
+    /// as they are iterated.
+    ///
+    /// If the `previous` render is given, the patches keep the local edits it does not contain.
+    fn render_patches(
+        &mut self,
+        new_doc: Document,
+        previous: Option<Document>,
+    ) -> MergePatches<'_> {
+        let mut patches = crate::diff::diff(self, &new_doc);
+        if let Some(previous) = previous {
+            patches = self.retain_local_edits(patches, &previous, &new_doc);
         }
 
-        let mut stack = vec![];
-        let mut editor = self.edit();
-        let results = patches
+        #[cfg(feature = "tracing")]
+        tracing::Span::current().record("patch_count", patches.len());
+
+        MergePatches {
+            options: self.merge_options.clone(),
+            editor: self.edit(),
+            patches: patches.into_iter(),
+            stack: vec![],
+            rendered: Some(new_doc),
+        }
+    }
+
+    /// Drops the parts of `patches` which would revert local edits of nodes marked with
+    /// [LOCAL_EDIT_ATTRIBUTE], unless the server changed the same value between its `previous`
+    /// render and `rendered`, in which case the server wins and the conflict is logged.
+    ///
+    /// Nodes are matched across the documents by their path from the root.
+    fn retain_local_edits(
+        &self,
+        patches: Vec<Patch>,
+        previous: &Document,
+        rendered: &Document,
+    ) -> Vec<Patch> {
+        let server_nodes = |node| {
+            let path = self.path_to(node)?;
+            Some((previous.node_at(&path)?, rendered.node_at(&path)?))
+        };
+
+        patches
             .into_iter()
-            .filter_map(|patch| patch.apply(&mut editor, &mut stack))
-            .collect();
+            .filter_map(|patch| {
+                let (node, before, after) = match &patch {
+                    Patch::SetAttributes { node, .. } | Patch::Replace { node, .. }
+                        if self.is_locally_edited(*node) =>
+                    {
+                        match server_nodes(*node) {
+                            Some((before, after)) => (*node, before, after),
+                            None => return Some(patch),
+                        }
+                    }
+                    _ => return Some(patch),
+                };
+
+                match patch {
+                    Patch::SetAttributes { attributes, .. } => {
+                        let attributes =
+                            self.keep_local_attributes(node, previous.attributes_ref(before), attributes);
+                        (attributes.as_slice() != self.attributes_ref(node))
+                            .then_some(Patch::SetAttributes { node, attributes })
+                    }
+                    Patch::Replace { .. } if previous.get(before) == rendered.get(after) => None,
+                    patch => {
+                        if self.get(node) != previous.get(before) {
+                            log::warn!(
+                                "The server changed the locally edited node {node:?}, discarding the local edit"
+                            );
+                        }
+                        Some(patch)
+                    }
+                }
+            })
+            .collect()
+    }
+
+    /// Restores the attributes of `node` which were edited locally into the server's new
+    /// `attributes`, unless the server changed them since its `previous` render
+    fn keep_local_attributes(
+        &self,
+        node: NodeRef,
+        previous: &[Attribute],
+        mut attributes: Vec<Attribute>,
+    ) -> Vec<Attribute> {
+        let local = self.attributes_ref(node);
+        let server = attributes.clone();
+        let value = |attributes: &[Attribute], name: &AttributeName| {
+            attributes
+                .iter()
+                .find(|attr| attr.name == *name)
+                .map(|attr| attr.value.clone())
+        };
+
+        let mut names = Vec::<&AttributeName>::new();
+        for attr in local.iter().chain(previous) {
+            if !names.contains(&&attr.name) {
+                names.push(&attr.name);
+            }
+        }
+
+        for name in names {
+            let ours = value(local, name);
+            let base = value(previous, name);
+            if ours == base {
+                continue;
+            }
+            if value(&server, name) != base {
+                log::warn!(
+                    "The server changed the locally edited attribute `{name}` of {node:?}, discarding the local value"
+                );
+                continue;
+            }
+
+            let index = attributes.iter().position(|attr| attr.name == *name);
+            match (index, ours) {
+                (Some(index), Some(value)) => attributes[index].value = value,
+                (Some(index), None) => {
+                    attributes.remove(index);
+                }
+                (None, Some(value)) => attributes.push(Attribute {
+                    name: name.clone(),
+                    value,
+                }),
+                (None, None) => {}
+            }
+        }
 
-        editor.finish();
-        Ok(results)
+        attributes
+    }
+
+    /// Returns true if `node` is marked with [LOCAL_EDIT_ATTRIBUTE], or is the text of such a node
+    fn is_locally_edited(&self, node: NodeRef) -> bool {
+        let marked = |node| {
+            self.get_attribute_by_name(node, LOCAL_EDIT_ATTRIBUTE)
+                .is_some()
+        };
+        match self.get(node) {
+            NodeData::Leaf { .. } => self.parent(node).is_some_and(marked),
+            _ => marked(node),
+        }
+    }
+
+    /// Returns the index of each node among its siblings on the way from the root to `node`,
+    /// or `None` if `node` is detached
+    fn path_to(&self, mut node: NodeRef) -> Option<Vec<usize>> {
+        let mut path = vec![];
+        while let Some(parent) = self.parent(node) {
+            path.push(
+                self.children(parent)
+                    .iter()
+                    .position(|child| *child == node)?,
+            );
+            node = parent;
+        }
+        path.reverse();
+        (node == self.root).then_some(path)
+    }
+
+    /// Returns the node reached by following `path` from the root, see [Document::path_to]
+    fn node_at(&self, path: &[usize]) -> Option<NodeRef> {
+        path.iter()
+            .try_fold(self.root, |node, index| self.nth_child(node, *index))
+    }
+
+    /// Merges the changes made to `base` in `theirs` into `ours`, returning the merged document
+    /// and the changes from `theirs` which conflicted with `ours` and were not applied.
+    ///
+    /// See [crate::diff::three_way_merge] for the requirements on the inputs.
+    pub fn three_way_merge(
+        base: &Document,
+        ours: &Document,
+        theirs: &Document,
+    ) -> (Document, Vec<crate::diff::Conflict>) {
+        crate::diff::three_way_merge(base, ours, theirs)
     }
 
     /// Returns the CSRF token if it is present in the page.
@@ -995,6 +1917,46 @@ impl DocumentBuilder for Editor<'_> {
     }
 }
 
+/// The patches of a merge, which are applied to the document as they are iterated.
+///
+/// See [Document::merge_fragment_json_iter].
+pub struct MergePatches<'a> {
+    editor: Editor<'a>,
+    patches: std::vec::IntoIter<crate::diff::Patch>,
+    stack: Vec<NodeRef>,
+    options: MergeOptions,
+    /// The document the patches were computed against, taken once all patches are applied
+    rendered: Option<Document>,
+}
+
+impl Iterator for MergePatches<'_> {
+    type Item = PatchResult;
+
+    fn next(&mut self) -> Option<PatchResult> {
+        loop {
+            let Some(patch) = self.patches.next() else {
+                if let Some(rendered) = self.rendered.take() {
+                    self.editor.document_mut().adopt_component_roots(&rendered);
+                }
+                return None;
+            };
+
+            let result = patch
+                .preserving_attributes(self.editor.document(), &self.options)
+                .apply(&mut self.editor, &mut self.stack);
+            if result.is_some() {
+                return result;
+            }
+        }
+    }
+}
+
+impl Drop for MergePatches<'_> {
+    fn drop(&mut self) {
+        self.for_each(drop);
+    }
+}
+
 /// Represents a directed edge from a parent node to a child node by combinding the
 /// parent and child NodeRefs into a single value.
 #[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
@@ -1065,7 +2027,7 @@ impl petgraph::visit::NodeCount for Document {
     fn node_count(&self) -> usize {
         // NOTE: This does not reflect the number of nodes that would be produced
         // by traversing the document, it is the total number of nodes created in
-        // this document so far
+        // this document so far, including detached ones. See `Document::attached_node_count`
         self.nodes.len()
     }
 }
@@ -1157,6 +2119,11 @@ impl petgraph::data::DataMapMut for Document {
     }
 }
 
+/// Returns true if `token` can be stored in a space separated token list
+fn is_token(token: &str) -> bool {
+    !token.is_empty() && !token.contains(|c: char| c.is_ascii_whitespace())
+}
+
 /// An iterator over node ids in a Document which are attached to the tree
 pub struct NodeIdentifiers<'a> {
     doc: &'a Document,
@@ -1,10 +1,15 @@
-use std::{fmt, sync::Arc};
+use std::{
+    fmt,
+    hash::{Hash, Hasher},
+    sync::Arc,
+};
 
 use cranelift_entity::entity_impl;
 use petgraph::graph::{IndexType, NodeIndex};
+use serde::{Deserialize, Serialize};
 use smallstr::SmallString;
 
-use super::{ffi::Document as FFiDocument, Attribute, AttributeName};
+use super::{ffi::Document as FFiDocument, Attribute, AttributeName, PrintOptions};
 use crate::{InternedString, Symbol};
 
 #[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, uniffi::Object)]
@@ -52,7 +57,7 @@ unsafe impl IndexType for NodeRef {
 }
 
 /// This enum represents the valid node types of a `Document` tree
-#[derive(Debug, Clone, PartialEq, uniffi::Enum)]
+#[derive(Debug, Clone, PartialEq, Hash, Serialize, Deserialize, uniffi::Enum)]
 pub enum NodeData {
     /// A marker node that indicates the root of a document
     ///
@@ -117,6 +122,13 @@ impl Node {
     pub fn attributes(&self) -> Vec<Attribute> {
         self.data.attributes()
     }
+    /// Returns true if this is an element which was self-closed in the parsed markup
+    pub fn self_closing(&self) -> bool {
+        matches!(
+            &self.data,
+            NodeData::NodeElement { element } if element.self_closing
+        )
+    }
     pub fn get_attribute(&self, name: AttributeName) -> Option<Attribute> {
         self.attributes()
             .iter()
@@ -126,6 +138,10 @@ impl Node {
     pub fn display(&self) -> String {
         format!("{self}")
     }
+    /// Like `display`, but printed using the given `options`
+    pub fn display_with_options(&self, options: PrintOptions) -> String {
+        self.document.render_node(self.id.into(), options)
+    }
 }
 impl NodeData {
     /// Returns a slice of Attributes for this node, if applicable
@@ -188,7 +204,9 @@ impl From<SmallString<[u8; 16]>> for NodeData {
 }
 
 /// Represents the fully-qualified name of an element
-#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, uniffi::Record)]
+#[derive(
+    Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize, uniffi::Record,
+)]
 pub struct ElementName {
     pub namespace: Option<String>,
     pub name: String,
@@ -257,10 +275,27 @@ impl PartialEq<InternedString> for ElementName {
 }
 
 /// An `Element` is a typed node in a document, with the ability to carry attributes and contain other nodes.
-#[derive(Debug, Clone, PartialEq, uniffi::Record)]
+#[derive(Debug, Clone, Serialize, Deserialize, uniffi::Record)]
 pub struct Element {
     pub name: ElementName,
     pub attributes: Vec<Attribute>,
+    /// Whether the element was self-closed in the markup it was parsed from, e.g. `<Icon />`.
+    ///
+    /// This is ignored when comparing and hashing elements since it does not change the document.
+    #[serde(default)]
+    #[uniffi(default = false)]
+    pub self_closing: bool,
+}
+impl PartialEq for Element {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name && self.attributes == other.attributes
+    }
+}
+impl Hash for Element {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.name.hash(state);
+        self.attributes.hash(state);
+    }
 }
 impl Element {
     /// Creates a new element whose type is given by `tag`, without any attributes or children
@@ -270,6 +305,7 @@ impl Element {
         Self {
             name,
             attributes: Vec::new(),
+            self_closing: false,
         }
     }
 
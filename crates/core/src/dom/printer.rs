@@ -1,8 +1,8 @@
 use std::fmt;
 
-use super::{Document, NodeData, NodeRef};
+use super::{Attribute, Document, NodeData, NodeRef};
 
-#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, uniffi::Enum)]
 pub enum PrintOptions {
     /// Prints a document/fragment without any extra whitespace (indentation/whitespace)
     Minified,
@@ -17,6 +17,51 @@ impl PrintOptions {
     }
 }
 
+/// Which characters the printer replaces with character references.
+///
+/// The parser always decodes character references, e.g. `&amp;` and `&#x23;` are stored as `&` and `#`,
+/// so by default a printed document does not parse back to the same document if its text contains
+/// markup characters. Select [EntityEncoding::Markup] for platforms which parse the printed output.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, uniffi::Enum)]
+pub enum EntityEncoding {
+    /// Text and attribute values are printed as they are stored
+    #[default]
+    Raw,
+    /// `&`, `<` and `>` are encoded in text, `&`, `<` and `"` in attribute values
+    Markup,
+}
+impl EntityEncoding {
+    fn write_text(&self, text: &str, writer: &mut dyn fmt::Write) -> fmt::Result {
+        match self {
+            Self::Raw => writer.write_str(text),
+            Self::Markup => write_encoded(text, &['&', '<', '>'], writer),
+        }
+    }
+
+    fn write_attribute_value(&self, value: &str, writer: &mut dyn fmt::Write) -> fmt::Result {
+        match self {
+            Self::Raw => writer.write_str(value),
+            Self::Markup => write_encoded(value, &['&', '<', '"'], writer),
+        }
+    }
+}
+
+fn write_encoded(text: &str, encoded: &[char], writer: &mut dyn fmt::Write) -> fmt::Result {
+    let mut rest = text;
+    while let Some(index) = rest.find(encoded) {
+        writer.write_str(&rest[..index])?;
+        let reference = match rest.as_bytes()[index] {
+            b'&' => "&amp;",
+            b'<' => "&lt;",
+            b'>' => "&gt;",
+            _ => "&quot;",
+        };
+        writer.write_str(reference)?;
+        rest = &rest[index + 1..];
+    }
+    writer.write_str(rest)
+}
+
 pub struct Printer<'a> {
     doc: &'a Document,
     root: NodeRef,
@@ -54,16 +99,13 @@ impl<'a> Printer<'a> {
                                 indent(self.indent, writer)?;
                             }
                             write!(writer, "<{}", &elem.name)?;
-                            let attrs = elem.attributes();
-                            if !attrs.is_empty() {
-                                for attr in attrs.iter() {
-                                    write!(
-                                        writer,
-                                        " {}=\"{}\"",
-                                        &attr.name,
-                                        &attr.value.clone().unwrap_or_default()
-                                    )?
-                                }
+                            for attr in elem.attributes().iter().filter(|a| self.prints(a)) {
+                                write!(writer, " {}=\"", &attr.name)?;
+                                self.doc.entity_encoding.write_attribute_value(
+                                    attr.value.as_deref().unwrap_or_default(),
+                                    writer,
+                                )?;
+                                writer.write_char('"')?;
                             }
                             if self_closing {
                                 writer.write_str(" />")
@@ -83,7 +125,7 @@ impl<'a> Printer<'a> {
                                 }
                                 indent(self.indent, writer)?;
                             }
-                            writer.write_str(content.as_str())
+                            self.doc.entity_encoding.write_text(content, writer)
                         }
                         NodeData::Root => Ok(()),
                     }
@@ -108,6 +150,14 @@ impl<'a> Printer<'a> {
             }
         })
     }
+
+    /// Returns false for attributes namespaced with a platform other than the one printed for
+    fn prints(&self, attr: &Attribute) -> bool {
+        match (self.doc.print_platform(), attr.name.namespace.as_deref()) {
+            (Some(platform), Some(namespace)) => platform == namespace,
+            _ => true,
+        }
+    }
 }
 
 fn indent(mut n: usize, writer: &mut dyn fmt::Write) -> fmt::Result {
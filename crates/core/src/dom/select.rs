@@ -5,6 +5,8 @@ use crate::dom::*;
 pub enum Selector<'a> {
     /// Selects an element with the given tag name, e.g. `foo`
     Tag(ElementName),
+    /// Selects an element with the given local tag name in any namespace, e.g. `*|foo`
+    TagLocal(&'a str),
     /// Selects an element with the given unique id, e.g. `#id`
     Id(&'a str),
     /// Selects all elements, e.g. `*`
@@ -13,6 +15,8 @@ pub enum Selector<'a> {
     And(Box<Selector<'a>>, Box<Selector<'a>>),
     /// Selects elements which match either sub-selector, e.g. `.foo, .bar`
     Or(Box<Selector<'a>>, Box<Selector<'a>>),
+    /// Selects elements which do not match the sub-selector, e.g. `:not(.foo)`
+    Not(Box<Selector<'a>>),
     /// Selects elements which are descendants of the first sub-selector and match the second sub-selector, e.g. `ul.foo li`
     Descendant(Box<Selector<'a>>, Box<Selector<'a>>),
     /// Selects elements which are direct children of the first sub-selector and match the second sub-selector, e.g. `ul.foo > li`
@@ -31,6 +35,11 @@ pub enum Selector<'a> {
     AttributeValueSubstring(AttributeName, &'a str),
 }
 impl Selector<'_> {
+    /// Selects elements by tag name, parsing an optional `namespace:` prefix
+    pub fn tag(name: &str) -> Self {
+        Self::Tag(name.into())
+    }
+
     /// Returns true if this selection can match at most one node, which is only true when an identified
     /// node is selected or is selected using a combinator that implies exclusion. For example, selecting
     /// an identified node as a descendant/child of an arbitrary selector is guaranteed to be unique,
@@ -54,6 +63,7 @@ impl Selector<'_> {
 
         match self {
             Self::Tag(t) => t == &element.name,
+            Self::TagLocal(name) => element.name.name == *name,
             Self::Id(id) => match document.get_by_id(*id) {
                 None => false,
                 Some(identified) => node == identified,
@@ -61,6 +71,7 @@ impl Selector<'_> {
             Self::All => true,
             Self::And(l, r) => l.matches(node, document) && r.matches(node, document),
             Self::Or(l, r) => l.matches(node, document) || r.matches(node, document),
+            Self::Not(selector) => !selector.matches(node, document),
             Self::Descendant(ancestor, selector) => {
                 if !selector.matches(node, document) {
                     return false;
@@ -0,0 +1,124 @@
+//! # Headless Client
+//!
+//! Drives the merge and render pipeline of a live channel with recorded payloads instead of a
+//! connection, e.g. for offline tests and tooling. This is available without the
+//! `liveview-channels` feature.
+//!
+//! A session is replayed from the reply to joining the channel, `{"rendered": {..}, ..}`,
+//! followed by the payloads of the `diff` events in the order they were received:
+//!
+//! ```
+//! use liveview_native_core::headless::HeadlessClient;
+//!
+//! let join = r#"{"rendered": {"0": "0", "s": ["<Text>", "</Text>"]}}"#;
+//! let mut client = HeadlessClient::join(join).expect("invalid join payload");
+//! client.apply_diff(r#"{"0": "1"}"#).expect("invalid diff");
+//! assert_eq!(client.document().to_string(), "<Text>\n    1\n</Text>");
+//! ```
+
+use crate::{
+    diff::{fragment::RenderError, PatchResult},
+    dom::Document,
+};
+
+/// A client without a connection, which merges recorded payloads into its document with the
+/// same code as a connected live channel
+#[derive(Debug)]
+pub struct HeadlessClient {
+    document: Document,
+}
+
+impl HeadlessClient {
+    /// Builds the document rendered in the reply to joining a channel
+    pub fn join(join_payload: &str) -> Result<Self, RenderError> {
+        let mut payload: serde_json::Value = serde_json::from_str(join_payload)?;
+        let rendered = payload
+            .get_mut("rendered")
+            .map(serde_json::Value::take)
+            .ok_or(RenderError::NoRenderInJoinPayload)?;
+
+        let document = Document::parse_fragment_json(rendered.to_string())?;
+        Ok(Self { document })
+    }
+
+    /// Joins with `join_payload` and merges each of `diffs` in order.
+    ///
+    /// Replay stops at the first diff which fails to parse or merge, and the returned error
+    /// carries that diff's index.
+    pub fn replay<S: AsRef<str>>(join_payload: &str, diffs: &[S]) -> Result<Self, RenderError> {
+        let mut client = Self::join(join_payload)?;
+        for (index, diff) in diffs.iter().enumerate() {
+            client
+                .apply_diff(diff.as_ref())
+                .map_err(|error| RenderError::Replay {
+                    index,
+                    error: Box::new(error),
+                })?;
+        }
+        Ok(client)
+    }
+
+    /// Merges the payload of a `diff` event into the document, returning the changes a
+    /// connected client would report to its change handler
+    pub fn apply_diff(&mut self, diff_payload: &str) -> Result<Vec<PatchResult>, RenderError> {
+        let diff = serde_json::from_str(diff_payload)?;
+        self.document.merge_fragment_json(diff)
+    }
+
+    /// Returns the document as of the last merged payload
+    pub fn document(&self) -> &Document {
+        &self.document
+    }
+
+    /// Returns the document, ending the session
+    pub fn into_document(self) -> Document {
+        self.document
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DIFFS: [&str; 3] = [
+        include_str!("diff/fragment/tests/flow-1-change-1.json"),
+        include_str!("diff/fragment/tests/flow-1-change-2.json"),
+        include_str!("diff/fragment/tests/flow-1-change-3.json"),
+    ];
+
+    fn join_payload() -> String {
+        format!(
+            r#"{{"rendered": {}}}"#,
+            include_str!("diff/fragment/tests/flow-1-change-0.json")
+        )
+    }
+
+    #[test]
+    fn replays_recorded_session() {
+        let client = HeadlessClient::replay(&join_payload(), &DIFFS).expect("replay failed");
+
+        let expected = Document::parse(include_str!("diff/fragment/tests/flow-1-change-3.html"))
+            .expect("invalid render");
+        assert_eq!(client.document().to_string(), expected.to_string());
+    }
+
+    #[test]
+    fn reports_each_diff() {
+        let mut client = HeadlessClient::join(&join_payload()).expect("join failed");
+        let expected = Document::parse(include_str!("diff/fragment/tests/flow-1-change-0.html"))
+            .expect("invalid render");
+        assert_eq!(client.document().to_string(), expected.to_string());
+
+        let changes = client.apply_diff(DIFFS[0]).expect("merge failed");
+        assert!(!changes.is_empty());
+
+        assert!(matches!(
+            HeadlessClient::join(r#"{"liveview_version": "1.0.0"}"#),
+            Err(RenderError::NoRenderInJoinPayload)
+        ));
+        assert!(matches!(
+            HeadlessClient::replay(&join_payload(), &[DIFFS[0], "{"]),
+            Err(RenderError::Replay { index: 1, .. })
+        ));
+    }
+}
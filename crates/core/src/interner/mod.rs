@@ -1,4 +1,5 @@
 use core::{
+    borrow::Borrow,
     cell::{Cell, RefCell},
     cmp::{self, Ordering},
     fmt,
@@ -19,6 +20,14 @@ pub mod symbols {
     include!(concat!(env!("OUT_DIR"), "/strings.rs"));
 }
 
+/// Constants for the tags of LiveView Native templates, e.g. `element_names::VStack`, which are
+/// interned on startup so that comparing against them never interns a string.
+#[rustfmt::skip]
+#[allow(nonstandard_style, non_upper_case_globals)]
+pub mod element_names {
+    include!(concat!(env!("OUT_DIR"), "/element_names.rs"));
+}
+
 static SYMBOL_TABLE: OnceLock<SymbolTable> = OnceLock::new();
 
 struct SymbolTable(RwLock<Interner>);
@@ -170,6 +179,37 @@ impl Interner {
     pub fn get(&self, symbol: Symbol) -> &str {
         self.strings[symbol.0.as_usize()]
     }
+
+    /// Returns the number of interned strings and the memory they occupy
+    pub fn stats(&self) -> InternerStats {
+        InternerStats {
+            symbols: self.strings.len(),
+            string_bytes: self.strings.iter().map(|s| s.len()).sum(),
+            arena_bytes: self.arena.allocated_bytes(),
+        }
+    }
+}
+
+/// A snapshot of the size of the global interner, which never frees its strings
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InternerStats {
+    /// The number of interned strings, including the predefined symbols
+    pub symbols: usize,
+    /// The total length of all interned strings
+    pub string_bytes: usize,
+    /// The number of bytes reserved by the arena backing strings interned at runtime
+    pub arena_bytes: usize,
+}
+
+/// Returns the current size of the global interner
+pub fn interner_stats() -> InternerStats {
+    with_read_only_interner(Interner::stats)
+}
+
+/// Returns true if `string` is stored in the global interner, without interning it
+pub fn is_interned(string: &str) -> bool {
+    with_read_only_interner(|interner| interner.symbols.contains_key(string))
 }
 
 // If an interner exists, return it. Otherwise, prepare a fresh one.
@@ -216,6 +256,18 @@ impl InternedString {
         })
     }
 }
+impl Borrow<str> for InternedString {
+    #[inline]
+    fn borrow(&self) -> &str {
+        self.as_str()
+    }
+}
+impl AsRef<str> for InternedString {
+    #[inline]
+    fn as_ref(&self) -> &str {
+        self.as_str()
+    }
+}
 impl Hash for InternedString {
     fn hash<H: Hasher>(&self, state: &mut H) {
         self.as_str().hash(state)
@@ -366,6 +418,15 @@ impl ByteArena {
         assert!(self.ptr <= self.end);
     }
 
+    /// Returns the capacity of all chunks allocated by this arena
+    fn allocated_bytes(&self) -> usize {
+        self.chunks
+            .borrow()
+            .iter()
+            .map(|chunk| chunk.storage.capacity())
+            .sum()
+    }
+
     #[inline(never)]
     #[cold]
     fn grow(&self, needed_bytes: usize) {
@@ -520,4 +581,45 @@ mod tests {
         // Should create a new symbol resulting in an index equal to the last entry in the table
         assert_eq!(i.intern("foo").as_u32(), (i.symbols.len() - 1) as u32);
     }
+
+    #[test]
+    fn interner_stats_grow() {
+        let mut i = Interner::new();
+        let before = i.stats();
+        assert_eq!(before.arena_bytes, 0);
+
+        i.intern("InternerStatsTag");
+        let after = i.stats();
+        assert_eq!(after.symbols, before.symbols + 1);
+        assert_eq!(
+            after.string_bytes,
+            before.string_bytes + "InternerStatsTag".len()
+        );
+        assert_eq!(after.arena_bytes, PAGE);
+
+        // interning an existing string is free
+        i.intern("InternerStatsTag");
+        assert_eq!(i.stats(), after);
+    }
+
+    #[test]
+    fn interned_string_map_lookup() {
+        use std::collections::{BTreeMap, HashMap};
+
+        use smallstr::SmallString;
+
+        let id: SmallString<[u8; 16]> = SmallString::from_str("main-view");
+        let key = InternedString::intern("main-view");
+        assert!(key == id);
+        assert!(id == key);
+
+        let hashed: HashMap<InternedString, u32> = [(key, 1)].into_iter().collect();
+        assert_eq!(hashed.get(id.as_str()), Some(&1));
+        assert_eq!(hashed.get("main-view"), Some(&1));
+        assert_eq!(hashed.get("other-view"), None);
+
+        let ordered: BTreeMap<InternedString, u32> = [(key, 1)].into_iter().collect();
+        assert_eq!(ordered.get(id.as_str()), Some(&1));
+        assert_eq!(ordered.get("other-view"), None);
+    }
 }
@@ -1,12 +1,15 @@
 pub mod diff;
 pub mod dom;
+pub mod headless;
 pub mod parser;
 
 #[cfg(feature = "liveview-channels")]
 pub mod live_socket;
 
 mod interner;
-pub use self::interner::{symbols, InternedString, Symbol};
+pub use self::interner::{
+    element_names, interner_stats, is_interned, symbols, InternedString, InternerStats, Symbol,
+};
 
 #[cfg(feature = "liveview-channels")]
 phoenix_channels_client::uniffi_reexport_scaffolding!();
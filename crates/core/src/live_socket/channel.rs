@@ -1,27 +1,96 @@
-use std::{collections::HashMap, sync::Arc, time::Duration};
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, SystemTime},
+};
 
 use futures::{future::FutureExt, pin_mut, select};
 use log::{debug, error};
 use phoenix_channels_client::{Channel, Event, Number, Payload, Socket, Topic, JSON};
+use tokio::sync::{broadcast, watch};
 
-use super::{LiveSocketError, UploadConfig, UploadError};
+use super::{
+    protocol::{self, RateLimit, RateLimiter, ServerCommandHandler},
+    LiveSocketError, UploadConfig, UploadError,
+};
 use crate::{
-    diff::fragment::{Root, RootDiff},
+    diff::{
+        self,
+        fragment::{RenderError, Root, RootDiff},
+        Patch, PatchResult,
+    },
     dom::{
         ffi::{Document as FFiDocument, DocumentChangeHandler},
-        AttributeName, AttributeValue, Document, LiveChannelStatus, Selector,
+        AttributeName, AttributeValue, Document, LiveChannelStatus, NodeRef, Selector,
     },
     parser::parse,
 };
 
+/// The event name reported to a [RawPayloadObserver] for replies to pushed events
+const REPLY_EVENT: &str = "phx_reply";
+
+/// How many changes a [LiveChannel::change_stream] receiver may fall behind before it lags
+pub(crate) const CHANGE_STREAM_CAPACITY: usize = 256;
+
 #[derive(uniffi::Object)]
 pub struct LiveChannel {
     pub channel: Arc<Channel>,
+    /// The topic the channel joined, e.g. `lv:phx-F6rgg119TbUm66NB`
+    pub(crate) topic: String,
     pub join_params: HashMap<String, JSON>,
     pub socket: Arc<Socket>,
     pub join_payload: Payload,
     pub document: FFiDocument,
     pub timeout: Duration,
+    pub(crate) command_handler: Mutex<Option<Arc<dyn ServerCommandHandler>>>,
+    pub(crate) payload_observer: Mutex<Option<Arc<dyn RawPayloadObserver>>>,
+    pub(crate) diff_error_handler: Mutex<Option<Arc<dyn DiffErrorHandler>>>,
+    pub(crate) diff_applied_handler: Mutex<Option<Arc<dyn DiffAppliedHandler>>>,
+    pub(crate) diff_stats: Mutex<DiffStats>,
+    /// Sends the idle timeout to [LiveChannel::merge_diffs], waking it when the timeout changes
+    pub(crate) idle_timeout: watch::Sender<Option<Duration>>,
+    /// Whether the channel was left when idle, it is joined again before the next event is sent
+    pub(crate) idled: Mutex<bool>,
+    pub(crate) rate_limiter: Mutex<RateLimiter>,
+    /// The [Document::render_hash] of the current document, `None` until computed after a change
+    pub(crate) render_hash: Mutex<Option<u64>>,
+    /// Fans the changes of every merge out to the [LiveChannel::change_stream] receivers
+    pub(crate) changes: broadcast::Sender<PatchResult>,
+    /// Whether binary diff payloads are decoded as MessagePack, see `ConnectOpts::msgpack_diffs`
+    pub(crate) msgpack_diffs: bool,
+}
+
+/// Counts the diffs applied by [LiveChannel::merge_diffs], e.g. to detect a stalled connection
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, uniffi::Record)]
+pub struct DiffStats {
+    /// When the last diff was applied, `None` until the first one is
+    pub last_applied: Option<SystemTime>,
+    /// The number of diffs applied since the channel was joined
+    pub count: u64,
+}
+
+/// Receives every raw payload the channel processes, before it is handled.
+/// This is intended for protocol debugging and cannot affect how the payload is handled.
+#[uniffi::export(callback_interface)]
+pub trait RawPayloadObserver: Send + Sync {
+    /// `event` is the name of the event which carried `payload`, `phx_reply` for replies
+    fn on_raw_payload(&self, event: String, payload: Payload);
+}
+
+/// Receives the diffs which could not be merged into the document.
+/// The diff is skipped and the channel stays joined, so the document may no longer match the server.
+#[uniffi::export(callback_interface)]
+pub trait DiffErrorHandler: Send + Sync {
+    /// `error` describes why `diff`, the raw JSON received from the server, could not be merged
+    fn on_diff_error(&self, error: String, diff: String);
+}
+
+/// Told about every diff merged into the document, after the change handler saw its changes
+#[uniffi::export(callback_interface)]
+pub trait DiffAppliedHandler: Send + Sync {
+    /// `full_render` is true if the diff replaced the whole render, see [RootDiff::is_full_render],
+    /// in which case rebuilding the view is likely cheaper than applying the changes one by one
+    fn on_diff_applied(&self, full_render: bool);
 }
 
 #[derive(uniffi::Object)]
@@ -109,6 +178,160 @@ impl LiveChannel {
         debug!("Join payload render:\n{document}");
         Ok(document)
     }
+
+    /// Returns the patches which transform [LiveChannel::join_document] into the current
+    /// document, i.e. everything the server and local edits changed since joining
+    pub fn diff_since_join(&self) -> Result<Vec<Patch>, LiveSocketError> {
+        let joined = self.join_document()?;
+        let current = self.document.inner();
+        let current = current.lock()?;
+        Ok(diff::diff(&joined, &current))
+    }
+
+    fn record_diff_applied(&self) {
+        let mut stats = self.diff_stats.lock().expect("lock poisoned!");
+        stats.last_applied = Some(SystemTime::now());
+        stats.count += 1;
+    }
+
+    /// Parses the JSON diff `json` and applies it, see [LiveChannel::apply_diff]
+    pub(crate) fn apply_json_diff(&self, json: &str) -> Result<(), LiveSocketError> {
+        match serde_json::from_str(json) {
+            Ok(diff) => self.apply_diff(diff, || json.to_owned()),
+            Err(error) => {
+                self.report_diff_error(error.to_string(), json.to_owned());
+                Ok(())
+            }
+        }
+    }
+
+    /// Merges the decoded `diff` into the document and dispatches its server commands.
+    ///
+    /// A diff which can not be merged, or whose events can not be decoded, is logged and reported
+    /// to the [DiffErrorHandler] with the text returned by `raw`, rather than returned, so that a
+    /// single malformed diff does not end [LiveChannel::merge_diffs]. The document and its
+    /// fragment template are left as they were before the diff.
+    pub(crate) fn apply_diff(
+        &self,
+        diff: RootDiff,
+        raw: impl FnOnce() -> String,
+    ) -> Result<(), LiveSocketError> {
+        // the diff is consumed by the merge, so take what the handlers need first
+        let full_render = diff.is_full_render();
+        let command_handler = self.command_handler.lock().expect("lock poisoned!").clone();
+        let events = match command_handler {
+            Some(_) => match diff.events::<serde_json::Value>() {
+                Ok(events) => events,
+                Err(error) => {
+                    self.report_diff_error(error.to_string(), raw());
+                    return Ok(());
+                }
+            },
+            None => None,
+        };
+
+        let changes = match self.document.merge_fragment_changes(diff) {
+            Ok(changes) => changes,
+            Err(error) => {
+                self.report_diff_error(error.to_string(), raw());
+                return Ok(());
+            }
+        };
+        // sending only fails if nobody subscribed
+        for change in changes {
+            let _ = self.changes.send(change);
+        }
+
+        self.record_diff_applied();
+        // rendering the document to hash it is only worth it if someone asks for the hash
+        *self.render_hash.lock().expect("lock poisoned!") = None;
+
+        let handler = self
+            .diff_applied_handler
+            .lock()
+            .expect("lock poisoned!")
+            .clone();
+        if let Some(handler) = handler {
+            handler.on_diff_applied(full_render);
+        }
+
+        // commands are delivered after the merge so their targets exist
+        if let (Some(handler), Some(events)) = (command_handler, events) {
+            protocol::dispatch(&events, handler.as_ref());
+        }
+        Ok(())
+    }
+
+    fn report_diff_error(&self, error: String, diff: String) {
+        error!("Skipping diff which failed to merge: {error}");
+        let handler = self
+            .diff_error_handler
+            .lock()
+            .expect("lock poisoned!")
+            .clone();
+        if let Some(handler) = handler {
+            handler.on_diff_error(error, diff);
+        }
+    }
+
+    /// Subscribes to the changes merged into the document from now on, as an alternative to
+    /// [LiveChannel::set_event_handler] for async Rust consumers.
+    ///
+    /// A receiver which falls more than [CHANGE_STREAM_CAPACITY] changes behind skips the oldest
+    /// ones and gets [broadcast::error::RecvError::Lagged].
+    pub fn change_stream(&self) -> broadcast::Receiver<PatchResult> {
+        self.changes.subscribe()
+    }
+
+    fn observe_payload(&self, event: &str, payload: &Payload) {
+        let observer = self
+            .payload_observer
+            .lock()
+            .expect("lock poisoned!")
+            .clone();
+        if let Some(observer) = observer {
+            observer.on_raw_payload(event.to_owned(), payload.clone());
+        }
+    }
+}
+
+/// Builds the document rendered in the reply to joining a channel, keeping its fragment
+/// template so later diffs can be merged
+pub(crate) fn document_from_join_payload(
+    join_payload: &Payload,
+) -> Result<Document, LiveSocketError> {
+    let document = match join_payload {
+        Payload::JSONPayload {
+            json: JSON::Object { object },
+        } => {
+            if let Some(rendered) = object.get("rendered") {
+                debug!("root diff: {rendered}");
+                Some(Document::parse_fragment_json(rendered.to_string())?)
+            } else {
+                None
+            }
+        }
+        _ => None,
+    };
+    document.ok_or(LiveSocketError::NoDocumentInJoinPayload)
+}
+
+/// Decodes the full render in the reply to joining a channel
+fn rendered_from_join_payload(join_payload: &Payload) -> Result<RootDiff, LiveSocketError> {
+    match join_payload {
+        Payload::JSONPayload {
+            json: JSON::Object { object },
+        } => {
+            let rendered = object
+                .get("rendered")
+                .ok_or(LiveSocketError::NoDocumentInJoinPayload)?;
+            debug!("root diff: {rendered}");
+            let rendered =
+                serde_json::from_str(&rendered.to_string()).map_err(RenderError::from)?;
+            Ok(rendered)
+        }
+        _ => Err(LiveSocketError::NoDocumentInJoinPayload),
+    }
 }
 
 #[cfg_attr(not(target_family = "wasm"), uniffi::export(async_runtime = "tokio"))]
@@ -121,10 +344,35 @@ impl LiveChannel {
         self.channel.clone()
     }
 
+    /// Returns the topic this channel joined
+    pub fn channel_topic(&self) -> String {
+        self.topic.clone()
+    }
+
     pub fn set_event_handler(&self, handler: Box<dyn DocumentChangeHandler>) {
         self.document.set_event_handler(handler);
     }
 
+    /// Sets the handler which receives commands the server pushes alongside diffs
+    pub fn set_server_command_handler(&self, handler: Box<dyn ServerCommandHandler>) {
+        *self.command_handler.lock().expect("lock poisoned!") = Some(Arc::from(handler));
+    }
+
+    /// Sets the handler which is told about diffs that were skipped because they failed to merge
+    pub fn set_diff_error_handler(&self, handler: Box<dyn DiffErrorHandler>) {
+        *self.diff_error_handler.lock().expect("lock poisoned!") = Some(Arc::from(handler));
+    }
+
+    /// Sets the handler which is told about every diff merged into the document
+    pub fn set_diff_applied_handler(&self, handler: Box<dyn DiffAppliedHandler>) {
+        *self.diff_applied_handler.lock().expect("lock poisoned!") = Some(Arc::from(handler));
+    }
+
+    /// Sets the observer which is handed every raw payload received on this channel
+    pub fn set_raw_payload_observer(&self, observer: Box<dyn RawPayloadObserver>) {
+        *self.payload_observer.lock().expect("lock poisoned!") = Some(Arc::from(observer));
+    }
+
     pub fn get_phx_upload_id(&self, phx_target_name: &str) -> Result<String, LiveSocketError> {
         // find the upload with target equal to phx_target_name
         // retrieve the security token
@@ -165,39 +413,87 @@ impl LiveChannel {
 
     /// Blocks indefinitely, processing changes to the document using the user provided callback
     /// In `set_event_handler`
+    ///
+    /// If an idle timeout is set and no message arrives for that long, this channel is left
+    /// and this returns. The socket stays connected since the other channels of the
+    /// [LiveSocket](super::LiveSocket), e.g. live reload, share it. The channel is joined again
+    /// with a fresh render before the next message is pushed to it, e.g. by
+    /// [LiveChannel::send_event_json], [LiveChannel::submit_form], [LiveChannel::clear_flash] or
+    /// [LiveChannel::upload_file], after which `merge_diffs` has to be called again.
     pub async fn merge_diffs(&self) -> Result<(), LiveSocketError> {
         // TODO: This should probably take the event closure to send changes back to swift/kotlin
         let document = self.document.clone();
         let events = self.channel.events();
         let statuses = self.channel.statuses();
+        let mut idle_timeouts = self.idle_timeout.subscribe();
         loop {
             let event = events.event().fuse();
             let status = statuses.status().fuse();
+            // restarted by every event, status change or new timeout
+            let idle_timeout = *idle_timeouts.borrow_and_update();
+            let idle = async {
+                match idle_timeout {
+                    Some(timeout) => tokio::time::sleep(timeout).await,
+                    None => futures::future::pending().await,
+                }
+            }
+            .fuse();
+            let timeout_changed = idle_timeouts.changed().fuse();
 
-            pin_mut!(event, status);
+            pin_mut!(event, status, idle, timeout_changed);
 
             select! {
                e = event => {
                    let e = e?;
+                   self.observe_payload(&e.event.to_string(), &e.payload);
                    match e.event {
                        Event::Phoenix { phoenix } => {
                            error!("Phoenix Event for {phoenix:?} is unimplemented");
                        }
                        Event::User { user } => {
                            if user == "diff" {
-                               let Payload::JSONPayload { json } = e.payload else {
-                                   error!("Diff was not json!");
-                                   continue;
-                               };
-
-                               debug!("PAYLOAD: {json:?}");
-                               // This function merges and uses the event handler set in `set_event_handler`
+                               // These functions merge and use the event handler set in `set_event_handler`
                                // which will call back into the Swift/Kotlin.
-                               document.merge_fragment_json(&json.to_string())?;
+                               match e.payload {
+                                   Payload::JSONPayload { json } => {
+                                       let json = json.to_string();
+                                       debug!("PAYLOAD: {json}");
+                                       self.apply_json_diff(&json)?;
+                                   }
+                                   Payload::Binary { bytes } if self.msgpack_diffs => {
+                                       match RootDiff::from_msgpack(&bytes) {
+                                           Ok(diff) => {
+                                               debug!("PAYLOAD: {} msgpack bytes", bytes.len());
+                                               // only decoded as JSON to report a diff which failed to merge
+                                               let raw = || {
+                                                   diff::fragment::decode_msgpack(&bytes)
+                                                       .map(|json| json.to_string())
+                                                       .unwrap_or_default()
+                                               };
+                                               self.apply_diff(diff, raw)?;
+                                           }
+                                           Err(error) => {
+                                               error!("Skipping diff which failed to decode: {error}");
+                                           }
+                                       }
+                                   }
+                                   _ => {
+                                       error!("Diff was not json!");
+                                   }
+                               }
                            }
                        }
                    };
                }
+               _ = idle => {
+                   debug!("No messages for {idle_timeout:?}, leaving the channel");
+                   self.channel.leave().await?;
+                   *self.idled.lock().expect("lock poisoned!") = true;
+                   return Ok(());
+               }
+               _ = timeout_changed => {
+                   debug!("Idle timeout changed, restarting the timer");
+               }
                new_status = status => {
 
                    let handler = document
@@ -229,7 +525,195 @@ impl LiveChannel {
         self.join_payload.clone()
     }
 
+    /// Sets how long [LiveChannel::merge_diffs] waits without any message before leaving
+    /// the channel to save power, `None` disables the timeout. Takes effect right away,
+    /// restarting the timer.
+    pub fn set_idle_timeout(&self, timeout: Option<Duration>) {
+        self.idle_timeout.send_replace(timeout);
+    }
+
+    /// Returns when the last server diff was applied and how many have been
+    pub fn diff_stats(&self) -> DiffStats {
+        *self.diff_stats.lock().expect("lock poisoned!")
+    }
+
+    /// Discards the local edits to the document and patches it to match a full render from the
+    /// server by rejoining the channel, e.g. after a diff was skipped or the document was edited
+    /// locally.
+    ///
+    /// The patches are reported to the change handler and [LiveChannel::change_stream] like those
+    /// of a merged diff, and the document keeps all of its options. [LiveChannel::join_payload]
+    /// still returns the payload of the first join.
+    pub async fn resync(&self) -> Result<(), LiveSocketError> {
+        self.channel.leave().await?;
+        self.rejoin().await?;
+        *self.idled.lock().expect("lock poisoned!") = false;
+        Ok(())
+    }
+
+    /// Joins the channel again if it was left by the idle timeout of [LiveChannel::merge_diffs]
+    async fn resume_if_idle(&self) -> Result<(), LiveSocketError> {
+        let idled = *self.idled.lock().expect("lock poisoned!");
+        if idled {
+            debug!("Joining the channel left when idle");
+            self.rejoin().await?;
+            *self.idled.lock().expect("lock poisoned!") = false;
+        }
+        Ok(())
+    }
+
+    /// Joins the left channel and patches the document to match the render of the join reply
+    async fn rejoin(&self) -> Result<(), LiveSocketError> {
+        let join_payload = self.channel.join(self.timeout).await?;
+        let rendered = rendered_from_join_payload(&join_payload)?;
+
+        let changes = self.document.replace_fragment_changes(rendered)?;
+        // the node refs of pending and throttled events may now point to other nodes
+        self.rate_limiter.lock().expect("lock poisoned!").clear();
+        // sending only fails if nobody subscribed
+        for change in changes {
+            let _ = self.changes.send(change);
+        }
+        *self.render_hash.lock().expect("lock poisoned!") = None;
+
+        Ok(())
+    }
+
+    /// Returns a hash of the rendered document, computed on the first call after a diff is merged.
+    ///
+    /// Clients can compare it to the hash of their last render to skip re-rendering after a diff
+    /// which changed nothing.
+    pub fn render_hash(&self) -> Result<u64, LiveSocketError> {
+        let mut render_hash = self.render_hash.lock().expect("lock poisoned!");
+        match *render_hash {
+            Some(hash) => Ok(hash),
+            None => {
+                let hash = self.document.inner().lock()?.render_hash();
+                *render_hash = Some(hash);
+                Ok(hash)
+            }
+        }
+    }
+
+    /// Returns the flash messages currently rendered in the document, keyed by kind, e.g. `info` or `error`
+    pub fn flash(&self) -> HashMap<String, String> {
+        let document = self.document.inner();
+        let document = document.lock().expect("lock poisoned!");
+        protocol::flash_messages(&document)
+    }
+
+    /// Clears the flash message of kind `key`, or every message if `key` is `None`.
+    ///
+    /// The message is removed from the local document right away, before the server
+    /// acknowledges the `lv:clear-flash` event.
+    pub async fn clear_flash(&self, key: Option<String>) -> Result<(), LiveSocketError> {
+        self.resume_if_idle().await?;
+        {
+            let document = self.document.inner();
+            let mut document = document.lock()?;
+            protocol::clear_flash_messages(&mut document, key.as_deref());
+        }
+        *self.render_hash.lock().expect("lock poisoned!") = None;
+
+        let payload = protocol::clear_flash_payload(key.as_deref());
+        debug!("Clear flash: {payload}");
+        let event = Event::User {
+            user: "event".to_string(),
+        };
+        let payload = Payload::json_from_serialized(payload.to_string())?;
+
+        let reply = self.channel.call(event, payload, self.timeout).await?;
+        self.observe_payload(REPLY_EVENT, &reply);
+
+        Ok(())
+    }
+
+    /// Sends the event `payload` originating from `node` to the server, honoring the node's
+    /// `phx-debounce` and `phx-throttle` bindings.
+    ///
+    /// Returns the server's reply, or `None` if the event was dropped because a later event
+    /// of the node superseded it or the node is throttled.
+    pub async fn send_event_json(
+        &self,
+        node: Arc<NodeRef>,
+        payload: JSON,
+    ) -> Result<Option<Payload>, LiveSocketError> {
+        let node = *node;
+        let limit = {
+            let document = self.document.inner();
+            let document = document.lock()?;
+            protocol::rate_limit(&document, node)
+        };
+
+        match limit {
+            Some(RateLimit::Debounce(delay)) => {
+                let ticket = self
+                    .rate_limiter
+                    .lock()
+                    .expect("lock poisoned!")
+                    .debounce(node);
+                tokio::time::sleep(delay).await;
+                let latest = self
+                    .rate_limiter
+                    .lock()
+                    .expect("lock poisoned!")
+                    .take_latest(node, ticket);
+                if !latest {
+                    return Ok(None);
+                }
+            }
+            Some(RateLimit::Throttle(interval)) => {
+                let allowed = self
+                    .rate_limiter
+                    .lock()
+                    .expect("lock poisoned!")
+                    .throttle(node, interval);
+                if !allowed {
+                    return Ok(None);
+                }
+            }
+            None => {}
+        }
+
+        self.resume_if_idle().await?;
+        let event = Event::User {
+            user: "event".to_string(),
+        };
+        let payload = Payload::JSONPayload { json: payload };
+        let reply = self.channel.call(event, payload, self.timeout).await?;
+        self.observe_payload(REPLY_EVENT, &reply);
+
+        Ok(Some(reply))
+    }
+
+    /// Sends the `phx-submit` event of `form_node` with the current values of its named inputs,
+    /// returning the server's reply
+    pub async fn submit_form(&self, form_node: Arc<NodeRef>) -> Result<Payload, LiveSocketError> {
+        let payload = {
+            let document = self.document.inner();
+            let document = document.lock()?;
+            protocol::submit_payload(&document, *form_node)
+        }
+        .ok_or(LiveSocketError::NoSubmitBinding)?;
+        self.resume_if_idle().await?;
+
+        debug!("Form submit: {payload}");
+        let submit_event = Event::User {
+            user: "event".to_string(),
+        };
+        let submit_payload = Payload::json_from_serialized(payload.to_string())?;
+
+        let reply = self
+            .channel
+            .call(submit_event, submit_payload, self.timeout)
+            .await?;
+        self.observe_payload(REPLY_EVENT, &reply);
+
+        Ok(reply)
+    }
+
     pub async fn upload_file(&self, file: &LiveFile) -> Result<(), LiveSocketError> {
+        self.resume_if_idle().await?;
         // this is not great but we have to mimic constructing
         // this ad hoc object to send to the server
         // https://github.com/phoenixframework/phoenix_live_view/blob/b59bede3fcec6995f1d5876a520af8badc4bb7fb/priv/static/phoenix_live_view.js#L1315
@@ -272,6 +756,7 @@ impl LiveChannel {
             .await?;
 
         debug!("allow_upload RESP: {allow_upload_resp:#?}");
+        self.observe_payload(REPLY_EVENT, &allow_upload_resp);
 
         /*
         The allow upload okay response looks like:
@@ -19,6 +19,12 @@ pub enum LiveSocketError {
     DisconnectionError,
     #[error("Navigation Impossible")]
     NavigationImpossible,
+    #[error("Navigation Cancelled")]
+    NavigationCancelled,
+    #[error("Navigation To The Current Url")]
+    DuplicateNavigation,
+    #[error("No Form Was Triggered")]
+    NoTriggeredForm,
     #[error("Expected Json Payload, Was Binary")]
     PayloadNotJson,
     #[error("Could Not Parse Mime - {error}")]
@@ -98,6 +104,9 @@ pub enum LiveSocketError {
     #[error("Failed to find the data-phx-upload-ref in the join payload.")]
     NoInputRefInDocument,
 
+    #[error("The form has no phx-submit binding.")]
+    NoSubmitBinding,
+
     #[error("Failed to find the data-phx-upload-ref in the join payload.")]
     Serde { error: String },
 
@@ -1,13 +1,32 @@
+/// Wraps the future `$fut` in a `tracing` span when the `tracing` feature is enabled.
+macro_rules! instrument {
+    ($fut:expr, $($span:tt)+) => {{
+        #[cfg(feature = "tracing")]
+        let fut = tracing::Instrument::instrument($fut, tracing::info_span!($($span)+));
+        #[cfg(not(feature = "tracing"))]
+        let fut = $fut;
+        fut
+    }};
+}
+
 mod channel;
 mod error;
 mod navigation;
+mod protocol;
 mod socket;
 
 #[cfg(test)]
 mod tests;
 
-pub use channel::LiveChannel;
+pub use channel::{
+    DiffAppliedHandler, DiffErrorHandler, DiffStats, LiveChannel, RawPayloadObserver,
+};
 pub use error::{LiveSocketError, UploadError};
+pub use protocol::{
+    clear_flash_messages, flash_messages, form_values, rate_limit, RateLimit, RateLimiter,
+    ServerCommand, ServerCommandHandler, CLEAR_FLASH_EVENT, DEBOUNCE_BINDING, FLASH_GROUP_ID,
+    FOCUS_EVENT, SCROLL_TO_EVENT, SUBMIT_BINDING, THROTTLE_BINDING, TRIGGER_ACTION_BINDING,
+};
 pub use socket::LiveSocket;
 
 pub struct UploadConfig {
@@ -0,0 +1,84 @@
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+use reqwest::Url;
+
+use crate::live_socket::socket::SessionData;
+
+/// A bounded cache of the dead renders fetched while navigating, keyed by the requested url.
+///
+/// Entries older than `max_age` are not returned, and once `capacity` entries are cached the
+/// least recently used one is evicted. A cache with a capacity of zero, the default, is disabled.
+#[derive(Debug, Default)]
+pub struct DeadRenderCache {
+    capacity: usize,
+    max_age: Duration,
+    /// Ordered from least to most recently used
+    entries: VecDeque<(Url, Instant, SessionData)>,
+    /// The number of lookups answered from the cache
+    pub hits: u64,
+    /// The number of lookups which had to fetch the dead render
+    pub misses: u64,
+}
+
+impl DeadRenderCache {
+    pub fn new(capacity: usize, max_age: Duration) -> Self {
+        Self {
+            capacity,
+            max_age,
+            ..Default::default()
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.capacity > 0
+    }
+
+    /// Returns the session of `url` if it was cached no longer than `max_age` before `now`,
+    /// marking it as the most recently used. Stale entries are evicted.
+    pub fn get(&mut self, url: &Url, now: Instant) -> Option<SessionData> {
+        if !self.is_enabled() {
+            return None;
+        }
+
+        let found = self
+            .entries
+            .iter()
+            .position(|(cached, _, _)| cached == url)
+            .and_then(|index| self.entries.remove(index))
+            .filter(|(_, fetched, _)| now.saturating_duration_since(*fetched) <= self.max_age);
+
+        match found {
+            Some((url, fetched, session)) => {
+                self.hits += 1;
+                self.entries.push_back((url, fetched, session.clone()));
+                Some(session)
+            }
+            None => {
+                self.misses += 1;
+                None
+            }
+        }
+    }
+
+    /// Caches the session of `url` as fetched at `now`, evicting the least recently used
+    /// entry if the cache is full
+    pub fn insert(&mut self, url: Url, session: SessionData, now: Instant) {
+        if !self.is_enabled() {
+            return;
+        }
+
+        self.remove(&url);
+        if self.entries.len() == self.capacity {
+            self.entries.pop_front();
+        }
+        self.entries.push_back((url, now, session));
+    }
+
+    /// Evicts the session of `url`, e.g. when the server rejected it
+    pub fn remove(&mut self, url: &Url) {
+        self.entries.retain(|(cached, _, _)| cached != url);
+    }
+}
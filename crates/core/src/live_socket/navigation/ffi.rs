@@ -1,13 +1,23 @@
 //! # FFI Navigation Types
 //!
 //! Types and utilities for interacting with the navigation API for the FFI api consumers.
-use std::collections::HashMap;
+use std::{
+    collections::HashMap,
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
+use futures::future::{abortable, Aborted};
 use phoenix_channels_client::{Payload, Socket, JSON};
-use reqwest::Url;
+use reqwest::{header::CONTENT_TYPE, Url};
+use serde::{Deserialize, Serialize};
+
+use crate::dom::ffi::Document as FFiDocument;
 
 pub type HistoryId = u64;
 const RETRY_REASONS: &[&str] = &["stale", "unauthorized"];
+/// The header `Plug.CSRFProtection` reads the session's token from
+const CSRF_HEADER: &str = "x-csrf-token";
 
 #[uniffi::export(callback_interface)]
 pub trait NavEventHandler: Send + Sync {
@@ -17,6 +27,38 @@ pub trait NavEventHandler: Send + Sync {
     fn handle_event(&self, event: NavEvent) -> HandlerResponse;
 }
 
+#[uniffi::export(callback_interface)]
+pub trait UrlRewriter: Send + Sync {
+    /// Maps the url requested by a navigation to the url that is actually fetched.
+    /// The history entry keeps the original url. Return the input unchanged to opt out.
+    fn rewrite(&self, url: String) -> String;
+}
+
+#[uniffi::export(callback_interface)]
+pub trait ReconnectUrlProvider: Send + Sync {
+    /// Called before a navigation reconnects with the url whose dead render is about to be
+    /// fetched. Return an alternate url to reconnect to instead, e.g. on a failover host,
+    /// or `None` to keep it.
+    fn reconnect_url(&self, url: String) -> Option<String>;
+}
+
+#[uniffi::export(callback_interface)]
+pub trait DocumentReplacedHandler: Send + Sync {
+    /// Called once a navigation has joined a new channel, with the freshly rendered
+    /// document that replaces the previous one wholesale rather than being diffed into it.
+    fn on_document_replaced(&self, document: Arc<FFiDocument>);
+}
+
+#[uniffi::export(callback_interface)]
+pub trait ConnectionEventHandler: Send + Sync {
+    /// Called when a navigation had to replace the underlying websocket, i.e. the server
+    /// rejected the join as stale and a new dead render was fetched to connect again.
+    fn on_socket_reconnected(&self);
+    /// Called when [LiveSocket::reload] or [LiveSocket::resume] rejoined the current view,
+    /// which mounts it again on the server.
+    fn on_view_reloaded(&self);
+}
+
 /// User emitted response from [NavEventHandler::handle_event].
 /// Determines whether or not the default navigation action is taken.
 #[derive(uniffi::Enum, Clone, Debug, PartialEq, Default)]
@@ -26,6 +68,9 @@ pub enum HandlerResponse {
     Default,
     /// Return this to cancel the navigation before it occurs.
     PreventDefault,
+    /// Same as [HandlerResponse::PreventDefault], the reason is logged and
+    /// retained on the navigation context for debugging.
+    PreventDefaultWithReason(String),
 }
 
 #[derive(uniffi::Enum, Clone, Debug, PartialEq)]
@@ -40,7 +85,7 @@ pub enum NavEventType {
     Traverse,
 }
 
-#[derive(uniffi::Record, Clone, Debug, PartialEq)]
+#[derive(uniffi::Record, Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct NavHistoryEntry {
     /// The target url.
     pub url: String,
@@ -48,6 +93,9 @@ pub struct NavHistoryEntry {
     pub id: HistoryId,
     /// state passed in by the user, to be passed in to the navigation event callback.
     pub state: Option<Vec<u8>>,
+    /// Typed UI state, such as scroll offsets, to restore when revisiting this entry.
+    #[uniffi(default = None)]
+    pub restoration_data: Option<HashMap<String, String>>,
 }
 
 /// An event emitted when the user navigates between views.
@@ -90,6 +138,14 @@ pub struct NavOptions {
     /// revisiting a given view.
     #[uniffi(default = None)]
     pub state: Option<Vec<u8>>,
+    /// Typed restoration data, such as scroll position, stored on the new history entry
+    /// alongside the opaque `state`.
+    #[uniffi(default = None)]
+    pub restoration_data: Option<HashMap<String, String>>,
+    /// Extra HTTP headers sent with the dead render request if this navigation has to
+    /// reconnect, overriding the headers of the same name in the socket's [ConnectOpts].
+    #[uniffi(default = None)]
+    pub headers: Option<HashMap<String, String>>,
 }
 
 impl NavEvent {
@@ -116,21 +172,66 @@ impl NavEvent {
     }
 }
 
-use super::{super::error::LiveSocketError, LiveSocket, NavCtx};
-use crate::live_socket::{socket::SessionData, LiveChannel};
+use super::{super::error::LiveSocketError, DeadRenderCache, LiveSocket, NavCtx};
+use crate::live_socket::{
+    protocol::{self, FormAction},
+    socket::{ConnectOpts, SessionData},
+    LiveChannel,
+};
 
 impl LiveSocket {
     /// Tries to navigate to the current item in the NavCtx.
     /// changing state in one fell swoop if initialilization succeeds
+    ///
+    /// With a [FormAction] the current item is the form's target, which is requested with the
+    /// form's values before reconnecting instead of joining the existing socket.
     async fn try_nav(
         &self,
         join_params: Option<HashMap<String, JSON>>,
+        headers: Option<HashMap<String, String>>,
+        form_action: Option<FormAction>,
     ) -> Result<LiveChannel, LiveSocketError> {
         let current = self
             .current()
             .ok_or(LiveSocketError::NavigationImpossible)?;
 
-        let url = Url::parse(&current.url)?;
+        let url = {
+            let nav_ctx = self.navigation_ctx.lock().expect("lock poison");
+            nav_ctx.rewrite_url(&Url::parse(&current.url)?)
+        };
+
+        if let Some(FormAction { method, body, .. }) = form_action {
+            let (connect_opts, session_url, csrf_token) = {
+                let session_data = self.session_data.try_lock()?;
+                (
+                    session_data.connect_opts.clone(),
+                    session_data.url.clone(),
+                    session_data.csrf_token.clone(),
+                )
+            };
+
+            let mut form_headers = HashMap::new();
+            if body.is_some() {
+                form_headers.insert(
+                    CONTENT_TYPE.to_string(),
+                    "application/x-www-form-urlencoded".to_string(),
+                );
+                // the token is only sent back to the origin which issued it
+                if url.origin() == session_url.origin() {
+                    form_headers.insert(CSRF_HEADER.to_string(), csrf_token);
+                }
+            }
+            let options = ConnectOpts {
+                method: Some(method),
+                body,
+                ..connect_opts
+            }
+            .with_extra_headers(headers)
+            .with_extra_headers(Some(form_headers));
+
+            // the response to a submission is never cached
+            return self.reconnect(url, join_params, options, false).await;
+        }
 
         match self
             .join_liveview_channel(join_params.clone(), url.to_string().into())
@@ -159,26 +260,97 @@ impl LiveSocket {
                     });
                 }
 
-                let format = self.session_data.try_lock()?.format.clone();
-                let options = self.session_data.try_lock()?.connect_opts.clone();
+                let url = self.reconnect_url(url);
+                let options = self
+                    .session_data
+                    .try_lock()?
+                    .connect_opts
+                    .clone()
+                    .with_extra_headers(headers);
 
-                let session_data = SessionData::request(&url, &format, options).await?;
+                self.reconnect(url, join_params, options, true).await
+            }
+            // Just reconnect or bail
+            Ok(chan) => Ok(chan),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Fetches the dead render of `url` with `options`, or takes it from the [DeadRenderCache]
+    /// if `use_cache` is set, then replaces the socket and joins the new view.
+    async fn reconnect(
+        &self,
+        url: Url,
+        join_params: Option<HashMap<String, JSON>>,
+        options: ConnectOpts,
+        use_cache: bool,
+    ) -> Result<LiveChannel, LiveSocketError> {
+        let format = self.session_data.try_lock()?.format.clone();
+        let http_client = self.session_data.try_lock()?.http_client.clone();
+
+        let mut cached = if use_cache {
+            self.dead_render_cache
+                .lock()
+                .expect("lock poison")
+                .get(&url, Instant::now())
+        } else {
+            None
+        };
+
+        loop {
+            let from_cache = cached.is_some();
+
+            let reconnect = async {
+                let session_data = match cached.take() {
+                    Some(session_data) => session_data,
+                    None => {
+                        let session_data = SessionData::request_with_client(
+                            &url,
+                            &format,
+                            options.clone(),
+                            http_client.clone(),
+                        )
+                        .await?;
+                        if use_cache {
+                            self.dead_render_cache.lock().expect("lock poison").insert(
+                                url.clone(),
+                                session_data.clone(),
+                                Instant::now(),
+                            );
+                        }
+                        session_data
+                    }
+                };
                 let websocket_url = session_data.get_live_socket_url()?;
                 let socket =
                     Socket::spawn(websocket_url, Some(session_data.cookies.clone())).await?;
+                Ok::<_, LiveSocketError>((session_data, socket))
+            };
+
+            let (session_data, socket) = instrument!(reconnect, "reconnect", url = %url).await?;
 
-                self.socket()
-                    .disconnect()
-                    .await
-                    .map_err(|_| LiveSocketError::DisconnectionError)?;
+            self.socket()
+                .disconnect()
+                .await
+                .map_err(|_| LiveSocketError::DisconnectionError)?;
 
-                *self.socket.try_lock()? = socket;
-                *self.session_data.try_lock()? = session_data;
-                self.join_liveview_channel(join_params, None).await
+            *self.socket.try_lock()? = socket;
+            *self.session_data.try_lock()? = session_data;
+            if let Some(handler) = self.connection_event_handler() {
+                handler.on_socket_reconnected();
+            }
+
+            match self.join_liveview_channel(join_params.clone(), None).await {
+                Err(e) if from_cache => {
+                    // the cached session may have expired, fetch a fresh dead render and retry once
+                    log::debug!("Joining with a cached dead render of `{url}` failed: {e}");
+                    self.dead_render_cache
+                        .lock()
+                        .expect("lock poison")
+                        .remove(&url);
+                }
+                channel => return channel,
             }
-            // Just reconnect or bail
-            Ok(chan) => Ok(chan),
-            Err(e) => Err(e),
         }
     }
 
@@ -186,6 +358,8 @@ impl LiveSocket {
     async fn try_nav_outer<F>(
         &self,
         join_params: Option<HashMap<String, JSON>>,
+        headers: Option<HashMap<String, String>>,
+        form_action: Option<FormAction>,
         nav_action: F,
     ) -> Result<LiveChannel, LiveSocketError>
     where
@@ -194,27 +368,99 @@ impl LiveSocket {
         // Tries to complete the nav action, updating state,
         // this may be cancelled by the user or by the navigation
         // being impossiblem, such as back navigation on an empty stack.
-        let new_id = {
+        let (previous_ctx, new_id) = {
             let mut ctx = self.navigation_ctx.lock().expect("lock poison");
-            nav_action(&mut ctx)
+            let previous_ctx = ctx.clone();
+            (previous_ctx, nav_action(&mut ctx))
         };
 
-        if new_id.is_none() {
+        let Some(new_id) = new_id else {
             return Err(LiveSocketError::NavigationImpossible);
         };
 
+        let (nav, abort_handle) = abortable(self.try_nav(join_params, headers, form_action));
+        *self.pending_navigation.lock().expect("lock poison") = Some((new_id, abort_handle));
+        let result = nav.await;
+        {
+            let mut pending = self.pending_navigation.lock().expect("lock poison");
+            if pending.as_ref().is_some_and(|(id, _)| *id == new_id) {
+                pending.take();
+            }
+        }
+
         // actually return the update liveview channel
-        match self.try_nav(join_params).await {
-            Ok(channel) => Ok(channel),
-            Err(e) => Err(e),
+        match result {
+            Err(Aborted) => {
+                // restore the history unless another navigation has since replaced this one
+                let mut ctx = self.navigation_ctx.lock().expect("lock poison");
+                if ctx.current().is_some_and(|entry| entry.id == new_id) {
+                    *ctx = previous_ctx;
+                }
+                Err(LiveSocketError::NavigationCancelled)
+            }
+            Ok(Ok(channel)) => {
+                let handler = self
+                    .document_replaced_handler
+                    .lock()
+                    .expect("lock poison")
+                    .clone();
+                if let Some(handler) = handler {
+                    handler.on_document_replaced(channel.document().into());
+                }
+                Ok(channel)
+            }
+            Ok(Err(e)) => Err(e),
         }
     }
+
+    /// Returns the url a reconnect to `url` should target, as chosen by the [ReconnectUrlProvider]
+    fn reconnect_url(&self, url: Url) -> Url {
+        let provider = self
+            .reconnect_url_provider
+            .lock()
+            .expect("lock poison")
+            .clone();
+        let Some(alternate) = provider.and_then(|provider| provider.reconnect_url(url.to_string()))
+        else {
+            return url;
+        };
+
+        match Url::parse(&alternate) {
+            Ok(alternate) => alternate,
+            Err(e) => {
+                log::warn!("Reconnect url provider returned an invalid url `{alternate}`: {e}");
+                url
+            }
+        }
+    }
+
+    fn connection_event_handler(&self) -> Option<Arc<dyn ConnectionEventHandler>> {
+        self.connection_event_handler
+            .lock()
+            .expect("lock poison")
+            .clone()
+    }
+
+    /// Notifies the [ConnectionEventHandler] once the current view was rejoined
+    fn view_reloaded(
+        &self,
+        result: Result<LiveChannel, LiveSocketError>,
+    ) -> Result<LiveChannel, LiveSocketError> {
+        if let (Ok(_), Some(handler)) = (&result, self.connection_event_handler()) {
+            handler.on_view_reloaded();
+        }
+        result
+    }
 }
 
 #[cfg_attr(not(target_family = "wasm"), uniffi::export(async_runtime = "tokio"))]
 impl LiveSocket {
     /// Navigates the socket to a new URL, reusing the previous channel's connection parameters, closing it safely,
     /// and emitting a new [LiveChannel]
+    ///
+    /// Pushing the url of the current entry without new state does nothing and returns
+    /// [LiveSocketError::DuplicateNavigation], the current channel stays connected and can be kept.
+    /// Use [LiveSocket::reload] to reconnect to the current view.
     pub async fn navigate(
         &self,
         url: String,
@@ -222,8 +468,70 @@ impl LiveSocket {
         opts: NavOptions,
     ) -> Result<LiveChannel, LiveSocketError> {
         let url = Url::parse(&url)?;
-        self.try_nav_outer(join_params, |ctx| ctx.navigate(url, opts, true))
-            .await
+        if self
+            .navigation_ctx
+            .lock()
+            .expect("lock poison")
+            .is_duplicate(&url, &opts)
+        {
+            log::debug!("Ignoring navigation to the current url `{url}`");
+            return Err(LiveSocketError::DuplicateNavigation);
+        }
+        let headers = opts.headers.clone();
+        self.try_nav_outer(join_params, headers, None, |ctx| {
+            ctx.navigate(url, opts, true)
+        })
+        .await
+    }
+
+    /// Submits the form of `channel` whose `phx-trigger-action` binding was set by the server,
+    /// as the JS client does once the binding is rendered, e.g. to let a controller sign in.
+    ///
+    /// The form's values are sent to its `action` over HTTP, then the view rendered in response,
+    /// usually after a redirect, is joined on a new socket. The submission pushes a history
+    /// entry for the form's action, whose url is then replaced by the page the response ended on,
+    /// which the [NavEventHandler] sees as a [NavEventType::Replace] event.
+    /// Returns [LiveSocketError::NoTriggeredForm] without navigating if no form of the channel
+    /// is triggered.
+    pub async fn submit_triggered_form(
+        &self,
+        channel: Arc<LiveChannel>,
+        join_params: Option<HashMap<String, JSON>>,
+        opts: NavOptions,
+    ) -> Result<LiveChannel, LiveSocketError> {
+        let base = match self.current() {
+            Some(entry) => Url::parse(&entry.url)?,
+            None => self.session_data.try_lock()?.url.clone(),
+        };
+        let form_action = {
+            let document = channel.document().inner();
+            let document = document.lock()?;
+            protocol::triggered_form(&document)
+                .and_then(|form| protocol::form_action(&document, form, &base))
+        };
+        let form_action = form_action.ok_or(LiveSocketError::NoTriggeredForm)?;
+
+        // a form posting to the current url reloads it instead of pushing a duplicate entry
+        let url = form_action.url.clone();
+        let headers = opts.headers.clone();
+        let channel = self
+            .try_nav_outer(join_params, headers, Some(form_action), |ctx| {
+                if ctx.is_duplicate(&url, &opts) {
+                    ctx.reload(opts.extra_event_info, true)
+                } else {
+                    ctx.navigate(url, opts, true)
+                }
+            })
+            .await?;
+
+        // the entry shows the page the submission ended up on, not the form's action
+        let page_url = self.session_data.try_lock()?.page_url();
+        self.navigation_ctx
+            .lock()
+            .expect("lock poison")
+            .set_current_url(&page_url, true);
+
+        Ok(channel)
     }
 
     /// Reload the current channel.
@@ -232,8 +540,26 @@ impl LiveSocket {
         join_params: Option<HashMap<String, JSON>>,
         info: Option<Vec<u8>>,
     ) -> Result<LiveChannel, LiveSocketError> {
-        self.try_nav_outer(join_params, |ctx| ctx.reload(info, true))
-            .await
+        let result = self
+            .try_nav_outer(join_params, None, None, |ctx| ctx.reload(info, true))
+            .await;
+        self.view_reloaded(result)
+    }
+
+    /// Reconnects to the current entry, e.g. after the channel disconnected when idle.
+    ///
+    /// Unlike [LiveSocket::reload] no navigation event is emitted, the history and the
+    /// state stored in the current entry are left untouched.
+    pub async fn resume(
+        &self,
+        join_params: Option<HashMap<String, JSON>>,
+    ) -> Result<LiveChannel, LiveSocketError> {
+        let result = self
+            .try_nav_outer(join_params, None, None, |ctx| {
+                ctx.current().map(|entry| entry.id)
+            })
+            .await;
+        self.view_reloaded(result)
     }
 
     /// Navigates the socket to the previous entry in the stack.
@@ -242,7 +568,7 @@ impl LiveSocket {
         join_params: Option<HashMap<String, JSON>>,
         info: Option<Vec<u8>>,
     ) -> Result<LiveChannel, LiveSocketError> {
-        self.try_nav_outer(join_params, |ctx| ctx.back(info, true))
+        self.try_nav_outer(join_params, None, None, |ctx| ctx.back(info, true))
             .await
     }
 
@@ -253,7 +579,7 @@ impl LiveSocket {
         join_params: Option<HashMap<String, JSON>>,
         info: Option<Vec<u8>>,
     ) -> Result<LiveChannel, LiveSocketError> {
-        self.try_nav_outer(join_params, |ctx| ctx.forward(info, true))
+        self.try_nav_outer(join_params, None, None, |ctx| ctx.forward(info, true))
             .await
     }
 
@@ -265,8 +591,24 @@ impl LiveSocket {
         join_params: Option<HashMap<String, JSON>>,
         info: Option<Vec<u8>>,
     ) -> Result<LiveChannel, LiveSocketError> {
-        self.try_nav_outer(join_params, |ctx| ctx.traverse_to(id, info, true))
-            .await
+        self.try_nav_outer(join_params, None, None, |ctx| {
+            ctx.traverse_to(id, info, true)
+        })
+        .await
+    }
+
+    /// Cancels the navigation which is currently fetching or joining its new view, restoring
+    /// the history to its state before that navigation. The cancelled call returns
+    /// [LiveSocketError::NavigationCancelled]. Returns false if no navigation was in flight.
+    pub fn cancel_pending_navigation(&self) -> bool {
+        let pending = self.pending_navigation.lock().expect("lock poison").take();
+        match pending {
+            Some((_, handle)) => {
+                handle.abort();
+                true
+            }
+            None => false,
+        }
     }
 
     /// Returns whether navigation backward in history is possible.
@@ -299,9 +641,62 @@ impl LiveSocket {
         nav_ctx.current()
     }
 
+    /// Returns the reason given the last time the navigation handler
+    /// prevented a navigation, if it supplied one.
+    pub fn last_prevented_reason(&self) -> Option<String> {
+        let nav_ctx = self.navigation_ctx.lock().expect("lock poison");
+        nav_ctx.last_prevented_reason()
+    }
+
     /// Sets the handler for navigation events.
     pub fn set_event_handler(&self, handler: Box<dyn NavEventHandler>) {
         let mut nav_ctx = self.navigation_ctx.lock().expect("lock poison");
         nav_ctx.set_event_handler(handler.into())
     }
+
+    /// Sets the handler notified when a navigation replaces the document with a newly joined one.
+    pub fn set_document_replaced_handler(&self, handler: Box<dyn DocumentReplacedHandler>) {
+        *self.document_replaced_handler.lock().expect("lock poison") = Some(handler.into());
+    }
+
+    /// Sets the handler notified when the socket reconnects or the current view is reloaded.
+    pub fn set_connection_event_handler(&self, handler: Box<dyn ConnectionEventHandler>) {
+        *self.connection_event_handler.lock().expect("lock poison") = Some(handler.into());
+    }
+
+    /// Sets the callback consulted for an alternate url before each reconnect.
+    pub fn set_reconnect_url_provider(&self, provider: Box<dyn ReconnectUrlProvider>) {
+        *self.reconnect_url_provider.lock().expect("lock poison") = Some(provider.into());
+    }
+
+    /// Sets the callback used to rewrite urls before they are fetched during navigation.
+    pub fn set_url_rewriter(&self, rewriter: Box<dyn UrlRewriter>) {
+        let mut nav_ctx = self.navigation_ctx.lock().expect("lock poison");
+        nav_ctx.set_url_rewriter(rewriter.into())
+    }
+
+    /// Enables caching up to `capacity` dead renders fetched when a navigation has to reconnect,
+    /// which are reused for `max_age_ms` milliseconds, e.g. when navigating back. The dead render
+    /// of the current entry is cached right away, a `capacity` of zero disables the cache.
+    pub fn set_dead_render_cache(&self, capacity: u32, max_age_ms: u64) {
+        let mut cache = DeadRenderCache::new(capacity as usize, Duration::from_millis(max_age_ms));
+        if let Some(current) = self.current() {
+            if let Ok(url) = Url::parse(&current.url) {
+                let url = self
+                    .navigation_ctx
+                    .lock()
+                    .expect("lock poison")
+                    .rewrite_url(&url);
+                let session_data = self.session_data.lock().expect("lock poison").clone();
+                cache.insert(url, session_data, Instant::now());
+            }
+        }
+        *self.dead_render_cache.lock().expect("lock poison") = cache;
+    }
+
+    /// Sets whether navigating to the url of the current entry reconnects, by default it is ignored.
+    pub fn set_allow_duplicate_navigation(&self, allow: bool) {
+        let mut nav_ctx = self.navigation_ctx.lock().expect("lock poison");
+        nav_ctx.set_allow_duplicate_navigation(allow)
+    }
 }
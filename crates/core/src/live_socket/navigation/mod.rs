@@ -1,9 +1,12 @@
+mod cache;
 mod ffi;
 
 use super::socket::LiveSocket;
+pub use cache::DeadRenderCache;
 pub use ffi::*;
 use reqwest::Url;
-use std::sync::Arc;
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, sync::Arc};
 
 #[derive(Clone, Default)]
 struct HandlerInternal(pub Option<Arc<dyn NavEventHandler>>);
@@ -19,6 +22,20 @@ impl std::fmt::Debug for HandlerInternal {
     }
 }
 
+#[derive(Clone, Default)]
+struct RewriterInternal(pub Option<Arc<dyn UrlRewriter>>);
+
+impl std::fmt::Debug for RewriterInternal {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.0.is_some() {
+            write!(f, "Rewriter Active")?;
+        } else {
+            write!(f, "No Rewriter Present")?;
+        };
+        Ok(())
+    }
+}
+
 /// The internal navigation context.
 /// handles the history state of the visited views.
 #[derive(Debug, Clone, Default)]
@@ -31,6 +48,20 @@ pub struct NavCtx {
     id_source: HistoryId,
     /// user provided callback
     navigation_event_handler: HandlerInternal,
+    /// The reason given by the handler the last time it prevented a navigation.
+    last_prevented_reason: Option<String>,
+    /// user provided url rewriter, applied before fetching a view
+    url_rewriter: RewriterInternal,
+    /// When false, navigating to the url of the current entry is a no-op.
+    allow_duplicate_navigation: bool,
+}
+
+/// The history of a [NavCtx] without its handlers, as saved by [LiveSocket::export_session]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct NavHistory {
+    history: Vec<NavHistoryEntry>,
+    future: Vec<NavHistoryEntry>,
+    id_source: HistoryId,
 }
 
 impl NavHistoryEntry {
@@ -39,16 +70,32 @@ impl NavHistoryEntry {
             url: url.to_string(),
             id,
             state,
+            restoration_data: None,
         }
     }
 }
 
 impl NavCtx {
     /// Navigate to `url` with behavior and metadata specified in `opts`.
-    /// Returns the current history ID if changed
+    /// Returns the current history ID if changed, pushing the url of the current
+    /// entry without new state does nothing unless duplicate navigations are allowed.
     pub fn navigate(&mut self, url: Url, opts: NavOptions, emit_event: bool) -> Option<HistoryId> {
+        if self.is_duplicate(&url, &opts) {
+            log::debug!("Ignoring navigation to the current url `{url}`");
+            return None;
+        }
+
+        #[cfg(feature = "tracing")]
+        let span = tracing::info_span!(
+            "navigate",
+            url = %url,
+            history_id = tracing::field::Empty
+        )
+        .entered();
+
         let action = opts.action.clone();
-        let next_dest = self.speculative_next_dest(&url, opts.state.clone());
+        let next_dest =
+            self.speculative_next_dest(&url, opts.state.clone(), opts.restoration_data.clone());
         let next_id = next_dest.id;
 
         let event = {
@@ -64,7 +111,9 @@ impl NavCtx {
 
         match self.handle_event(event, emit_event) {
             HandlerResponse::Default => {}
-            HandlerResponse::PreventDefault => return None,
+            HandlerResponse::PreventDefault | HandlerResponse::PreventDefaultWithReason(_) => {
+                return None
+            }
         };
 
         match action {
@@ -75,6 +124,10 @@ impl NavCtx {
         // successful navigation invalidates previously coalesced state from
         // calls to `back`
         self.future.clear();
+
+        #[cfg(feature = "tracing")]
+        span.record("history_id", next_id);
+
         Some(next_id)
     }
 
@@ -115,7 +168,9 @@ impl NavCtx {
 
         match self.handle_event(event, emit_event) {
             HandlerResponse::Default => {}
-            HandlerResponse::PreventDefault => return None,
+            HandlerResponse::PreventDefault | HandlerResponse::PreventDefaultWithReason(_) => {
+                return None
+            }
         };
 
         Some(id)
@@ -148,7 +203,7 @@ impl NavCtx {
                 self.future.push(previous);
                 out
             }
-            HandlerResponse::PreventDefault => None,
+            HandlerResponse::PreventDefault | HandlerResponse::PreventDefaultWithReason(_) => None,
         }
     }
 
@@ -174,7 +229,7 @@ impl NavCtx {
                 self.push_entry(next);
                 out
             }
-            HandlerResponse::PreventDefault => None,
+            HandlerResponse::PreventDefault | HandlerResponse::PreventDefaultWithReason(_) => None,
         }
     }
 
@@ -198,7 +253,9 @@ impl NavCtx {
 
             match self.handle_event(event, emit_event) {
                 HandlerResponse::Default => {}
-                HandlerResponse::PreventDefault => return None,
+                HandlerResponse::PreventDefault | HandlerResponse::PreventDefaultWithReason(_) => {
+                    return None
+                }
             };
 
             // All entries except the target
@@ -215,7 +272,9 @@ impl NavCtx {
 
             match self.handle_event(event, emit_event) {
                 HandlerResponse::Default => {}
-                HandlerResponse::PreventDefault => return None,
+                HandlerResponse::PreventDefault | HandlerResponse::PreventDefaultWithReason(_) => {
+                    return None
+                }
             };
 
             // All entries including the target, which will be at the front.
@@ -227,11 +286,51 @@ impl NavCtx {
         None
     }
 
+    /// Returns a copy of the history, see [NavCtx::restore_history]
+    pub fn export_history(&self) -> NavHistory {
+        NavHistory {
+            history: self.history.clone(),
+            future: self.future.clone(),
+            id_source: self.id_source,
+        }
+    }
+
+    /// Replaces the history with one from [NavCtx::export_history], keeping the handlers
+    /// and settings. No events are emitted.
+    pub fn restore_history(&mut self, history: NavHistory) {
+        self.history = history.history;
+        self.future = history.future;
+        self.id_source = history.id_source;
+    }
+
     /// Returns the current history entry and state
     pub fn current(&self) -> Option<NavHistoryEntry> {
         self.history.last().cloned()
     }
 
+    /// Sets the url of the current entry, e.g. to the url a submitted form was redirected to.
+    /// A changed url is reported to the handler as a [NavEventType::Replace] event, the entry
+    /// is updated regardless of the response since the view was already loaded.
+    pub(crate) fn set_current_url(&mut self, url: &Url, emit_event: bool) {
+        let Some(previous) = self.current() else {
+            return;
+        };
+        if previous.url == url.as_str() {
+            return;
+        }
+
+        let next = NavHistoryEntry {
+            url: url.to_string(),
+            ..previous.clone()
+        };
+        let event = NavEvent::new(NavEventType::Replace, next.clone(), Some(previous), None);
+        self.handle_event(event, emit_event);
+
+        if let Some(current) = self.history.last_mut() {
+            *current = next;
+        }
+    }
+
     fn replace_entry(&mut self, history_entry: NavHistoryEntry) {
         if let Some(last) = self.history.last_mut() {
             self.id_source += 1;
@@ -251,25 +350,88 @@ impl NavCtx {
         self.navigation_event_handler.0 = Some(handler)
     }
 
+    pub fn set_url_rewriter(&mut self, rewriter: Arc<dyn UrlRewriter>) {
+        self.url_rewriter.0 = Some(rewriter)
+    }
+
+    /// When `allow` is true, pushing the url of the current entry adds a new
+    /// entry instead of being ignored.
+    pub fn set_allow_duplicate_navigation(&mut self, allow: bool) {
+        self.allow_duplicate_navigation = allow
+    }
+
+    /// Returns true if navigating to `url` with `opts` would be ignored because it
+    /// pushes the url of the current entry without any new state. Replacing the
+    /// current entry, or navigating with a new `state` or `restoration_data`, is
+    /// never a duplicate.
+    pub fn is_duplicate(&self, url: &Url, opts: &NavOptions) -> bool {
+        let is_push = matches!(opts.action, None | Some(NavAction::Push));
+        let has_state = opts.state.is_some() || opts.restoration_data.is_some();
+
+        !self.allow_duplicate_navigation
+            && is_push
+            && !has_state
+            && self
+                .history
+                .last()
+                .is_some_and(|current| current.url == url.as_str())
+    }
+
+    /// Returns the url that should actually be fetched for `url`, this is `url`
+    /// itself unless a [UrlRewriter] is set.
+    pub fn rewrite_url(&self, url: &Url) -> Url {
+        let Some(rewriter) = self.url_rewriter.0.as_ref() else {
+            return url.clone();
+        };
+
+        let rewritten = rewriter.rewrite(url.to_string());
+        match Url::parse(&rewritten) {
+            Ok(rewritten) => rewritten,
+            Err(e) => {
+                log::warn!("Url rewriter returned an invalid url `{rewritten}`: {e}");
+                url.clone()
+            }
+        }
+    }
+
     pub fn handle_event(&mut self, event: NavEvent, emit_event: bool) -> HandlerResponse {
         if !emit_event {
             return HandlerResponse::Default;
         }
 
-        if let Some(handler) = self.navigation_event_handler.0.as_ref() {
+        let response = if let Some(handler) = self.navigation_event_handler.0.as_ref() {
             handler.handle_event(event)
         } else {
             HandlerResponse::Default
+        };
+
+        if let HandlerResponse::PreventDefaultWithReason(reason) = &response {
+            log::info!("Navigation prevented by handler: {reason}");
+            self.last_prevented_reason = Some(reason.clone());
         }
+
+        response
+    }
+
+    /// Returns the reason given by the event handler the last time it
+    /// prevented a navigation with [HandlerResponse::PreventDefaultWithReason].
+    pub fn last_prevented_reason(&self) -> Option<String> {
+        self.last_prevented_reason.clone()
     }
 
     /// create a new destination if one would be added to history, this includes
     /// the next unique ID that would be issued.
-    fn speculative_next_dest(&self, url: &Url, state: Option<Vec<u8>>) -> NavHistoryEntry {
+    fn speculative_next_dest(
+        &self,
+        url: &Url,
+        state: Option<Vec<u8>>,
+        restoration_data: Option<HashMap<String, String>>,
+    ) -> NavHistoryEntry {
         NavHistoryEntry {
             id: self.id_source + 1,
             url: url.to_string(),
             state,
+            restoration_data,
         }
     }
 }
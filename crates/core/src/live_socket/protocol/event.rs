@@ -0,0 +1,71 @@
+use serde_json::Value;
+
+/// The event name the server pushes to focus an element, e.g. `push_event(socket, "lvn:focus", %{id: "name"})`
+pub const FOCUS_EVENT: &str = "lvn:focus";
+/// The event name the server pushes to scroll an element into view
+pub const SCROLL_TO_EVENT: &str = "lvn:scroll_to";
+
+/// A command pushed by the server in the `e` field of a diff
+#[derive(Debug, Clone, PartialEq, Eq, uniffi::Enum)]
+pub enum ServerCommand {
+    /// Focus the element with the given id
+    Focus { id: String },
+    /// Scroll the element with the given id into view
+    ScrollTo { id: String },
+    /// Any other pushed event, `payload` is the JSON encoded event payload
+    Dispatch { event: String, payload: String },
+}
+
+/// Receives commands pushed by the server so native clients can act on them
+#[uniffi::export(callback_interface)]
+pub trait ServerCommandHandler: Send + Sync {
+    fn on_server_command(&self, command: ServerCommand);
+}
+
+impl ServerCommand {
+    /// Parses the commands carried by `diff`, which is a list of `[event, payload]` pairs
+    /// in the `e` field. Malformed entries are skipped.
+    pub fn from_diff(diff: &Value) -> Vec<Self> {
+        diff.get("e").map(Self::from_events).unwrap_or_default()
+    }
+
+    /// Parses the `[event, payload]` pairs of the `e` field of a diff, see
+    /// [crate::diff::fragment::RootDiff::events]
+    pub fn from_events(events: &Value) -> Vec<Self> {
+        let Some(events) = events.as_array() else {
+            return vec![];
+        };
+
+        events
+            .iter()
+            .filter_map(|entry| match entry.as_array()?.as_slice() {
+                [Value::String(event), payload] => Some(Self::from_event(event, payload)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn from_event(event: &str, payload: &Value) -> Self {
+        let id = payload
+            .get("id")
+            .and_then(Value::as_str)
+            .map(ToOwned::to_owned);
+
+        match (event, id) {
+            (FOCUS_EVENT, Some(id)) => Self::Focus { id },
+            (SCROLL_TO_EVENT, Some(id)) => Self::ScrollTo { id },
+            _ => Self::Dispatch {
+                event: event.to_owned(),
+                payload: payload.to_string(),
+            },
+        }
+    }
+}
+
+/// Delivers every command in `events`, the `e` field of a diff, to `handler`, in the order
+/// they were pushed
+pub(crate) fn dispatch(events: &Value, handler: &dyn ServerCommandHandler) {
+    for command in ServerCommand::from_events(events) {
+        handler.on_server_command(command);
+    }
+}
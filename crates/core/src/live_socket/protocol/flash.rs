@@ -0,0 +1,86 @@
+use std::collections::HashMap;
+
+use serde_json::{json, Value};
+
+use crate::dom::{Document, NodeData, NodeRef};
+
+/// The id of the element the flash messages of a LiveView are rendered into
+pub const FLASH_GROUP_ID: &str = "flash-group";
+
+/// The event sent to the server to clear flash messages
+pub const CLEAR_FLASH_EVENT: &str = "lv:clear-flash";
+
+/// Returns the flash messages rendered in the flash group of `doc`, keyed by kind.
+///
+/// Like the `flash` component of Phoenix, each message is an element with the id
+/// `flash-<kind>` below the flash group, its text content is the message.
+pub fn flash_messages(doc: &Document) -> HashMap<String, String> {
+    flash_nodes(doc)
+        .into_iter()
+        .map(|(kind, node)| (kind, text_content(doc, node)))
+        .collect()
+}
+
+/// Deletes the flash message of kind `key` from `doc`, or every message if `key` is `None`.
+/// Returns true if a message was removed.
+pub fn clear_flash_messages(doc: &mut Document, key: Option<&str>) -> bool {
+    let mut cleared = false;
+    for (kind, node) in flash_nodes(doc) {
+        if key.is_none_or(|key| key == kind) {
+            doc.delete(node);
+            cleared = true;
+        }
+    }
+    cleared
+}
+
+/// Builds the payload of the `lv:clear-flash` event for `key`, the server clears every
+/// message when no key is given
+pub(crate) fn clear_flash_payload(key: Option<&str>) -> Value {
+    let value = match key {
+        Some(key) => json!({ "key": key }),
+        None => json!({}),
+    };
+
+    json!({
+        "type": "click",
+        "event": CLEAR_FLASH_EVENT,
+        "value": value,
+    })
+}
+
+/// Returns the kind and element of every flash message rendered in `doc`
+fn flash_nodes(doc: &Document) -> Vec<(String, NodeRef)> {
+    let mut nodes = vec![];
+    let Some(group) = doc.get_by_id(FLASH_GROUP_ID) else {
+        return nodes;
+    };
+
+    let mut stack: Vec<NodeRef> = doc.children(group).to_vec();
+    while let Some(node) = stack.pop() {
+        let kind = doc
+            .get(node)
+            .id()
+            .and_then(|id| id.strip_prefix("flash-").map(str::to_owned));
+        match kind {
+            Some(kind) => nodes.push((kind, node)),
+            None => stack.extend(doc.children(node)),
+        }
+    }
+
+    nodes
+}
+
+/// Joins the text below `node` with single spaces, the parser drops whitespace between elements
+fn text_content(doc: &Document, node: NodeRef) -> String {
+    let mut text = vec![];
+    let mut stack = vec![node];
+    while let Some(node) = stack.pop() {
+        if let NodeData::Leaf { value } = doc.get(node) {
+            text.push(value.trim());
+        }
+        stack.extend(doc.children(node).iter().rev());
+    }
+    text.retain(|part| !part.is_empty());
+    text.join(" ")
+}
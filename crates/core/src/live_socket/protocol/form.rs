@@ -0,0 +1,120 @@
+use phoenix_channels_client::url::{form_urlencoded, Url};
+use serde_json::{json, Value};
+
+use crate::{
+    dom::{AttributeName, Document, NodeRef, Selector},
+    live_socket::socket::Method,
+};
+
+/// The binding naming the event sent when a form is submitted
+pub const SUBMIT_BINDING: &str = "phx-submit";
+
+/// The binding the server sets on a form to have it submitted over HTTP instead of the channel
+pub const TRIGGER_ACTION_BINDING: &str = "phx-trigger-action";
+
+/// Collects the `name` and `value` of every enabled input below `form`, in document order.
+/// Inputs without a `value` attribute submit an empty string.
+pub fn form_values(doc: &Document, form: NodeRef) -> Vec<(String, String)> {
+    let mut values = vec![];
+    let mut stack: Vec<NodeRef> = doc.children(form).iter().rev().copied().collect();
+
+    while let Some(node) = stack.pop() {
+        stack.extend(doc.children(node).iter().rev().copied());
+
+        let attributes = doc.attributes_ref(node);
+        let attribute = |name: &str| {
+            attributes
+                .iter()
+                .find(|attr| attr.name.namespace.is_none() && attr.name.name == name)
+        };
+
+        if attribute("disabled").is_some() {
+            continue;
+        }
+        let Some(name) = attribute("name").and_then(|attr| attr.value.clone()) else {
+            continue;
+        };
+        let value = attribute("value")
+            .and_then(|attr| attr.value.clone())
+            .unwrap_or_default();
+        values.push((name, value));
+    }
+
+    values
+}
+
+/// Builds the payload of the `phx-submit` event for `form`, or `None` if it has no binding.
+///
+/// Like the JS client, the form's `phx-value-*` attributes are appended to the input values.
+pub(crate) fn submit_payload(doc: &Document, form: NodeRef) -> Option<Value> {
+    let event = doc
+        .get_attribute_by_name(form, SUBMIT_BINDING)?
+        .value
+        .unwrap_or_default();
+
+    let mut meta: Vec<_> = doc.phx_values(form).into_iter().collect();
+    meta.sort();
+
+    let value = form_urlencoded::Serializer::new(String::new())
+        .extend_pairs(form_values(doc, form))
+        .extend_pairs(meta)
+        .finish();
+
+    Some(json!({
+        "type": "form",
+        "event": event,
+        "value": value,
+    }))
+}
+
+/// The HTTP request submitting a form whose `phx-trigger-action` binding was set
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct FormAction {
+    pub url: Url,
+    pub method: Method,
+    /// The url encoded form values sent with a `POST`, a `GET` carries them in the query
+    pub body: Option<String>,
+}
+
+/// Returns the first form of `doc` with the `phx-trigger-action` binding
+pub(crate) fn triggered_form(doc: &Document) -> Option<NodeRef> {
+    doc.select(Selector::Attribute(AttributeName::new(
+        TRIGGER_ACTION_BINDING,
+    )))
+    .next()
+}
+
+/// Builds the request submitting `form` natively, like a browser would.
+///
+/// The form's `action` is resolved against `base`, which is also the target if there is no action.
+/// Only `method="post"` sends a body, any other method submits the values with a `GET`.
+pub(crate) fn form_action(doc: &Document, form: NodeRef, base: &Url) -> Option<FormAction> {
+    let attribute = |name: &str| {
+        doc.get_attribute_by_name(form, name)
+            .and_then(|attr| attr.value)
+    };
+
+    let mut url = match attribute("action").filter(|action| !action.trim().is_empty()) {
+        Some(action) => base.join(action.trim()).ok()?,
+        None => base.clone(),
+    };
+    let values = form_values(doc, form);
+
+    if attribute("method").is_some_and(|method| method.trim().eq_ignore_ascii_case("post")) {
+        let body = form_urlencoded::Serializer::new(String::new())
+            .extend_pairs(values)
+            .finish();
+        Some(FormAction {
+            url,
+            method: Method::Post,
+            body: Some(body),
+        })
+    } else {
+        url.query_pairs_mut().clear().extend_pairs(values);
+        Some(FormAction {
+            url,
+            method: Method::Get,
+            body: None,
+        })
+    }
+}
@@ -0,0 +1,12 @@
+mod event;
+mod flash;
+mod form;
+mod rate_limit;
+
+pub(crate) use event::dispatch;
+pub use event::{ServerCommand, ServerCommandHandler, FOCUS_EVENT, SCROLL_TO_EVENT};
+pub(crate) use flash::clear_flash_payload;
+pub use flash::{clear_flash_messages, flash_messages, CLEAR_FLASH_EVENT, FLASH_GROUP_ID};
+pub(crate) use form::{form_action, submit_payload, triggered_form, FormAction};
+pub use form::{form_values, SUBMIT_BINDING, TRIGGER_ACTION_BINDING};
+pub use rate_limit::{rate_limit, RateLimit, RateLimiter, DEBOUNCE_BINDING, THROTTLE_BINDING};
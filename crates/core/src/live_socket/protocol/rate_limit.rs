@@ -0,0 +1,134 @@
+use std::{
+    collections::HashMap,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use crate::dom::{Document, NodeRef};
+
+/// The binding delaying an event until the node stopped sending for the given milliseconds
+pub const DEBOUNCE_BINDING: &str = "phx-debounce";
+
+/// The binding limiting a node to one event per the given milliseconds
+pub const THROTTLE_BINDING: &str = "phx-throttle";
+
+/// How often the events of a node may be sent to the server
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RateLimit {
+    /// Only the last of a burst of events is sent, once the node was quiet for the duration
+    Debounce(Duration),
+    /// The first event is sent right away, further events are dropped for the duration
+    Throttle(Duration),
+}
+
+/// Reads the `phx-debounce` or `phx-throttle` binding of `node`.
+///
+/// Only millisecond values are supported, `phx-debounce="blur"` is ignored since
+/// focus is tracked by the native client.
+pub fn rate_limit(doc: &Document, node: NodeRef) -> Option<RateLimit> {
+    let millis = |name| {
+        doc.get_attribute_by_name(node, name)?
+            .value?
+            .trim()
+            .parse()
+            .ok()
+            .map(Duration::from_millis)
+    };
+
+    millis(DEBOUNCE_BINDING)
+        .map(RateLimit::Debounce)
+        .or_else(|| millis(THROTTLE_BINDING).map(RateLimit::Throttle))
+}
+
+/// The source of the current time of a [RateLimiter]
+#[derive(Clone)]
+struct Clock(Arc<dyn Fn() -> Instant + Send + Sync>);
+
+impl Default for Clock {
+    fn default() -> Self {
+        Self(Arc::new(Instant::now))
+    }
+}
+
+impl std::fmt::Debug for Clock {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Clock")
+    }
+}
+
+/// Tracks the events sent per node to apply their [RateLimit].
+///
+/// The limiter never sleeps, a node is only tracked while one of its events is pending
+/// or throttled. Node refs are only meaningful for one document, so the limiter has to be
+/// cleared whenever the document is replaced.
+#[derive(Debug, Default)]
+pub struct RateLimiter {
+    /// The ticket of the latest debounced event of each node
+    debounced: HashMap<NodeRef, u64>,
+    /// The last ticket handed out, shared by all nodes so tickets are never reused
+    last_ticket: u64,
+    /// When each throttled node may send its next event
+    throttled: HashMap<NodeRef, Instant>,
+    clock: Clock,
+}
+
+impl RateLimiter {
+    /// Creates a limiter reading the current time from `clock` instead of [Instant::now]
+    pub fn with_clock(clock: impl Fn() -> Instant + Send + Sync + 'static) -> Self {
+        Self {
+            clock: Clock(Arc::new(clock)),
+            ..Default::default()
+        }
+    }
+
+    /// Registers a debounced event of `node`, returning its ticket.
+    ///
+    /// Once the debounce duration elapsed, the event should only be sent if
+    /// [RateLimiter::take_latest] holds for the ticket.
+    pub fn debounce(&mut self, node: NodeRef) -> u64 {
+        self.last_ticket += 1;
+        self.debounced.insert(node, self.last_ticket);
+        self.last_ticket
+    }
+
+    /// Returns true if no event of `node` was debounced after the one holding `ticket`,
+    /// in which case the node is no longer tracked
+    pub fn take_latest(&mut self, node: NodeRef, ticket: u64) -> bool {
+        let latest = self.debounced.get(&node) == Some(&ticket);
+        if latest {
+            self.debounced.remove(&node);
+        }
+        latest
+    }
+
+    /// Returns true if an event of `node` throttled to one per `interval` may be sent now,
+    /// recording it as sent
+    pub fn throttle(&mut self, node: NodeRef, interval: Duration) -> bool {
+        let now = (self.clock.0)();
+        self.throttled.retain(|_, until| *until > now);
+        if self.throttled.contains_key(&node) {
+            return false;
+        }
+        self.throttled.insert(node, now + interval);
+        true
+    }
+
+    /// Returns the number of nodes with a pending debounced event or a throttle window,
+    /// including windows which elapsed since the last call to [RateLimiter::throttle]
+    pub fn len(&self) -> usize {
+        self.debounced.len() + self.throttled.len()
+    }
+
+    /// Returns true if no node is tracked, see [RateLimiter::len]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Forgets every node, e.g. once the document was replaced and its node refs may be reused.
+    ///
+    /// Events debounced before are dropped when their delay elapses.
+    pub fn clear(&mut self) {
+        self.debounced.clear();
+        self.throttled.clear();
+    }
+}
@@ -5,19 +5,27 @@ use std::{
     time::Duration,
 };
 
+use futures::future::AbortHandle;
 use log::debug;
 use phoenix_channels_client::{url::Url, Number, Payload, Socket, SocketStatus, Topic, JSON};
 use reqwest::{
     cookie::{CookieStore, Jar},
-    header::{HeaderMap, LOCATION, SET_COOKIE},
+    header::{HeaderMap, COOKIE, LOCATION, SET_COOKIE},
     redirect::Policy,
     Method as ReqMethod,
 };
-
-use super::navigation::{NavCtx, NavOptions};
+use serde::{Deserialize, Serialize};
+use tokio::sync::{broadcast, watch};
+
+use super::{
+    channel::{document_from_join_payload, CHANGE_STREAM_CAPACITY},
+    navigation::{
+        ConnectionEventHandler, DeadRenderCache, DocumentReplacedHandler, HistoryId, NavCtx,
+        NavHistory, NavOptions, ReconnectUrlProvider,
+    },
+};
 pub use super::{LiveChannel, LiveSocketError};
 use crate::{
-    diff::fragment::{Root, RootDiff},
     dom::{ffi::Document as FFiDocument, AttributeName, Document, ElementName, Selector},
     parser::parse,
 };
@@ -49,7 +57,10 @@ const LVN_VSN: &str = "2.0.0";
 const LVN_VSN_KEY: &str = "vsn";
 const CSRF_KEY: &str = "_csrf_token";
 const MOUNT_KEY: &str = "_mounts";
+// If you change this also change the
+// default of `ConnectOpts::format_param_name`
 const FMT_KEY: &str = "_format";
+const DIFF_ENCODING_KEY: &str = "_diff_encoding";
 
 #[derive(Debug, Clone, PartialEq, Eq, uniffi::Enum)]
 #[repr(u8)]
@@ -95,6 +106,14 @@ pub struct ConnectOpts {
     pub method: Option<Method>,
     #[uniffi(default = 30_000)]
     pub timeout_ms: u64,
+    /// The name of the query parameter carrying the format in the dead render and socket urls,
+    /// and of the matching join param, for servers which do not expect `_format`.
+    #[uniffi(default = "_format")]
+    pub format_param_name: String,
+    /// Asks the server to send diffs encoded as MessagePack with the `_diff_encoding` join param,
+    /// and decodes binary diff payloads. This is experimental, JSON diffs are still accepted.
+    #[uniffi(default = false)]
+    pub msgpack_diffs: bool,
 }
 
 impl Default for ConnectOpts {
@@ -104,7 +123,21 @@ impl Default for ConnectOpts {
             body: None,
             method: None,
             timeout_ms: DEFAULT_TIMEOUT,
+            format_param_name: FMT_KEY.to_string(),
+            msgpack_diffs: false,
+        }
+    }
+}
+
+impl ConnectOpts {
+    /// Returns these options with `headers` added, replacing any existing headers of the same name
+    pub fn with_extra_headers(mut self, headers: Option<HashMap<String, String>>) -> Self {
+        if let Some(headers) = headers {
+            self.headers
+                .get_or_insert_with(HashMap::new)
+                .extend(headers);
         }
+        self
     }
 }
 
@@ -117,8 +150,13 @@ pub struct SessionData {
     pub csrf_token: String,
     /// The id of the phoenix channel to join.
     pub phx_id: String,
+    /// The `data-phx-static` token of the main live view, sent when joining its channel.
     pub phx_static: String,
+    /// The signed `data-phx-session` token of the main live view, sent when joining its channel.
     pub phx_session: String,
+    /// Whether the `data-phx-main` attribute of the live view marks it as the main view,
+    /// i.e. it is present without the value `false`.
+    pub is_main_view: bool,
     pub url: Url,
     /// One of `swift`, `kotlin` or `html` indicating the developer platform.
     pub format: String,
@@ -129,6 +167,8 @@ pub struct SessionData {
     pub has_live_reload: bool,
     /// A list of cookies sent over with the dead render.
     pub cookies: Vec<String>,
+    /// The client supplied to [LiveSocket::with_http_client], reused when reconnecting.
+    pub http_client: Option<reqwest::Client>,
 }
 
 impl SessionData {
@@ -136,6 +176,16 @@ impl SessionData {
         url: &Url,
         format: &String,
         connect_opts: ConnectOpts,
+    ) -> Result<Self, LiveSocketError> {
+        Self::request_with_client(url, format, connect_opts, None).await
+    }
+
+    /// Like [SessionData::request], fetching the dead render with `http_client` if given.
+    pub async fn request_with_client(
+        url: &Url,
+        format: &String,
+        connect_opts: ConnectOpts,
+        http_client: Option<reqwest::Client>,
     ) -> Result<Self, LiveSocketError> {
         // NEED:
         // these from inside data-phx-main
@@ -147,7 +197,7 @@ impl SessionData {
         // csrf-token
         // "iframe[src=\"/phoenix/live_reload/frame\"]"
         let (dead_render, cookies, url, header_map) =
-            LiveSocket::get_dead_render(url, format, &connect_opts).await?;
+            LiveSocket::get_dead_render(url, format, &connect_opts, http_client.as_ref()).await?;
 
         let csrf_token = dead_render
             .get_csrf_token()
@@ -156,6 +206,7 @@ impl SessionData {
         let mut phx_id: Option<String> = None;
         let mut phx_static: Option<String> = None;
         let mut phx_session: Option<String> = None;
+        let mut is_main_view = false;
 
         let main_div_attributes = dead_render
             .select(Selector::Attribute(AttributeName {
@@ -183,6 +234,8 @@ impl SessionData {
                 phx_session.clone_from(&attr.value)
             } else if attr.name.name == "data-phx-static" {
                 phx_static.clone_from(&attr.value)
+            } else if attr.name.name == "data-phx-main" {
+                is_main_view = attr.value.as_deref() != Some("false");
             }
         }
         let phx_id = phx_id.ok_or(LiveSocketError::PhoenixIDMissing)?;
@@ -250,10 +303,12 @@ impl SessionData {
             phx_id,
             phx_static,
             phx_session,
+            is_main_view,
             dead_render,
             style_urls,
             has_live_reload,
             cookies,
+            http_client,
         };
 
         debug!("Session data successfully acquired {out:?}");
@@ -261,6 +316,26 @@ impl SessionData {
         Ok(out)
     }
 
+    /// The url of the page, i.e. [SessionData::url] without the format parameter which was
+    /// added to request its dead render
+    pub(crate) fn page_url(&self) -> Url {
+        let format_param_name = &self.connect_opts.format_param_name;
+        let query: Vec<(String, String)> = self
+            .url
+            .query_pairs()
+            .filter(|(name, _)| name != format_param_name)
+            .map(|(name, value)| (name.into_owned(), value.into_owned()))
+            .collect();
+
+        let mut url = self.url.clone();
+        if query.is_empty() {
+            url.set_query(None);
+        } else {
+            url.query_pairs_mut().clear().extend_pairs(query);
+        }
+        url
+    }
+
     /// reconstruct the live socket url from the session data
     pub fn get_live_socket_url(&self) -> Result<Url, LiveSocketError> {
         let websocket_scheme = match self.url.scheme() {
@@ -283,7 +358,7 @@ impl SessionData {
             .append_pair(LVN_VSN_KEY, LVN_VSN)
             .append_pair(CSRF_KEY, &self.csrf_token)
             .append_pair(MOUNT_KEY, "0")
-            .append_pair(FMT_KEY, &self.format);
+            .append_pair(&self.connect_opts.format_param_name, &self.format);
 
         websocket_url.set_path("/live/websocket");
 
@@ -293,27 +368,110 @@ impl SessionData {
     }
 }
 
+/// Everything [LiveSocket::restore_session] needs to reconnect to where a socket left off
+#[derive(Debug, Serialize, Deserialize)]
+struct SessionSnapshot {
+    /// The url of the final dead render
+    url: String,
+    format: String,
+    cookies: Vec<String>,
+    navigation: NavHistory,
+    join_params: Option<HashMap<String, serde_json::Value>>,
+}
+
 #[derive(uniffi::Object)]
 pub struct LiveSocket {
     pub socket: Mutex<Arc<Socket>>,
     pub session_data: Mutex<SessionData>,
+    /// The join params of the last liveview channel joined
+    pub(super) join_params: Mutex<Option<HashMap<String, JSON>>>,
     pub(super) navigation_ctx: Mutex<NavCtx>,
+    pub(super) document_replaced_handler: Mutex<Option<Arc<dyn DocumentReplacedHandler>>>,
+    pub(super) connection_event_handler: Mutex<Option<Arc<dyn ConnectionEventHandler>>>,
+    pub(super) reconnect_url_provider: Mutex<Option<Arc<dyn ReconnectUrlProvider>>>,
+    /// Aborts the navigation currently joining its new channel, if any
+    pub(super) pending_navigation: Mutex<Option<(HistoryId, AbortHandle)>>,
+    /// The dead renders fetched when a navigation has to reconnect
+    pub(super) dead_render_cache: Mutex<DeadRenderCache>,
 }
 
 // non uniffi bindings.
 impl LiveSocket {
+    /// Connects like [LiveSocket::new], but performs the HTTP requests for the dead render with
+    /// `http_client`, e.g. to use a custom TLS configuration, a proxy or a shared connection pool.
+    ///
+    /// A prebuilt client can not be given the crate's cookie store, so cookies from the store are
+    /// sent in a `Cookie` header and `Set-Cookie` responses are copied back into it. If the client
+    /// has a cookie store of its own, cookies may be sent twice. Redirects are followed by the
+    /// crate as long as the client does not follow them itself.
+    pub async fn with_http_client(
+        url: String,
+        format: String,
+        options: Option<ConnectOpts>,
+        http_client: reqwest::Client,
+    ) -> Result<Self, LiveSocketError> {
+        Self::connect_with_client(url, format, options, Some(http_client)).await
+    }
+
+    async fn connect_with_client(
+        url: String,
+        format: String,
+        options: Option<ConnectOpts>,
+        http_client: Option<reqwest::Client>,
+    ) -> Result<Self, LiveSocketError> {
+        let url = Url::parse(&url)?;
+        let options = options.unwrap_or_default();
+
+        let connect = async {
+            // Make HTTP request to get initial dead render, an HTML document with
+            // metadata needed to set up the liveview websocket connection.
+            let session_data =
+                SessionData::request_with_client(&url, &format, options, http_client).await?;
+            let websocket_url = session_data.get_live_socket_url()?;
+
+            let socket = Socket::spawn(websocket_url, Some(session_data.cookies.clone())).await?;
+            Ok::<_, LiveSocketError>((session_data, socket))
+        };
+
+        let (session_data, socket) = instrument!(connect, "connect", url = %url).await?;
+        let socket = socket.into();
+
+        let navigation_ctx = Mutex::new(NavCtx::default());
+
+        navigation_ctx.lock().expect("Lock Poisoned!").navigate(
+            url.clone(),
+            NavOptions::default(),
+            false,
+        );
+
+        Ok(Self {
+            socket,
+            session_data: session_data.into(),
+            join_params: Default::default(),
+            navigation_ctx,
+            document_replaced_handler: Default::default(),
+            connection_event_handler: Default::default(),
+            reconnect_url_provider: Default::default(),
+            pending_navigation: Default::default(),
+            dead_render_cache: Default::default(),
+        })
+    }
+
     /// Gets the 'dead render', a static html page containing metadata about how to
     /// connect to a websocket and initialize the live view session.
     async fn get_dead_render(
         url: &Url,
         format: &str,
         options: &ConnectOpts,
+        http_client: Option<&reqwest::Client>,
     ) -> Result<(Document, Vec<String>, Url, HeaderMap), LiveSocketError> {
         let ConnectOpts {
             headers,
             body,
             method,
             timeout_ms,
+            format_param_name,
+            ..
         } = options;
 
         let method = method.clone().unwrap_or(Method::Get).into();
@@ -321,8 +479,11 @@ impl LiveSocket {
         // TODO: Check if params contains all of phx_id, phx_static, phx_session and csrf_token, if
         // it does maybe we don't need to do a full dead render.
         let mut url = url.clone();
-        if url.query_pairs().all(|(name, _)| name != FMT_KEY) {
-            url.query_pairs_mut().append_pair(FMT_KEY, format);
+        if url
+            .query_pairs()
+            .all(|(name, _)| name != *format_param_name)
+        {
+            url.query_pairs_mut().append_pair(format_param_name, format);
         }
 
         let headers = (&headers.clone().unwrap_or_default())
@@ -337,10 +498,24 @@ impl LiveSocket {
         #[cfg(test)]
         let jar = TEST_COOKIE_JAR.with(|inner| inner.clone());
 
-        let client = reqwest::Client::builder()
-            .cookie_provider(jar.clone())
-            .redirect(Policy::none())
-            .build()?;
+        let client = match http_client {
+            Some(client) => client.clone(),
+            None => reqwest::Client::builder()
+                .cookie_provider(jar.clone())
+                .redirect(Policy::none())
+                .build()?,
+        };
+
+        // a supplied client does not use the jar, so cookies are copied in and out by hand
+        let attach_cookies = |builder: reqwest::RequestBuilder, url: &Url| match jar.cookies(url) {
+            Some(cookies) if http_client.is_some() => builder.header(COOKIE, cookies),
+            _ => builder,
+        };
+        let store_cookies = |resp: &reqwest::Response| {
+            if http_client.is_some() {
+                jar.set_cookies(&mut resp.headers().get_all(SET_COOKIE).iter(), resp.url());
+            }
+        };
 
         let req = reqwest::Request::new(method, url.clone());
         let builder = reqwest::RequestBuilder::from_parts(client, req);
@@ -352,9 +527,11 @@ impl LiveSocket {
         };
 
         let timeout = Duration::from_millis(*timeout_ms);
-        let (client, request) = builder.timeout(timeout).headers(headers).build_split();
+        let builder = attach_cookies(builder.timeout(timeout).headers(headers), &url);
+        let (client, request) = builder.build_split();
 
         let mut resp = client.execute(request?).await?;
+        store_cookies(&resp);
         let mut headers = resp.headers().clone();
 
         for _ in 0..MAX_REDIRECTS {
@@ -374,11 +551,19 @@ impl LiveSocket {
                     error: "No valid redirect location in 300 response".into(),
                 })?;
 
-            if location.query_pairs().all(|(name, _)| name != FMT_KEY) {
-                location.query_pairs_mut().append_pair(FMT_KEY, format);
+            if location
+                .query_pairs()
+                .all(|(name, _)| name != *format_param_name)
+            {
+                location
+                    .query_pairs_mut()
+                    .append_pair(format_param_name, format);
             }
 
-            resp = client.get(location).send().await?;
+            resp = attach_cookies(client.get(location.clone()), &location)
+                .send()
+                .await?;
+            store_cookies(&resp);
 
             // TODO: Remove this when persistent state is managed by core
             let cookies = resp.headers().get_all(SET_COOKIE);
@@ -416,6 +601,13 @@ impl LiveSocket {
         Ok((dead_render, cookies, url, headers))
     }
 }
+fn json_from_value(value: serde_json::Value) -> Result<JSON, LiveSocketError> {
+    match Payload::json_from_serialized(value.to_string())? {
+        Payload::JSONPayload { json } => Ok(json),
+        _ => Err(LiveSocketError::PayloadNotJson),
+    }
+}
+
 /// Stores a cookie for the duration of the application run.
 #[uniffi::export]
 pub fn store_session_cookie(cookie: String, url: String) -> Result<(), LiveSocketError> {
@@ -450,31 +642,81 @@ impl LiveSocket {
         format: String,
         options: Option<ConnectOpts>,
     ) -> Result<Self, LiveSocketError> {
-        let url = Url::parse(&url)?;
-        let options = options.unwrap_or_default();
+        Self::connect_with_client(url, format, options, None).await
+    }
 
-        // Make HTTP request to get initial dead render, an HTML document with
-        // metadata needed to set up the liveview websocket connection.
-        let session_data = SessionData::request(&url, &format, options).await?;
-        let websocket_url = session_data.get_live_socket_url()?;
+    /// Reconnects to a session saved with [LiveSocket::export_session], e.g. when the app
+    /// returns to the foreground.
+    ///
+    /// The saved cookies are stored before the dead render of the current history entry is
+    /// fetched, and the history is restored without emitting navigation events. Join the
+    /// channel with [LiveSocket::last_join_params] to land on the same view.
+    #[uniffi::constructor]
+    pub async fn restore_session(
+        session: Vec<u8>,
+        options: Option<ConnectOpts>,
+    ) -> Result<Self, LiveSocketError> {
+        let snapshot: SessionSnapshot = serde_json::from_slice(&session)?;
 
-        let socket = Socket::spawn(websocket_url, Some(session_data.cookies.clone()))
-            .await?
-            .into();
+        for cookie in snapshot.cookies {
+            store_session_cookie(cookie, snapshot.url.clone())?;
+        }
 
-        let navigation_ctx = Mutex::new(NavCtx::default());
+        let mut navigation_ctx = NavCtx::default();
+        navigation_ctx.restore_history(snapshot.navigation);
+        let url = navigation_ctx
+            .current()
+            .map_or(snapshot.url, |entry| entry.url);
+
+        let socket = Self::new(url, snapshot.format, options).await?;
+        *socket.navigation_ctx.lock().expect("lock poison") = navigation_ctx;
+        *lock!(socket.join_params) = snapshot
+            .join_params
+            .map(|params| {
+                params
+                    .into_iter()
+                    .map(|(key, value)| Ok((key, json_from_value(value)?)))
+                    .collect::<Result<_, LiveSocketError>>()
+            })
+            .transpose()?;
 
-        navigation_ctx.lock().expect("Lock Poisoned!").navigate(
-            url.clone(),
-            NavOptions::default(),
-            false,
-        );
+        Ok(socket)
+    }
 
-        Ok(Self {
-            socket,
-            session_data: session_data.into(),
-            navigation_ctx,
-        })
+    /// Saves the cookies, navigation history, current url and join params of this socket, to
+    /// reconnect to the same place with [LiveSocket::restore_session].
+    pub fn export_session(&self) -> Result<Vec<u8>, LiveSocketError> {
+        let session_data = lock!(self.session_data);
+        let join_params = lock!(self.join_params)
+            .as_ref()
+            .map(|params| {
+                params
+                    .iter()
+                    .map(|(key, value)| {
+                        Ok((key.clone(), serde_json::from_str(&value.to_string())?))
+                    })
+                    .collect::<Result<_, LiveSocketError>>()
+            })
+            .transpose()?;
+
+        let snapshot = SessionSnapshot {
+            url: session_data.url.to_string(),
+            format: session_data.format.clone(),
+            cookies: session_data.cookies.clone(),
+            navigation: self
+                .navigation_ctx
+                .lock()
+                .expect("lock poison")
+                .export_history(),
+            join_params,
+        };
+        Ok(serde_json::to_vec(&snapshot)?)
+    }
+
+    /// Returns the join params of the last liveview channel joined, or those restored
+    /// by [LiveSocket::restore_session]
+    pub fn last_join_params(&self) -> Option<HashMap<String, JSON>> {
+        lock!(self.join_params).clone()
     }
 
     /// Returns the url of the final dead render
@@ -487,10 +729,30 @@ impl LiveSocket {
         lock!(self.session_data).join_headers.clone()
     }
 
+    /// Returns the format the current connection rendered with, e.g. `swiftui` or `jetpack`
+    pub fn format(&self) -> String {
+        lock!(self.session_data).format.clone()
+    }
+
     pub fn csrf_token(&self) -> String {
         lock!(self.session_data).csrf_token.clone()
     }
 
+    /// Returns the `data-phx-session` token of the live view in the dead render
+    pub fn phx_session(&self) -> String {
+        lock!(self.session_data).phx_session.clone()
+    }
+
+    /// Returns the `data-phx-static` token of the live view in the dead render
+    pub fn phx_static(&self) -> String {
+        lock!(self.session_data).phx_static.clone()
+    }
+
+    /// Returns true if the live view in the dead render is marked as the main view with `data-phx-main`
+    pub fn is_main_view(&self) -> bool {
+        lock!(self.session_data).is_main_view
+    }
+
     pub fn cookies(&self) -> Vec<String> {
         lock!(self.session_data).cookies.clone()
     }
@@ -507,6 +769,29 @@ impl LiveSocket {
             .clone()
     }
 
+    /// Returns the websocket url derived from the current session, over which liveview
+    /// channels are joined
+    pub fn socket_url(&self) -> Result<String, LiveSocketError> {
+        Ok(lock!(self.session_data).get_live_socket_url()?.to_string())
+    }
+
+    /// Returns [Self::style_urls] joined against the url of the dead render, so that
+    /// each entry is fully qualified. Urls which cannot be joined are skipped.
+    pub fn resolved_style_urls(&self) -> Vec<String> {
+        let session_data = lock!(self.session_data);
+        session_data
+            .style_urls
+            .iter()
+            .filter_map(|style_url| match session_data.url.join(style_url) {
+                Ok(url) => Some(url.to_string()),
+                Err(e) => {
+                    log::warn!("Could not resolve style url {style_url}: {e}");
+                    None
+                }
+            })
+            .collect()
+    }
+
     pub async fn join_livereload_channel(&self) -> Result<LiveChannel, LiveSocketError> {
         let mut url = lock!(self.session_data).url.clone();
 
@@ -529,8 +814,9 @@ impl LiveSocket {
         socket.connect(self.timeout()).await?;
 
         debug!("Joining live reload channel on url {url}");
+        let topic = "phoenix:live_reload".to_string();
         let channel = socket
-            .channel(Topic::from_string("phoenix:live_reload".to_string()), None)
+            .channel(Topic::from_string(topic.clone()), None)
             .await?;
         debug!("Created channel for live reload socket");
         let join_payload = channel.join(self.timeout()).await?;
@@ -538,11 +824,23 @@ impl LiveSocket {
 
         Ok(LiveChannel {
             channel,
+            topic,
             join_params: Default::default(),
             join_payload,
             socket: self.socket(),
             document: document.into(),
             timeout: self.timeout(),
+            command_handler: Default::default(),
+            payload_observer: Default::default(),
+            diff_error_handler: Default::default(),
+            diff_applied_handler: Default::default(),
+            diff_stats: Default::default(),
+            idle_timeout: watch::channel(None).0,
+            idled: Default::default(),
+            rate_limiter: Default::default(),
+            render_hash: Default::default(),
+            changes: broadcast::channel(CHANGE_STREAM_CAPACITY).0,
+            msgpack_diffs: false,
         })
     }
 
@@ -569,12 +867,20 @@ impl LiveSocket {
                 },
             ),
             (
-                FMT_KEY.to_string(),
+                session_data.connect_opts.format_param_name,
                 JSON::Str {
                     string: session_data.format,
                 },
             ),
         ]);
+        if session_data.connect_opts.msgpack_diffs {
+            collected_join_params.insert(
+                DIFF_ENCODING_KEY.to_string(),
+                JSON::Str {
+                    string: "msgpack".to_string(),
+                },
+            );
+        }
         if let Some(join_params) = join_params.clone() {
             for (key, value) in &join_params {
                 collected_join_params.insert(key.clone(), value.clone());
@@ -619,45 +925,38 @@ impl LiveSocket {
             },
         };
 
+        let topic = format!("lv:{}", session_data.phx_id);
         let channel = self
             .socket()
-            .channel(
-                Topic::from_string(format!("lv:{}", session_data.phx_id)),
-                Some(join_payload),
-            )
+            .channel(Topic::from_string(topic.clone()), Some(join_payload))
             .await?;
 
-        let join_payload = channel.join(self.timeout()).await?;
+        let join_payload =
+            instrument!(channel.join(self.timeout()), "join", topic = %topic).await?;
+        lock!(self.join_params).clone_from(&join_params);
 
         debug!("Join payload: {join_payload:#?}");
-        let document = match join_payload {
-            Payload::JSONPayload {
-                json: JSON::Object { ref object },
-            } => {
-                if let Some(rendered) = object.get("rendered") {
-                    let rendered = rendered.to_string();
-                    let root: RootDiff = serde_json::from_str(rendered.as_str())?;
-                    debug!("root diff: {root:#?}");
-                    let root: Root = root.try_into()?;
-                    let rendered: String = root.clone().try_into()?;
-                    let mut document = crate::parser::parse(&rendered)?;
-                    document.fragment_template = Some(root);
-                    Some(document)
-                } else {
-                    None
-                }
-            }
-            _ => None,
-        }
-        .ok_or(LiveSocketError::NoDocumentInJoinPayload)?;
+        let document = document_from_join_payload(&join_payload)?;
 
         Ok(LiveChannel {
             channel,
+            topic,
             join_payload,
             join_params: join_params.unwrap_or_default(),
             socket: self.socket(),
             document: document.into(),
             timeout: self.timeout(),
+            command_handler: Default::default(),
+            payload_observer: Default::default(),
+            diff_error_handler: Default::default(),
+            diff_applied_handler: Default::default(),
+            diff_stats: Default::default(),
+            idle_timeout: watch::channel(None).0,
+            idled: Default::default(),
+            rate_limiter: Default::default(),
+            render_hash: Default::default(),
+            changes: broadcast::channel(CHANGE_STREAM_CAPACITY).0,
+            msgpack_diffs: session_data.connect_opts.msgpack_diffs,
         })
     }
 
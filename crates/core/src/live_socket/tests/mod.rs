@@ -1,11 +1,16 @@
 use std::{sync::Arc, time::Duration};
 
 use super::*;
-use crate::dom::{
-    ChangeType, ControlFlow, DocumentChangeHandler, LiveChannelStatus, NodeData, NodeRef,
+use crate::{
+    diff::{Patch, PatchResult},
+    dom::{
+        ChangeType, ControlFlow, DocumentChangeHandler, ElementName, EntityEncoding,
+        LiveChannelStatus, NodeData, NodeRef, Selector,
+    },
 };
 mod error;
 mod navigation;
+mod protocol;
 mod streaming;
 mod upload;
 
@@ -15,7 +20,7 @@ const HOST: &str = "10.0.2.2:4001";
 #[cfg(not(target_os = "android"))]
 const HOST: &str = "127.0.0.1:4001";
 
-use phoenix_channels_client::ChannelStatus;
+use phoenix_channels_client::{ChannelStatus, Payload, SocketStatus, JSON};
 use pretty_assertions::assert_eq;
 
 macro_rules! assert_doc_eq {
@@ -110,6 +115,351 @@ async fn channels_drop_on_shutdown() {
     assert_eq!(chan_clone.status(), ChannelStatus::ShutDown);
 }
 
+#[tokio::test]
+async fn idle_timeout_leaves_channel() {
+    let _ = env_logger::builder()
+        .parse_default_env()
+        .is_test(true)
+        .try_init();
+
+    let url = format!("http://{HOST}/thermostat");
+
+    let live_socket = LiveSocket::new(url.to_string(), "swiftui".into(), Default::default())
+        .await
+        .expect("Failed to get liveview socket");
+
+    let live_channel = live_socket
+        .join_liveview_channel(None, None)
+        .await
+        .expect("Failed to join channel");
+    let live_channel = Arc::new(live_channel);
+
+    let chan_clone = live_channel.clone();
+    let handle = tokio::spawn(async move {
+        chan_clone
+            .merge_diffs()
+            .await
+            .expect("Failed to merge diffs");
+    });
+
+    // applies to the running loop without waiting for a message
+    live_channel.set_idle_timeout(Some(Duration::from_millis(200)));
+
+    tokio::time::timeout(Duration::from_secs(5), handle)
+        .await
+        .expect("merge_diffs did not exit when idle")
+        .expect("merge_diffs panicked");
+    assert_eq!(live_channel.channel().status(), ChannelStatus::Left);
+    // the socket is shared with the other channels
+    assert_eq!(live_socket.status(), SocketStatus::Connected);
+
+    // the next event joins the channel again
+    let button = live_channel
+        .document()
+        .inner()
+        .lock()
+        .expect("lock poisoned")
+        .select(Selector::Tag(ElementName::new("Button")))
+        .next()
+        .expect("missing button");
+    let Payload::JSONPayload { json } = Payload::json_from_serialized(
+        r#"{"type": "click", "event": "inc_temperature", "value": {}}"#.to_owned(),
+    )
+    .expect("invalid payload") else {
+        unreachable!("serialized payloads are json");
+    };
+    live_channel
+        .send_event_json(button.into(), json)
+        .await
+        .expect("Failed to send click")
+        .expect("click was rate limited");
+    assert_eq!(live_channel.channel().status(), ChannelStatus::Joined);
+}
+
+#[tokio::test]
+async fn custom_http_client() {
+    let _ = env_logger::builder()
+        .parse_default_env()
+        .is_test(true)
+        .try_init();
+
+    let url = format!("http://{HOST}/hello");
+
+    // nothing listens on the proxy, so the dead render can only succeed without it
+    let proxied = reqwest::Client::builder()
+        .proxy(reqwest::Proxy::all("http://127.0.0.1:9").expect("invalid proxy"))
+        .build()
+        .expect("Failed to build client");
+    let result = LiveSocket::with_http_client(url.clone(), "swiftui".into(), None, proxied).await;
+    assert!(matches!(result, Err(LiveSocketError::Request { .. })));
+
+    let client = reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .build()
+        .expect("Failed to build client");
+    let live_socket = LiveSocket::with_http_client(url, "swiftui".into(), None, client)
+        .await
+        .expect("Failed to get liveview socket");
+
+    let _live_channel = live_socket
+        .join_liveview_channel(None, None)
+        .await
+        .expect("Failed to join channel");
+}
+
+#[tokio::test]
+async fn dead_render_session_tokens() {
+    let _ = env_logger::builder()
+        .parse_default_env()
+        .is_test(true)
+        .try_init();
+
+    let url = format!("http://{HOST}/hello");
+    let live_socket = LiveSocket::new(url.to_string(), "swiftui".into(), Default::default())
+        .await
+        .expect("Failed to get liveview socket");
+
+    assert!(!live_socket.phx_session().is_empty());
+    assert!(!live_socket.phx_static().is_empty());
+    assert!(live_socket.is_main_view());
+
+    // the tokens are those of the main live view in the dead render
+    let session_data = live_socket.session_data.lock().expect("lock poisoned");
+    let dead_render = &session_data.dead_render;
+    let main = dead_render
+        .select(Selector::Attribute("data-phx-main".into()))
+        .last()
+        .expect("missing main view");
+    let attribute = |name: &str| {
+        dead_render
+            .get_attribute_by_name(main, name)
+            .and_then(|attr| attr.value)
+    };
+    assert_eq!(
+        attribute("data-phx-session"),
+        Some(live_socket.phx_session())
+    );
+    assert_eq!(attribute("data-phx-static"), Some(live_socket.phx_static()));
+}
+
+#[derive(Default)]
+struct DiffErrors(std::sync::Mutex<Vec<(String, String)>>);
+
+impl DiffErrorHandler for Arc<DiffErrors> {
+    fn on_diff_error(&self, error: String, diff: String) {
+        self.0.lock().expect("lock poisoned").push((error, diff));
+    }
+}
+
+#[tokio::test]
+async fn bad_diff_is_skipped() {
+    let _ = env_logger::builder()
+        .parse_default_env()
+        .is_test(true)
+        .try_init();
+
+    let url = format!("http://{HOST}/hello");
+    let live_socket = LiveSocket::new(url.to_string(), "swiftui".into(), Default::default())
+        .await
+        .expect("Failed to get liveview socket");
+
+    let live_channel = live_socket
+        .join_liveview_channel(None, None)
+        .await
+        .expect("Failed to join channel");
+    let errors = Arc::new(DiffErrors::default());
+    live_channel.set_diff_error_handler(Box::new(errors.clone()));
+    let before = live_channel.document().render();
+
+    // an update to a component which was never rendered, after a change which merges
+    let bad_diff = r#"{"0": "oops", "c": {"1": {"0": "oops"}}}"#;
+    live_channel
+        .apply_json_diff(bad_diff)
+        .expect("bad diffs should be skipped");
+
+    let reported = errors.0.lock().expect("lock poisoned").clone();
+    assert_eq!(reported.len(), 1);
+    assert_eq!(reported[0].1, bad_diff);
+    assert_eq!(live_channel.document().render(), before);
+    assert_eq!(live_channel.diff_stats().count, 0);
+    assert_eq!(live_channel.channel().status(), ChannelStatus::Joined);
+
+    // the skipped diff left nothing behind in the template
+    live_channel
+        .apply_json_diff("{}")
+        .expect("Failed to merge diff");
+    assert_eq!(live_channel.diff_stats().count, 1);
+    assert_eq!(live_channel.document().render(), before);
+}
+
+#[tokio::test]
+async fn change_stream_receives_merges() {
+    let _ = env_logger::builder()
+        .parse_default_env()
+        .is_test(true)
+        .try_init();
+
+    let url = format!("http://{HOST}/thermostat");
+    let live_socket = LiveSocket::new(url.to_string(), "swiftui".into(), Default::default())
+        .await
+        .expect("Failed to get liveview socket");
+
+    let live_channel = live_socket
+        .join_liveview_channel(None, None)
+        .await
+        .expect("Failed to join channel");
+    let mut changes = live_channel.change_stream();
+
+    let button = live_channel
+        .document()
+        .inner()
+        .lock()
+        .expect("lock poisoned")
+        .select(Selector::Tag(ElementName::new("Button")))
+        .next()
+        .expect("missing button");
+    let Payload::JSONPayload { json } = Payload::json_from_serialized(
+        r#"{"type": "click", "event": "inc_temperature", "value": {}}"#.to_owned(),
+    )
+    .expect("invalid payload") else {
+        unreachable!("serialized payloads are json");
+    };
+    let reply = live_channel
+        .send_event_json(button.into(), json)
+        .await
+        .expect("Failed to send click")
+        .expect("click was rate limited");
+
+    let Payload::JSONPayload {
+        json: JSON::Object { object },
+    } = reply
+    else {
+        panic!("reply was not a json object");
+    };
+    let diff = object.get("diff").expect("click reply without a diff");
+    live_channel
+        .apply_json_diff(&diff.to_string())
+        .expect("Failed to merge diff");
+
+    let streamed: Vec<_> = std::iter::from_fn(|| changes.try_recv().ok()).collect();
+    assert!(
+        streamed.iter().any(|change| matches!(
+            change,
+            PatchResult::Change { data: NodeData::Leaf { value }, .. }
+                | PatchResult::Replace { data: NodeData::Leaf { value }, .. }
+                | PatchResult::Add { data: NodeData::Leaf { value }, .. }
+                if value.contains("71")
+        )),
+        "the new temperature was not streamed: {streamed:?}"
+    );
+}
+
+#[tokio::test]
+async fn diff_since_join_reports_click() {
+    let _ = env_logger::builder()
+        .parse_default_env()
+        .is_test(true)
+        .try_init();
+
+    let url = format!("http://{HOST}/thermostat");
+    let live_socket = LiveSocket::new(url.to_string(), "swiftui".into(), Default::default())
+        .await
+        .expect("Failed to get liveview socket");
+
+    let live_channel = live_socket
+        .join_liveview_channel(None, None)
+        .await
+        .expect("Failed to join channel");
+    assert!(live_channel
+        .diff_since_join()
+        .expect("Failed to diff")
+        .is_empty());
+
+    let button = live_channel
+        .document()
+        .inner()
+        .lock()
+        .expect("lock poisoned")
+        .select(Selector::Tag(ElementName::new("Button")))
+        .next()
+        .expect("missing button");
+    let Payload::JSONPayload { json } = Payload::json_from_serialized(
+        r#"{"type": "click", "event": "inc_temperature", "value": {}}"#.to_owned(),
+    )
+    .expect("invalid payload") else {
+        unreachable!("serialized payloads are json");
+    };
+    let reply = live_channel
+        .send_event_json(button.into(), json)
+        .await
+        .expect("Failed to send click")
+        .expect("click was rate limited");
+
+    let Payload::JSONPayload {
+        json: JSON::Object { object },
+    } = reply
+    else {
+        panic!("reply was not a json object");
+    };
+    let diff = object.get("diff").expect("click reply without a diff");
+    live_channel
+        .apply_json_diff(&diff.to_string())
+        .expect("Failed to merge diff");
+
+    let patches = live_channel.diff_since_join().expect("Failed to diff");
+    assert!(
+        patches.iter().any(|patch| matches!(
+            patch,
+            Patch::Replace { replacement: NodeData::Leaf { value }, .. }
+                if value.contains("71")
+        )),
+        "the new temperature was not reported: {patches:?}"
+    );
+}
+
+#[tokio::test]
+async fn resync_restores_document() {
+    let _ = env_logger::builder()
+        .parse_default_env()
+        .is_test(true)
+        .try_init();
+
+    let url = format!("http://{HOST}/hello");
+    let live_socket = LiveSocket::new(url.to_string(), "swiftui".into(), Default::default())
+        .await
+        .expect("Failed to get liveview socket");
+
+    let live_channel = live_socket
+        .join_liveview_channel(None, None)
+        .await
+        .expect("Failed to join channel");
+    let expected = live_channel.document().render();
+    live_channel
+        .document()
+        .set_entity_encoding(EntityEncoding::Markup);
+    let mut changes = live_channel.change_stream();
+
+    // drift from the server by editing the document locally
+    {
+        let document = live_channel.document().inner();
+        let mut document = document.lock().expect("lock poisoned");
+        let root = document.root();
+        let last = document.last_child(root).expect("empty document");
+        document.delete(last);
+    }
+    assert_ne!(live_channel.document().render(), expected);
+
+    live_channel.resync().await.expect("Failed to resync");
+    assert_eq!(live_channel.document().render(), expected);
+    assert_eq!(live_channel.channel().status(), ChannelStatus::Joined);
+
+    // the document is patched rather than replaced, so the changes are reported
+    assert!(matches!(changes.try_recv(), Ok(PatchResult::Add { .. })));
+    let document = live_channel.document().inner();
+    let document = document.lock().expect("lock poisoned");
+    assert_eq!(document.entity_encoding(), EntityEncoding::Markup);
+}
+
 #[tokio::test]
 async fn join_redirect() {
     let _ = env_logger::builder()
@@ -123,10 +473,16 @@ async fn join_redirect() {
         .await
         .expect("Failed to get liveview socket");
 
+    let socket_url = live_socket
+        .socket_url()
+        .expect("Failed to derive socket url");
+    assert!(socket_url.starts_with(&format!("ws://{HOST}/live/websocket?")));
+
     let live_channel = live_socket
         .join_liveview_channel(None, None)
         .await
         .expect("Failed to join channel");
+    assert!(live_channel.channel_topic().starts_with("lv:"));
 
     let join_doc = live_channel
         .join_document()
@@ -163,6 +519,10 @@ async fn join_live_view() {
     let expected_style_urls = vec!["/assets/app.swiftui.styles".to_string()];
     assert_eq!(style_urls, expected_style_urls);
 
+    let resolved_style_urls = live_socket.resolved_style_urls();
+    let expected_resolved = vec![format!("http://{HOST}/assets/app.swiftui.styles")];
+    assert_eq!(resolved_style_urls, expected_resolved);
+
     let live_channel = live_socket
         .join_liveview_channel(None, None)
         .await
@@ -187,6 +547,48 @@ async fn join_live_view() {
         .expect("Failed to join channel");
 }
 
+#[tokio::test]
+async fn reports_format() {
+    let _ = env_logger::builder()
+        .parse_default_env()
+        .is_test(true)
+        .try_init();
+
+    let url = format!("http://{HOST}/hello");
+    let live_socket = LiveSocket::new(url.to_string(), "jetpack".into(), Default::default())
+        .await
+        .expect("Failed to get liveview socket");
+    assert_eq!(live_socket.format(), "jetpack");
+
+    let websocket_url = live_socket.socket_url().expect("Failed to get socket url");
+    assert!(websocket_url.contains("_format=jetpack"));
+}
+
+#[tokio::test]
+async fn custom_format_param_name() {
+    let _ = env_logger::builder()
+        .parse_default_env()
+        .is_test(true)
+        .try_init();
+
+    let url = format!("http://{HOST}/hello");
+    let opts = socket::ConnectOpts {
+        format_param_name: "_platform".to_string(),
+        ..Default::default()
+    };
+    let live_socket = LiveSocket::new(url.to_string(), "swiftui".into(), Some(opts))
+        .await
+        .expect("Failed to get liveview socket");
+
+    let dead_render_url = live_socket.join_url();
+    assert!(dead_render_url.contains("_platform=swiftui"));
+    assert!(!dead_render_url.contains("_format="));
+
+    let websocket_url = live_socket.socket_url().expect("Failed to get socket url");
+    assert!(websocket_url.contains("_platform=swiftui"));
+    assert!(!websocket_url.contains("_format="));
+}
+
 #[tokio::test]
 async fn channel_redirect() {
     let _ = env_logger::builder()
@@ -1,11 +1,23 @@
-use std::sync::{Arc, Mutex};
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
 
+use phoenix_channels_client::JSON;
 use pretty_assertions::assert_eq;
 use reqwest::Url;
 use serde::{Deserialize, Serialize};
 
 use super::assert_doc_eq;
-use crate::live_socket::{navigation::*, LiveSocket};
+use crate::{
+    dom::Document,
+    live_socket::{
+        navigation::*,
+        socket::{ConnectOpts, SessionData},
+        LiveSocket, LiveSocketError,
+    },
+};
 
 // Mock event handler used to validate the internal
 // navigation objects state.
@@ -50,6 +62,7 @@ impl NavEvent {
                 url: String::new(),
                 id: 0,
                 state: None,
+                restoration_data: None,
             },
             event: NavEventType::Push,
             same_document: false,
@@ -75,6 +88,7 @@ fn basic_internal_nav() {
             event: NavEventType::Push,
             to: NavHistoryEntry {
                 state: None,
+                restoration_data: None,
                 id: 1,
                 url: url_str.to_string(),
             },
@@ -105,11 +119,13 @@ fn basic_internal_navigate_back() {
         NavEvent {
             to: NavHistoryEntry {
                 state: None,
+                restoration_data: None,
                 id: 2,
                 url: url_str.to_string(),
             },
             from: NavHistoryEntry {
                 state: None,
+                restoration_data: None,
                 id: 1,
                 url: first_url_str.to_string(),
             }
@@ -126,11 +142,13 @@ fn basic_internal_navigate_back() {
         NavEvent {
             to: NavHistoryEntry {
                 state: None,
+                restoration_data: None,
                 id: 1,
                 url: first_url_str.to_string(),
             },
             from: NavHistoryEntry {
                 state: None,
+                restoration_data: None,
                 id: 2,
                 url: url_str.to_string(),
             }
@@ -167,6 +185,102 @@ fn test_navigation_with_state() {
     assert_eq!(current.state, Some(state));
 }
 
+#[test]
+fn test_restoration_data_round_trip() {
+    let mut ctx = NavCtx::default();
+    let first = Url::parse("https://example.com/first").expect("parse first");
+    let second = Url::parse("https://example.com/second").expect("parse second");
+
+    let restoration_data = HashMap::from([("scroll_y".to_string(), "240".to_string())]);
+    let opts = NavOptions {
+        restoration_data: Some(restoration_data.clone()),
+        ..Default::default()
+    };
+
+    let id1 = ctx.navigate(first, opts, true).expect("nav first");
+    ctx.navigate(second, NavOptions::default(), true)
+        .expect("nav second");
+
+    ctx.back(None, true).expect("back");
+    let current = ctx.current().expect("current");
+    assert_eq!(current.id, id1);
+    assert_eq!(current.restoration_data, Some(restoration_data.clone()));
+
+    ctx.forward(None, true).expect("forward");
+    assert_eq!(ctx.current().expect("current").restoration_data, None);
+
+    let entries = ctx.entries();
+    assert_eq!(entries[0].restoration_data, Some(restoration_data));
+}
+
+struct SchemeRewriter;
+
+impl UrlRewriter for SchemeRewriter {
+    fn rewrite(&self, url: String) -> String {
+        url.replacen("app://", "http://", 1)
+    }
+}
+
+#[test]
+fn test_url_rewriter() {
+    let mut ctx = NavCtx::default();
+    ctx.set_url_rewriter(Arc::new(SchemeRewriter));
+
+    let url_str = "app://example.com/live";
+    let url = Url::parse(url_str).expect("parse");
+    ctx.navigate(url, NavOptions::default(), true).expect("nav");
+
+    let current = ctx.current().expect("current");
+    assert_eq!(current.url, url_str);
+
+    let fetched = ctx.rewrite_url(&Url::parse(&current.url).expect("parse"));
+    assert_eq!(fetched.as_str(), "http://example.com/live");
+}
+
+/// Serves the `alias` page from `second_page`
+struct AliasRewriter;
+
+impl UrlRewriter for AliasRewriter {
+    fn rewrite(&self, url: String) -> String {
+        url.replacen("app://", "http://", 1)
+            .replace("/nav/alias", "/nav/second_page")
+    }
+}
+
+#[tokio::test]
+async fn url_rewriter_rewrites_joined_url() {
+    let _ = env_logger::builder()
+        .parse_default_env()
+        .is_test(true)
+        .try_init();
+
+    let url = format!("http://{HOST}/nav/first_page");
+    let live_socket = LiveSocket::new(url.to_string(), "swiftui".into(), Default::default())
+        .await
+        .expect("Failed to get liveview socket");
+    live_socket.set_url_rewriter(Box::new(AliasRewriter));
+
+    let _live_channel = live_socket
+        .join_liveview_channel(None, None)
+        .await
+        .expect("Failed to join channel");
+
+    let alias = format!("app://{HOST}/nav/alias");
+    let live_channel = live_socket
+        .navigate(alias.clone(), None, Default::default())
+        .await
+        .expect("navigate");
+
+    // history keeps the original url, while the view of the rewritten url was joined
+    assert_eq!(live_socket.current().expect("current").url, alias);
+    let document = live_channel
+        .join_document()
+        .expect("join document")
+        .to_string();
+    assert!(document.contains("second_page"));
+    assert!(!document.contains("alias"));
+}
+
 #[test]
 fn test_navigation_stack() {
     let mut ctx = NavCtx::default();
@@ -207,6 +321,72 @@ fn test_navigation_stack() {
     assert_eq!(ctx.entries().len(), 3);
 }
 
+#[cfg(feature = "tracing")]
+#[test]
+fn test_navigate_span() {
+    use std::collections::BTreeMap;
+
+    use tracing::{
+        field::{Field, Visit},
+        span, Event, Metadata, Subscriber,
+    };
+
+    // Records the name and fields of every span created while it is the default subscriber.
+    #[derive(Default)]
+    struct SpanRecorder {
+        spans: Mutex<Vec<(&'static str, BTreeMap<String, String>)>>,
+    }
+
+    struct FieldVisitor<'a>(&'a mut BTreeMap<String, String>);
+
+    impl Visit for FieldVisitor<'_> {
+        fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+            self.0
+                .insert(field.name().to_string(), format!("{value:?}"));
+        }
+    }
+
+    impl Subscriber for SpanRecorder {
+        fn enabled(&self, _: &Metadata<'_>) -> bool {
+            true
+        }
+
+        fn new_span(&self, span: &span::Attributes<'_>) -> span::Id {
+            let mut fields = BTreeMap::new();
+            span.record(&mut FieldVisitor(&mut fields));
+            let mut spans = self.spans.lock().expect("Lock poisoned!");
+            spans.push((span.metadata().name(), fields));
+            span::Id::from_u64(spans.len() as u64)
+        }
+
+        fn record(&self, id: &span::Id, values: &span::Record<'_>) {
+            let mut spans = self.spans.lock().expect("Lock poisoned!");
+            let (_, fields) = &mut spans[id.into_u64() as usize - 1];
+            values.record(&mut FieldVisitor(fields));
+        }
+
+        fn record_follows_from(&self, _: &span::Id, _: &span::Id) {}
+        fn event(&self, _: &Event<'_>) {}
+        fn enter(&self, _: &span::Id) {}
+        fn exit(&self, _: &span::Id) {}
+    }
+
+    let recorder = Arc::new(SpanRecorder::default());
+    let url_str = "https://example.com/live";
+
+    let id = tracing::subscriber::with_default(recorder.clone(), || {
+        let mut ctx = NavCtx::default();
+        let url = Url::parse(url_str).expect("parse");
+        ctx.navigate(url, NavOptions::default(), true).expect("nav")
+    });
+
+    let spans = recorder.spans.lock().expect("Lock poisoned!");
+    let (name, fields) = spans.first().expect("no span recorded");
+    assert_eq!(*name, "navigate");
+    assert_eq!(fields.get("url").map(String::as_str), Some(url_str));
+    assert_eq!(fields.get("history_id"), Some(&id.to_string()));
+}
+
 #[cfg(target_os = "android")]
 const HOST: &str = "10.0.2.2:4001";
 
@@ -234,6 +414,83 @@ fn test_navigation_rollback_forward() {
     assert_eq!(ctx.current().expect("current").id, id2);
 }
 
+struct ReasonedPreventer;
+
+impl NavEventHandler for ReasonedPreventer {
+    fn handle_event(&self, _event: NavEvent) -> HandlerResponse {
+        HandlerResponse::PreventDefaultWithReason("unsaved changes".to_string())
+    }
+}
+
+#[test]
+fn test_prevent_default_with_reason() {
+    let mut ctx = NavCtx::default();
+    let first = Url::parse("https://example.com/first").expect("parse first");
+    let second = Url::parse("https://example.com/second").expect("parse second");
+
+    ctx.navigate(first.clone(), NavOptions::default(), true)
+        .expect("nav first");
+    ctx.navigate(second.clone(), NavOptions::default(), true)
+        .expect("nav second");
+    let entries = ctx.entries();
+
+    ctx.set_event_handler(Arc::new(ReasonedPreventer));
+    assert_eq!(ctx.last_prevented_reason(), None);
+
+    let third = Url::parse("https://example.com/third").expect("parse third");
+    assert_eq!(ctx.navigate(third, NavOptions::default(), true), None);
+    assert_eq!(ctx.back(None, true), None);
+
+    assert_eq!(ctx.entries(), entries);
+    assert_eq!(ctx.current().expect("current").url, second.to_string());
+    assert_eq!(
+        ctx.last_prevented_reason(),
+        Some("unsaved changes".to_string())
+    );
+}
+
+#[test]
+fn duplicate_navigation_is_ignored() {
+    let handler = Arc::new(NavigationInspector::new());
+    let mut ctx = NavCtx::default();
+    ctx.set_event_handler(handler.clone());
+
+    let url = Url::parse("https://example.com/live").expect("parse");
+    let id = ctx
+        .navigate(url.clone(), NavOptions::default(), true)
+        .expect("nav");
+    let event = handler.last_event();
+
+    assert_eq!(ctx.navigate(url.clone(), NavOptions::default(), true), None);
+    assert_eq!(handler.last_event(), event);
+    assert_eq!(ctx.entries().len(), 1);
+    assert_eq!(ctx.current().expect("current").id, id);
+
+    // replacing the entry or pushing new state is not a duplicate
+    let replace = NavOptions {
+        action: Some(NavAction::Replace),
+        ..Default::default()
+    };
+    let id = ctx.navigate(url.clone(), replace, true).expect("replace");
+    assert_eq!(ctx.entries().len(), 1);
+    assert_eq!(ctx.current().expect("current").id, id);
+
+    let with_state = NavOptions {
+        state: Some(vec![1]),
+        ..Default::default()
+    };
+    let id = ctx.navigate(url.clone(), with_state, true).expect("state");
+    assert_eq!(ctx.entries().len(), 2);
+    assert_eq!(ctx.current().expect("current").state, Some(vec![1]));
+
+    ctx.set_allow_duplicate_navigation(true);
+    let next = ctx
+        .navigate(url, NavOptions::default(), true)
+        .expect("duplicate nav");
+    assert_ne!(next, id);
+    assert_eq!(ctx.entries().len(), 3);
+}
+
 #[tokio::test]
 async fn basic_nav_flow() {
     let _ = env_logger::builder()
@@ -300,3 +557,626 @@ async fn basic_nav_flow() {
 
     assert_doc_eq!(expected, join_doc.to_string());
 }
+
+#[derive(Default, Clone)]
+struct ReplacementCounter {
+    documents: Arc<Mutex<Vec<String>>>,
+}
+
+impl DocumentReplacedHandler for ReplacementCounter {
+    fn on_document_replaced(&self, document: Arc<crate::dom::ffi::Document>) {
+        self.documents
+            .lock()
+            .expect("lock poison")
+            .push(document.render());
+    }
+}
+
+#[tokio::test]
+async fn document_replaced_on_navigation() {
+    let _ = env_logger::builder()
+        .parse_default_env()
+        .is_test(true)
+        .try_init();
+
+    let url = format!("http://{HOST}/nav/first_page");
+    let live_socket = LiveSocket::new(url.to_string(), "swiftui".into(), Default::default())
+        .await
+        .expect("Failed to get liveview socket");
+
+    let counter = ReplacementCounter::default();
+    live_socket.set_document_replaced_handler(Box::new(counter.clone()));
+
+    // joining the initial channel is not a replacement
+    let _live_channel = live_socket
+        .join_liveview_channel(None, None)
+        .await
+        .expect("Failed to join channel");
+    assert!(counter.documents.lock().expect("lock poison").is_empty());
+
+    let url = format!("http://{HOST}/nav/second_page");
+    let live_channel = live_socket
+        .navigate(url, None, Default::default())
+        .await
+        .expect("navigate");
+
+    let documents = counter.documents.lock().expect("lock poison");
+    assert_eq!(documents.len(), 1);
+    assert_eq!(documents[0], live_channel.document().render());
+}
+
+#[derive(Default, Clone)]
+struct ConnectionEvents {
+    reconnected: Arc<Mutex<u32>>,
+    reloaded: Arc<Mutex<u32>>,
+}
+
+impl ConnectionEvents {
+    fn counts(&self) -> (u32, u32) {
+        (
+            *self.reconnected.lock().expect("lock poison"),
+            *self.reloaded.lock().expect("lock poison"),
+        )
+    }
+}
+
+impl ConnectionEventHandler for ConnectionEvents {
+    fn on_socket_reconnected(&self) {
+        *self.reconnected.lock().expect("lock poison") += 1;
+    }
+
+    fn on_view_reloaded(&self) {
+        *self.reloaded.lock().expect("lock poison") += 1;
+    }
+}
+
+#[tokio::test]
+async fn socket_reconnect_and_view_reload_callbacks() {
+    let _ = env_logger::builder()
+        .parse_default_env()
+        .is_test(true)
+        .try_init();
+
+    let url = format!("http://{HOST}/nav/first_page");
+    let live_socket = LiveSocket::new(url.to_string(), "swiftui".into(), Default::default())
+        .await
+        .expect("Failed to get liveview socket");
+
+    let events = ConnectionEvents::default();
+    live_socket.set_connection_event_handler(Box::new(events.clone()));
+
+    let _live_channel = live_socket
+        .join_liveview_channel(None, None)
+        .await
+        .expect("Failed to join channel");
+    assert_eq!(events.counts(), (0, 0));
+
+    // the session of the first page is stale for the second, forcing a new socket
+    let url = format!("http://{HOST}/nav/second_page");
+    let _live_channel = live_socket
+        .navigate(url, None, Default::default())
+        .await
+        .expect("navigate");
+    assert_eq!(events.counts(), (1, 0));
+
+    // reloading rejoins the view over the same socket
+    let _live_channel = live_socket.reload(None, None).await.expect("reload");
+    assert_eq!(events.counts(), (1, 1));
+}
+
+#[derive(Default, Clone)]
+struct FailoverProvider {
+    requested: Arc<Mutex<Vec<String>>>,
+}
+
+impl ReconnectUrlProvider for FailoverProvider {
+    fn reconnect_url(&self, url: String) -> Option<String> {
+        let mut failover = Url::parse(&url).expect("invalid url");
+        failover.set_host(Some("localhost")).expect("invalid host");
+        self.requested.lock().expect("lock poison").push(url);
+        Some(failover.to_string())
+    }
+}
+
+#[tokio::test]
+async fn reconnect_targets_provided_url() {
+    let _ = env_logger::builder()
+        .parse_default_env()
+        .is_test(true)
+        .try_init();
+
+    let url = format!("http://{HOST}/nav/first_page");
+    let live_socket = LiveSocket::new(url.to_string(), "swiftui".into(), Default::default())
+        .await
+        .expect("Failed to get liveview socket");
+
+    let provider = FailoverProvider::default();
+    live_socket.set_reconnect_url_provider(Box::new(provider.clone()));
+
+    let _live_channel = live_socket
+        .join_liveview_channel(None, None)
+        .await
+        .expect("Failed to join channel");
+
+    // the stale session forces a reconnect, which the provider moves to the failover host
+    let url = format!("http://{HOST}/nav/second_page");
+    let live_channel = live_socket
+        .navigate(url.clone(), None, Default::default())
+        .await
+        .expect("navigate");
+
+    assert_eq!(
+        *provider.requested.lock().expect("lock poison"),
+        vec![url.clone()]
+    );
+    let join_url = Url::parse(&live_socket.join_url()).expect("invalid join url");
+    assert_eq!(join_url.host_str(), Some("localhost"));
+    assert_eq!(join_url.path(), "/nav/second_page");
+    assert!(live_channel.document().render().contains("second_page"));
+
+    // the history keeps the requested url
+    assert_eq!(live_socket.current().expect("current").url, url);
+}
+
+#[derive(Default, Clone)]
+struct NavEventRecorder {
+    events: Arc<Mutex<Vec<NavEvent>>>,
+}
+
+impl NavEventHandler for NavEventRecorder {
+    fn handle_event(&self, event: NavEvent) -> HandlerResponse {
+        self.events.lock().expect("lock poison").push(event);
+        HandlerResponse::Default
+    }
+}
+
+#[tokio::test]
+async fn trigger_action_submits_form() {
+    let _ = env_logger::builder()
+        .parse_default_env()
+        .is_test(true)
+        .try_init();
+
+    let url = format!("http://{HOST}/trigger_action");
+    let live_socket = LiveSocket::new(url.to_string(), "swiftui".into(), Default::default())
+        .await
+        .expect("Failed to get liveview socket");
+
+    let live_channel = Arc::new(
+        live_socket
+            .join_liveview_channel(None, None)
+            .await
+            .expect("Failed to join channel"),
+    );
+
+    let recorder = NavEventRecorder::default();
+    live_socket.set_event_handler(Box::new(recorder.clone()));
+
+    // nothing is submitted until the server sets the binding
+    let untriggered = live_socket
+        .submit_triggered_form(live_channel.clone(), None, Default::default())
+        .await;
+    assert!(matches!(untriggered, Err(LiveSocketError::NoTriggeredForm)));
+
+    let form = live_channel
+        .document()
+        .inner()
+        .lock()
+        .expect("lock poisoned")
+        .get_by_id("trigger")
+        .expect("missing form");
+    let reply = live_channel
+        .submit_form(form.into())
+        .await
+        .expect("Failed to submit form");
+    let phoenix_channels_client::Payload::JSONPayload {
+        json: JSON::Object { object },
+    } = reply
+    else {
+        panic!("reply was not a json object");
+    };
+    let diff = object.get("diff").expect("submit reply without a diff");
+    live_channel
+        .apply_json_diff(&diff.to_string())
+        .expect("Failed to merge diff");
+
+    let triggered = live_socket
+        .submit_triggered_form(live_channel, None, Default::default())
+        .await
+        .expect("Failed to submit the triggered form");
+
+    // the controller redirects to the view named by the submitted value
+    let join_url = Url::parse(&live_socket.join_url()).expect("invalid join url");
+    assert_eq!(join_url.path(), "/nav/triggered");
+    assert!(triggered.document().render().contains("triggered"));
+    // the entry is the page the submission was redirected to
+    assert_eq!(
+        live_socket.current().expect("current").url,
+        format!("http://{HOST}/nav/triggered")
+    );
+    assert!(live_socket.can_go_back());
+
+    // the handler saw the push to the form's action, then its replacement by the final page
+    let events = recorder.events.lock().expect("lock poison").clone();
+    let [push, replace] = events.as_slice() else {
+        panic!("expected two navigation events, got {events:?}");
+    };
+    assert_eq!(push.event, NavEventType::Push);
+    assert_eq!(replace.event, NavEventType::Replace);
+    assert_eq!(replace.from.as_ref(), Some(&push.to));
+    assert_eq!(replace.to.url, format!("http://{HOST}/nav/triggered"));
+}
+
+#[tokio::test]
+async fn cancel_pending_navigation() {
+    let _ = env_logger::builder()
+        .parse_default_env()
+        .is_test(true)
+        .try_init();
+
+    let first = format!("http://{HOST}/nav/first_page");
+    let live_socket = LiveSocket::new(first.clone(), "swiftui".into(), Default::default())
+        .await
+        .expect("Failed to get liveview socket");
+
+    let _live_channel = live_socket
+        .join_liveview_channel(None, None)
+        .await
+        .expect("Failed to join channel");
+    assert!(!live_socket.cancel_pending_navigation());
+
+    let second = format!("http://{HOST}/nav/second_page");
+    let navigation = live_socket.navigate(second.clone(), None, Default::default());
+    let cancel = async {
+        // let the navigation start its request before cancelling it
+        tokio::task::yield_now().await;
+        live_socket.cancel_pending_navigation()
+    };
+
+    let (result, cancelled) = tokio::join!(navigation, cancel);
+    assert!(cancelled);
+    assert!(matches!(result, Err(LiveSocketError::NavigationCancelled)));
+
+    // the cancelled navigation is rolled back out of the history
+    let current = live_socket.current().expect("current");
+    assert_eq!(current.url, first);
+    assert_eq!(live_socket.get_entries().len(), 1);
+
+    let live_channel = live_socket
+        .navigate(second.clone(), None, Default::default())
+        .await
+        .expect("navigate after cancel");
+
+    let current = live_socket.current().expect("current");
+    assert_eq!(current.url, second);
+    assert!(live_channel
+        .join_document()
+        .expect("join document")
+        .to_string()
+        .contains("second_page"));
+}
+
+#[tokio::test]
+async fn duplicate_navigation_does_not_reconnect() {
+    let _ = env_logger::builder()
+        .parse_default_env()
+        .is_test(true)
+        .try_init();
+
+    let url = format!("http://{HOST}/nav/first_page");
+    let live_socket = LiveSocket::new(url.to_string(), "swiftui".into(), Default::default())
+        .await
+        .expect("Failed to get liveview socket");
+
+    let counter = ReplacementCounter::default();
+    live_socket.set_document_replaced_handler(Box::new(counter.clone()));
+
+    let _live_channel = live_socket
+        .join_liveview_channel(None, None)
+        .await
+        .expect("Failed to join channel");
+
+    let second = format!("http://{HOST}/nav/second_page");
+    let _live_channel = live_socket
+        .navigate(second.clone(), None, Default::default())
+        .await
+        .expect("navigate");
+    let duplicate = live_socket
+        .navigate(second.clone(), None, Default::default())
+        .await;
+
+    // the current channel is kept
+    assert!(matches!(
+        duplicate,
+        Err(LiveSocketError::DuplicateNavigation)
+    ));
+    assert_eq!(counter.documents.lock().expect("lock poison").len(), 1);
+    assert_eq!(live_socket.get_entries().len(), 2);
+
+    // replacing the current entry is not a duplicate
+    let replace = NavOptions {
+        action: Some(NavAction::Replace),
+        ..Default::default()
+    };
+    let _live_channel = live_socket
+        .navigate(second.clone(), None, replace)
+        .await
+        .expect("replace");
+
+    assert_eq!(counter.documents.lock().expect("lock poison").len(), 2);
+    assert_eq!(live_socket.get_entries().len(), 2);
+}
+
+#[tokio::test]
+async fn resume_after_disconnect() {
+    let _ = env_logger::builder()
+        .parse_default_env()
+        .is_test(true)
+        .try_init();
+
+    let url = format!("http://{HOST}/nav/first_page");
+    let live_socket = LiveSocket::new(url.to_string(), "swiftui".into(), Default::default())
+        .await
+        .expect("Failed to get liveview socket");
+
+    let _live_channel = live_socket
+        .join_liveview_channel(None, None)
+        .await
+        .expect("Failed to join channel");
+
+    let second = format!("http://{HOST}/nav/second_page");
+    let live_channel = live_socket
+        .navigate(second.clone(), None, Default::default())
+        .await
+        .expect("navigate");
+    let before = live_channel.document().render();
+    let entries = live_socket.get_entries();
+
+    live_socket
+        .socket()
+        .disconnect()
+        .await
+        .expect("Failed to disconnect");
+
+    let live_channel = live_socket.resume(None).await.expect("resume");
+
+    assert_eq!(live_socket.get_entries(), entries);
+    assert_eq!(live_socket.current().expect("current").url, second);
+    assert_eq!(live_channel.document().render(), before);
+}
+
+#[tokio::test]
+async fn restore_exported_session() {
+    let _ = env_logger::builder()
+        .parse_default_env()
+        .is_test(true)
+        .try_init();
+
+    let url = format!("http://{HOST}/nav/first_page");
+    let live_socket = LiveSocket::new(url.to_string(), "swiftui".into(), Default::default())
+        .await
+        .expect("Failed to get liveview socket");
+
+    let _live_channel = live_socket
+        .join_liveview_channel(None, None)
+        .await
+        .expect("Failed to join channel");
+
+    let join_params = HashMap::from([(
+        "mode".to_string(),
+        JSON::Str {
+            string: "compact".to_string(),
+        },
+    )]);
+    let second = format!("http://{HOST}/nav/second_page");
+    let live_channel = live_socket
+        .navigate(second.clone(), Some(join_params), Default::default())
+        .await
+        .expect("navigate");
+    let before = live_channel.document().render();
+    let entries = live_socket.get_entries();
+
+    let session = live_socket.export_session().expect("Failed to export");
+    drop(live_channel);
+    drop(live_socket);
+
+    let live_socket = LiveSocket::restore_session(session, None)
+        .await
+        .expect("Failed to restore session");
+    assert_eq!(live_socket.get_entries(), entries);
+    assert_eq!(live_socket.current().expect("current").url, second);
+    let restored_params = live_socket.last_join_params().expect("missing join params");
+    assert_eq!(restored_params.len(), 1);
+    assert_eq!(restored_params["mode"].to_string(), r#""compact""#);
+
+    let live_channel = live_socket
+        .join_liveview_channel(live_socket.last_join_params(), None)
+        .await
+        .expect("Failed to join channel");
+    assert_eq!(live_channel.document().render(), before);
+
+    // new entries continue the restored ids
+    let _live_channel = live_socket
+        .navigate(url, None, Default::default())
+        .await
+        .expect("navigate");
+    let current = live_socket.current().expect("current");
+    assert!(entries.iter().all(|entry| entry.id < current.id));
+}
+
+fn cached_session(phx_id: &str) -> SessionData {
+    SessionData {
+        join_headers: Default::default(),
+        connect_opts: Default::default(),
+        csrf_token: String::new(),
+        phx_id: phx_id.to_string(),
+        phx_static: String::new(),
+        phx_session: String::new(),
+        is_main_view: true,
+        url: Url::parse("https://www.website.com/").expect("URL failed to parse"),
+        format: "swiftui".to_string(),
+        dead_render: Document::empty(),
+        style_urls: Vec::new(),
+        has_live_reload: false,
+        cookies: Vec::new(),
+        http_client: None,
+    }
+}
+
+#[test]
+fn dead_render_cache_evicts() {
+    let url = |path: &str| {
+        Url::parse("https://www.website.com/")
+            .and_then(|base| base.join(path))
+            .expect("URL failed to parse")
+    };
+    let start = Instant::now();
+    let phx_id = |session: Option<SessionData>| session.map(|session| session.phx_id);
+
+    // disabled by default
+    let mut cache = DeadRenderCache::default();
+    cache.insert(url("a"), cached_session("a"), start);
+    assert_eq!(phx_id(cache.get(&url("a"), start)), None);
+    assert_eq!((cache.hits, cache.misses), (0, 0));
+
+    let mut cache = DeadRenderCache::new(2, Duration::from_secs(60));
+    cache.insert(url("a"), cached_session("a"), start);
+    cache.insert(url("b"), cached_session("b"), start);
+    // using `a` makes `b` the least recently used entry
+    assert_eq!(phx_id(cache.get(&url("a"), start)), Some("a".to_string()));
+    cache.insert(url("c"), cached_session("c"), start);
+    assert_eq!(phx_id(cache.get(&url("b"), start)), None);
+    assert_eq!(phx_id(cache.get(&url("c"), start)), Some("c".to_string()));
+
+    // stale entries are not returned
+    let later = start + Duration::from_secs(61);
+    assert_eq!(phx_id(cache.get(&url("a"), later)), None);
+    assert_eq!((cache.hits, cache.misses), (2, 2));
+
+    cache.remove(&url("c"));
+    assert_eq!(phx_id(cache.get(&url("c"), start)), None);
+}
+
+#[tokio::test]
+async fn back_navigation_uses_cached_dead_render() {
+    let _ = env_logger::builder()
+        .parse_default_env()
+        .is_test(true)
+        .try_init();
+
+    let url = format!("http://{HOST}/nav/first_page");
+    let live_socket = LiveSocket::new(url.to_string(), "swiftui".into(), Default::default())
+        .await
+        .expect("Failed to get liveview socket");
+    live_socket.set_dead_render_cache(4, 60_000);
+
+    let _live_channel = live_socket
+        .join_liveview_channel(None, None)
+        .await
+        .expect("Failed to join channel");
+
+    let second = format!("http://{HOST}/nav/second_page");
+    let _live_channel = live_socket
+        .navigate(second, None, Default::default())
+        .await
+        .expect("navigate");
+    let fetches = live_socket
+        .dead_render_cache
+        .lock()
+        .expect("lock poison")
+        .misses;
+
+    let live_channel = live_socket.back(None, None).await.expect("back");
+    assert_eq!(live_socket.current().expect("current").url, url);
+    assert!(live_channel.document().render().contains("first_page"));
+
+    // the first page's dead render was cached when the cache was enabled
+    let cache = live_socket.dead_render_cache.lock().expect("lock poison");
+    assert_eq!(cache.misses, fetches);
+}
+
+#[tokio::test]
+async fn rejected_cached_dead_render_is_refetched() {
+    let _ = env_logger::builder()
+        .parse_default_env()
+        .is_test(true)
+        .try_init();
+
+    let url = format!("http://{HOST}/nav/first_page");
+    let live_socket = LiveSocket::new(url.to_string(), "swiftui".into(), Default::default())
+        .await
+        .expect("Failed to get liveview socket");
+    live_socket.set_dead_render_cache(4, 60_000);
+
+    let _live_channel = live_socket
+        .join_liveview_channel(None, None)
+        .await
+        .expect("Failed to join channel");
+
+    let second = format!("http://{HOST}/nav/second_page");
+    let _live_channel = live_socket
+        .navigate(second, None, Default::default())
+        .await
+        .expect("navigate");
+
+    // a cached session the server no longer accepts
+    let mut expired = live_socket
+        .session_data
+        .lock()
+        .expect("lock poison")
+        .clone();
+    expired.phx_session = "expired".to_string();
+    live_socket
+        .dead_render_cache
+        .lock()
+        .expect("lock poison")
+        .insert(
+            Url::parse(&url).expect("URL failed to parse"),
+            expired,
+            Instant::now(),
+        );
+
+    let live_channel = live_socket.back(None, None).await.expect("back");
+    assert_eq!(live_socket.current().expect("current").url, url);
+    assert!(live_channel.document().render().contains("first_page"));
+
+    // the rejected entry was replaced with the dead render fetched for the retry
+    let cached = live_socket
+        .dead_render_cache
+        .lock()
+        .expect("lock poison")
+        .get(
+            &Url::parse(&url).expect("URL failed to parse"),
+            Instant::now(),
+        )
+        .expect("the refetched dead render was not cached");
+    assert_ne!(cached.phx_session, "expired");
+}
+
+#[test]
+fn navigation_headers_merge_into_connect_opts() {
+    let opts = ConnectOpts {
+        headers: Some(HashMap::from([
+            ("Referer".to_string(), "https://example.com/a".to_string()),
+            ("X-App".to_string(), "lvn".to_string()),
+        ])),
+        ..Default::default()
+    };
+
+    let nav_headers = HashMap::from([
+        ("Referer".to_string(), "https://example.com/b".to_string()),
+        ("X-Experiment".to_string(), "variant-b".to_string()),
+    ]);
+
+    let merged = opts.clone().with_extra_headers(Some(nav_headers.clone()));
+    let headers = merged.headers.expect("missing headers");
+    assert_eq!(headers.len(), 3);
+    assert_eq!(headers["Referer"], "https://example.com/b");
+    assert_eq!(headers["X-App"], "lvn");
+    assert_eq!(headers["X-Experiment"], "variant-b");
+
+    assert_eq!(opts.clone().with_extra_headers(None), opts);
+
+    let merged = ConnectOpts::default().with_extra_headers(Some(nav_headers.clone()));
+    assert_eq!(merged.headers, Some(nav_headers));
+}
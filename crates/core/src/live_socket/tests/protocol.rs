@@ -0,0 +1,252 @@
+use std::sync::{Arc, Mutex};
+
+use super::*;
+
+#[derive(Default)]
+struct CommandRecorder {
+    commands: Mutex<Vec<ServerCommand>>,
+}
+
+impl ServerCommandHandler for CommandRecorder {
+    fn on_server_command(&self, command: ServerCommand) {
+        self.commands.lock().expect("lock poisoned").push(command);
+    }
+}
+
+#[test]
+fn server_commands_dispatched() {
+    let diff = serde_json::json!({
+        "0": "typing",
+        "e": [
+            ["lvn:focus", {"id": "name-input"}],
+            ["lvn:scroll_to", {"id": "footer"}],
+            ["highlight", {"color": "red"}],
+            "not a command"
+        ]
+    });
+
+    let recorder = CommandRecorder::default();
+    protocol::dispatch(&diff["e"], &recorder);
+
+    let commands = recorder.commands.into_inner().expect("lock poisoned");
+    assert_eq!(
+        commands,
+        vec![
+            ServerCommand::Focus {
+                id: "name-input".into()
+            },
+            ServerCommand::ScrollTo {
+                id: "footer".into()
+            },
+            ServerCommand::Dispatch {
+                event: "highlight".into(),
+                payload: r#"{"color":"red"}"#.into()
+            },
+        ]
+    );
+
+    assert!(ServerCommand::from_diff(&serde_json::json!({"0": "idle"})).is_empty());
+}
+
+#[test]
+fn submit_form_payload() {
+    let doc = crate::dom::Document::parse(
+        r#"<LiveForm id="login" phx-submit="login" phx-value-source="sheet">
+            <TextField name="email" value="a b@example.com">Email</TextField>
+            <VStack>
+                <SecureField name="password" value="p&amp;ss">Password</SecureField>
+                <TextField name="disabled" value="x" disabled>Skipped</TextField>
+            </VStack>
+            <LiveSubmitButton>Enter</LiveSubmitButton>
+        </LiveForm>"#,
+    )
+    .expect("invalid document");
+    let form = doc.first_child(doc.root()).expect("missing form");
+
+    assert_eq!(
+        protocol::form_values(&doc, form),
+        vec![
+            ("email".to_string(), "a b@example.com".to_string()),
+            ("password".to_string(), "p&ss".to_string()),
+        ]
+    );
+    assert_eq!(
+        protocol::submit_payload(&doc, form),
+        Some(serde_json::json!({
+            "type": "form",
+            "event": "login",
+            "value": "email=a+b%40example.com&password=p%26ss&source=sheet",
+        }))
+    );
+
+    let button = doc.last_child(form).expect("missing button");
+    assert_eq!(protocol::submit_payload(&doc, button), None);
+}
+
+#[test]
+fn flash_messages_from_flash_group() {
+    let doc = crate::dom::Document::parse(
+        r#"<Group id="flash-group">
+            <VStack id="flash-info" phx-click="lv:clear-flash" phx-value-key="info">
+                <Text>Welcome back!</Text>
+            </VStack>
+            <VStack id="flash-error" phx-click="lv:clear-flash" phx-value-key="error">
+                <Text>Something</Text> <Text>went wrong</Text>
+            </VStack>
+        </Group>
+        <VStack>
+            <Text id="flash-unrelated">Not a flash</Text>
+        </VStack>"#,
+    )
+    .expect("invalid document");
+
+    let flash = protocol::flash_messages(&doc);
+    assert_eq!(flash.len(), 2);
+    assert_eq!(flash["info"], "Welcome back!");
+    assert_eq!(flash["error"], "Something went wrong");
+
+    let empty =
+        crate::dom::Document::parse(r#"<Group id="flash-group" />"#).expect("invalid document");
+    assert!(protocol::flash_messages(&empty).is_empty());
+}
+
+#[test]
+fn clear_flash_messages_locally() {
+    let mut doc = crate::dom::Document::parse(
+        r#"<Group id="flash-group">
+            <Text id="flash-info">Saved</Text>
+            <Text id="flash-error">Failed</Text>
+        </Group>"#,
+    )
+    .expect("invalid document");
+
+    assert!(protocol::clear_flash_messages(&mut doc, Some("info")));
+    let flash = protocol::flash_messages(&doc);
+    assert!(!flash.contains_key("info"));
+    assert_eq!(flash["error"], "Failed");
+    assert!(!protocol::clear_flash_messages(&mut doc, Some("info")));
+
+    assert!(protocol::clear_flash_messages(&mut doc, None));
+    assert!(protocol::flash_messages(&doc).is_empty());
+
+    assert_eq!(
+        protocol::clear_flash_payload(Some("info")),
+        serde_json::json!({
+            "type": "click",
+            "event": "lv:clear-flash",
+            "value": {"key": "info"},
+        })
+    );
+    assert_eq!(
+        protocol::clear_flash_payload(None)["value"],
+        serde_json::json!({})
+    );
+}
+
+#[test]
+fn debounced_events_collapse() {
+    use std::time::{Duration, Instant};
+
+    let doc = crate::dom::Document::parse(
+        r#"<Group>
+            <TextField id="search" phx-debounce="300" />
+            <Button id="save" phx-throttle="1000" />
+            <TextField id="name" phx-debounce="blur" />
+        </Group>"#,
+    )
+    .expect("invalid document");
+    let search = doc.get_by_id("search").expect("missing search");
+    let save = doc.get_by_id("save").expect("missing save");
+    let name = doc.get_by_id("name").expect("missing name");
+
+    assert_eq!(
+        protocol::rate_limit(&doc, search),
+        Some(RateLimit::Debounce(Duration::from_millis(300)))
+    );
+    assert_eq!(
+        protocol::rate_limit(&doc, save),
+        Some(RateLimit::Throttle(Duration::from_millis(1000)))
+    );
+    assert_eq!(protocol::rate_limit(&doc, name), None);
+
+    // only the last of a burst of events within the window is sent
+    let now = Arc::new(Mutex::new(Instant::now()));
+    let clock = now.clone();
+    let mut limiter = RateLimiter::with_clock(move || *clock.lock().expect("lock poisoned"));
+    let tickets: Vec<_> = (0..3).map(|_| limiter.debounce(search)).collect();
+    let sent: Vec<_> = tickets
+        .iter()
+        .filter(|ticket| limiter.take_latest(search, **ticket))
+        .collect();
+    assert_eq!(sent, [&tickets[2]]);
+    let next = limiter.debounce(search);
+    assert!(!limiter.take_latest(search, tickets[2]));
+    assert!(limiter.take_latest(search, next));
+    assert!(limiter.is_empty());
+
+    let interval = Duration::from_millis(1000);
+    let advance = |by: Duration| *now.lock().expect("lock poisoned") += by;
+    assert!(limiter.throttle(save, interval));
+    advance(Duration::from_millis(500));
+    assert!(!limiter.throttle(save, interval));
+    advance(Duration::from_millis(500));
+    assert!(limiter.throttle(save, interval));
+
+    // a node is only tracked until its window elapsed
+    assert!(limiter.throttle(search, interval));
+    assert_eq!(limiter.len(), 2);
+    advance(interval);
+    assert!(limiter.throttle(search, interval));
+    assert_eq!(limiter.len(), 1);
+
+    // clearing forgets every node, dropping the events debounced before
+    let ticket = limiter.debounce(name);
+    limiter.clear();
+    assert!(limiter.is_empty());
+    assert!(!limiter.take_latest(name, ticket));
+    assert!(limiter.throttle(save, interval));
+}
+
+#[test]
+fn trigger_action_submits_natively() {
+    use phoenix_channels_client::url::Url;
+
+    use crate::live_socket::socket::Method;
+
+    let doc = crate::dom::Document::parse(
+        r#"<Group>
+            <LiveForm id="search" action="/search" phx-submit="search">
+                <TextField name="q" value="live view">Query</TextField>
+            </LiveForm>
+            <LiveForm id="login" action="/session?next=1" method="POST" phx-submit="login" phx-trigger-action>
+                <TextField name="email" value="a@example.com">Email</TextField>
+            </LiveForm>
+        </Group>"#,
+    )
+    .expect("invalid document");
+    let search = doc.get_by_id("search").expect("missing search");
+    let login = doc.get_by_id("login").expect("missing login");
+    let base = Url::parse("http://example.com/users/log_in").expect("invalid url");
+
+    assert_eq!(protocol::triggered_form(&doc), Some(login));
+    assert_eq!(
+        protocol::form_action(&doc, login, &base),
+        Some(protocol::FormAction {
+            url: Url::parse("http://example.com/session?next=1").expect("invalid url"),
+            method: Method::Post,
+            body: Some("email=a%40example.com".into()),
+        })
+    );
+    assert_eq!(
+        protocol::form_action(&doc, search, &base),
+        Some(protocol::FormAction {
+            url: Url::parse("http://example.com/search?q=live+view").expect("invalid url"),
+            method: Method::Get,
+            body: None,
+        })
+    );
+
+    let untriggered =
+        crate::dom::Document::parse(r#"<LiveForm phx-submit="save" />"#).expect("invalid document");
+    assert_eq!(protocol::triggered_form(&untriggered), None);
+}
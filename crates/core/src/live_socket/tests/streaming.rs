@@ -1,3 +1,4 @@
+use phoenix_channels_client::Payload;
 use tokio::sync::mpsc::error::TryRecvError::Empty;
 
 use super::*;
@@ -63,3 +64,119 @@ async fn streaming_connect() -> Result<(), String> {
         MAX_TRIES * MS_DELAY
     ))
 }
+
+struct PayloadRecorder(UnboundedSender<(String, Payload)>);
+
+impl RawPayloadObserver for PayloadRecorder {
+    fn on_raw_payload(&self, event: String, payload: Payload) {
+        let _ = self.0.send((event, payload));
+    }
+}
+
+// The stream view pushes diffs on its own, so the observer must see one without any input.
+#[tokio::test]
+async fn raw_payload_observer_sees_diffs() -> Result<(), String> {
+    let _ = env_logger::builder()
+        .parse_default_env()
+        .is_test(true)
+        .try_init();
+
+    let url = format!("http://{HOST}/stream");
+
+    let live_socket = LiveSocket::new(url.to_string(), "swiftui".into(), Default::default())
+        .await
+        .map_err(|e| format!("Failed to get liveview socket {e}"))?;
+
+    let live_channel = live_socket
+        .join_liveview_channel(None, None)
+        .await
+        .map_err(|e| format!("Failed to join the liveview channel {e}"))?;
+
+    let (tx, mut rx) = unbounded_channel();
+    live_channel.set_raw_payload_observer(Box::new(PayloadRecorder(tx)));
+
+    let chan_clone = live_channel.channel().clone();
+    tokio::spawn(async move {
+        live_channel
+            .merge_diffs()
+            .await
+            .expect("Failed to merge diffs");
+    });
+
+    for _ in 0..MAX_TRIES {
+        match rx.try_recv() {
+            Ok((event, payload)) if event == "diff" => {
+                assert!(matches!(payload, Payload::JSONPayload { .. }));
+                chan_clone
+                    .leave()
+                    .await
+                    .map_err(|e| format!("Failed to leave channel {e}"))?;
+
+                return Ok(());
+            }
+            Ok(_) => {}
+            Err(Empty) => {
+                tokio::time::sleep(Duration::from_millis(MS_DELAY)).await;
+            }
+            Err(_) => {
+                return Err(String::from("Observer dropped"));
+            }
+        }
+    }
+
+    Err(format!(
+        "Exceeded {MAX_TRIES} Max tries, waited {} ms",
+        MAX_TRIES * MS_DELAY
+    ))
+}
+
+// The stream view pushes diffs on its own, wait for two of them to be applied.
+#[tokio::test]
+async fn diff_stats_track_applied_diffs() -> Result<(), String> {
+    let _ = env_logger::builder()
+        .parse_default_env()
+        .is_test(true)
+        .try_init();
+
+    let url = format!("http://{HOST}/stream");
+
+    let live_socket = LiveSocket::new(url.to_string(), "swiftui".into(), Default::default())
+        .await
+        .map_err(|e| format!("Failed to get liveview socket {e}"))?;
+
+    let live_channel = live_socket
+        .join_liveview_channel(None, None)
+        .await
+        .map_err(|e| format!("Failed to join the liveview channel {e}"))?;
+    let live_channel = Arc::new(live_channel);
+    assert_eq!(live_channel.diff_stats(), DiffStats::default());
+
+    let merging = live_channel.clone();
+    tokio::spawn(async move {
+        merging.merge_diffs().await.expect("Failed to merge diffs");
+    });
+
+    let mut first: Option<DiffStats> = None;
+    for _ in 0..2 * MAX_TRIES {
+        let stats = live_channel.diff_stats();
+        match first {
+            None if stats.count > 0 => first = Some(stats),
+            Some(first) if stats.count > first.count => {
+                assert!(stats.last_applied > first.last_applied);
+                live_channel
+                    .channel()
+                    .leave()
+                    .await
+                    .map_err(|e| format!("Failed to leave channel {e}"))?;
+                return Ok(());
+            }
+            _ => tokio::time::sleep(Duration::from_millis(MS_DELAY)).await,
+        }
+    }
+
+    Err(format!(
+        "Exceeded {} Max tries, waited {} ms",
+        2 * MAX_TRIES,
+        2 * MAX_TRIES * MS_DELAY
+    ))
+}
@@ -1,21 +1,80 @@
-use std::{borrow::Cow, collections::VecDeque, convert::Infallible, fmt, mem};
+use std::{borrow::Cow, cell::Cell, collections::VecDeque, convert::Infallible, fmt, mem, rc::Rc};
 
 use html5gum::{Emitter, Error, Readable, Reader, State, Tokenizer};
 use smallstr::SmallString;
 use smallvec::SmallVec;
 
-use crate::{dom::*, symbols, InternedString};
+use crate::{diff::fragment::ComponentSpan, dom::*, symbols, InternedString};
+
+/// Options which customize how a `Document` is parsed
+#[derive(Debug, Clone)]
+pub struct ParseOptions {
+    /// Applied to the name of every start and end tag before it is interned,
+    /// e.g. to lowercase tag names on platforms where they are case-insensitive
+    pub normalize_tag: Option<fn(&str) -> Cow<'_, str>>,
+    /// The attribute whose value registers an element as identified in the document,
+    /// so that it can be found with `Document::get_by_id`. Defaults to `id`.
+    pub id_attribute: SmallString<[u8; 16]>,
+    /// Rejects documents which do not have exactly one top-level node, see `Document::single_root`.
+    /// Useful on platforms which require a single root view. Defaults to `false`.
+    pub require_single_root: bool,
+}
+impl Default for ParseOptions {
+    fn default() -> Self {
+        Self {
+            normalize_tag: None,
+            id_attribute: SmallString::from_str("id"),
+            require_single_root: false,
+        }
+    }
+}
 
 /// Parses a `Document` from the given input
+///
+/// Character references in text and attribute values are decoded, both named (`&amp;`, `&nbsp;`)
+/// and numeric (`&#35;`, `&#x23;`). Unknown named references such as `&bogus;` are kept as written.
+/// See [EntityEncoding] for how they are encoded again when printing.
 pub fn parse<'a, R>(input: R) -> Result<Document, ParseError>
 where
     R: Readable<'a>,
     ParseError: From<<<R as Readable<'a>>::Reader as Reader>::Error>,
 {
-    let mut document = Document::empty();
-    let emitter = DocumentEmitter::new();
+    parse_with_options(input, ParseOptions::default())
+}
+
+/// Parses a `Document` from the given input, using the provided `ParseOptions`
+pub fn parse_with_options<'a, R>(input: R, options: ParseOptions) -> Result<Document, ParseError>
+where
+    R: Readable<'a>,
+    ParseError: From<<<R as Readable<'a>>::Reader as Reader>::Error>,
+{
+    parse_into(Document::empty(), input, options, &[])
+}
+
+/// Parses the given input into `document`, which is expected to be empty
+///
+/// The first element rendered in each of the `components`, which must be ordered by their start,
+/// is recorded as the root of that component.
+pub(crate) fn parse_into<'a, R>(
+    mut document: Document,
+    input: R,
+    options: ParseOptions,
+    components: &[ComponentSpan],
+) -> Result<Document, ParseError>
+where
+    R: Readable<'a>,
+    ParseError: From<<<R as Readable<'a>>::Reader as Reader>::Error>,
+{
+    let require_single_root = options.require_single_root;
+    let emitter = DocumentEmitter::new(options);
+    let position = Rc::new(Cell::new(Position::default()));
+    let reader = PositionReader {
+        inner: input.to_reader(),
+        position: position.clone(),
+    };
     let mut current_node = document.root();
-    for token in Tokenizer::new_with_emitter(input, emitter) {
+    let mut components = components.iter().peekable();
+    for token in Tokenizer::new_with_emitter(reader, emitter) {
         match token? {
             Token::Start(StartToken {
                 mut ids, element, ..
@@ -26,6 +85,17 @@ where
                 for id in ids.drain(..) {
                     document.register_id(node, id);
                 }
+
+                // the start tag was just read, so it ends at the current offset
+                let end = position.get().offset;
+                while let Some(span) = components.next_if(|span| span.range.start < end) {
+                    // a component which rendered no element ends before the tag
+                    if end <= span.range.end {
+                        if let Ok(cid) = u32::try_from(span.cid) {
+                            document.add_component_root(node, cid);
+                        }
+                    }
+                }
             }
             Token::End(_) => {
                 current_node = document.parent(current_node).unwrap();
@@ -35,13 +105,26 @@ where
                 document.append_child(current_node, node);
             }
             Token::Error(err) => {
-                return Err(ParseError::Tokenizer(err.into()));
+                let Position { line, column, .. } = position.get();
+                return Err(ParseError::Tokenizer(TokenizerError {
+                    error: err,
+                    line,
+                    column,
+                }));
+            }
+            Token::Doctype(doctype) => {
+                if document.doctype().is_none() {
+                    document.set_doctype(doctype);
+                }
             }
-            Token::Doctype(_) => {}
             Token::Comment => {}
         }
     }
 
+    if require_single_root {
+        document.single_root()?;
+    }
+
     Ok(document)
 }
 
@@ -53,6 +136,8 @@ pub enum ParseError {
     Reader(#[from] std::io::Error),
     #[error("encountered an error while tokenizing input: {0}")]
     Tokenizer(#[from] TokenizerError),
+    #[error(transparent)]
+    MultipleRoots(#[from] MultipleRootsError),
 }
 impl From<Infallible> for ParseError {
     #[inline(always)]
@@ -67,23 +152,129 @@ impl From<html5gum::Error> for ParseError {
     }
 }
 
-/// Wraps `html5gum::Error` to implement `std::error::Error`
+impl ParseError {
+    /// Returns the 1-based line and column of the character at which tokenizing failed,
+    /// if known
+    pub fn position(&self) -> Option<(u32, u32)> {
+        match self {
+            Self::Tokenizer(err) if err.line > 0 => Some((err.line, err.column)),
+            _ => None,
+        }
+    }
+}
+
+/// Wraps `html5gum::Error` to implement `std::error::Error`, along with the position of the
+/// last character read before the error was reported.
+///
+/// Lines and columns are 1-based and count characters, a line of 0 means the position is unknown.
 #[derive(Debug)]
-#[repr(transparent)]
-pub struct TokenizerError(html5gum::Error);
+pub struct TokenizerError {
+    error: html5gum::Error,
+    line: u32,
+    column: u32,
+}
+impl TokenizerError {
+    pub fn error(&self) -> &html5gum::Error {
+        &self.error
+    }
+
+    pub fn line(&self) -> u32 {
+        self.line
+    }
+
+    pub fn column(&self) -> u32 {
+        self.column
+    }
+}
 impl fmt::Display for TokenizerError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{}", self.0.as_str())
+        if self.line > 0 {
+            write!(f, "{}:{}: ", self.line, self.column)?;
+        }
+        write!(f, "{}", self.error.as_str())
     }
 }
 impl From<html5gum::Error> for TokenizerError {
     #[inline(always)]
     fn from(err: html5gum::Error) -> Self {
-        Self(err)
+        Self {
+            error: err,
+            line: 0,
+            column: 0,
+        }
     }
 }
 impl std::error::Error for TokenizerError {}
 
+/// The position of the last character read by the tokenizer
+#[derive(Debug, Default, Clone, Copy)]
+struct Position {
+    line: u32,
+    column: u32,
+    /// The number of bytes read
+    offset: usize,
+    /// Whether the last character was a newline, so the next one starts a line
+    after_newline: bool,
+}
+impl Position {
+    fn advance(mut self, bytes: &[u8]) -> Self {
+        self.offset += bytes.len();
+        // utf-8 continuation bytes belong to the preceding character
+        for &byte in bytes.iter().filter(|byte| *byte & 0xC0 != 0x80) {
+            if self.line == 0 || self.after_newline {
+                self.line += 1;
+                self.column = 1;
+            } else {
+                self.column += 1;
+            }
+            self.after_newline = byte == b'\n';
+        }
+        self
+    }
+}
+
+/// Wraps the reader of the input to track the [Position] of the tokenizer
+struct PositionReader<R> {
+    inner: R,
+    position: Rc<Cell<Position>>,
+}
+impl<R: Reader> PositionReader<R> {
+    fn advance(&self, bytes: &[u8]) {
+        self.position.set(self.position.get().advance(bytes));
+    }
+}
+impl<R: Reader> Reader for PositionReader<R> {
+    type Error = R::Error;
+
+    fn read_byte(&mut self) -> Result<Option<u8>, Self::Error> {
+        let byte = self.inner.read_byte()?;
+        if let Some(byte) = byte {
+            self.advance(&[byte]);
+        }
+        Ok(byte)
+    }
+
+    fn try_read_string(&mut self, s: &[u8], case_sensitive: bool) -> Result<bool, Self::Error> {
+        let matched = self.inner.try_read_string(s, case_sensitive)?;
+        if matched {
+            self.advance(s);
+        }
+        Ok(matched)
+    }
+
+    fn read_until<'b>(
+        &'b mut self,
+        needle: &[u8],
+        char_buf: &'b mut [u8; 4],
+    ) -> Result<Option<&'b [u8]>, Self::Error> {
+        let bytes = self.inner.read_until(needle, char_buf)?;
+        if let Some(bytes) = bytes {
+            self.position.set(self.position.get().advance(bytes));
+        }
+        Ok(bytes)
+    }
+}
+
 #[derive(Debug)]
 struct StartToken {
     ids: Vec<SmallString<[u8; 16]>>,
@@ -138,9 +329,10 @@ struct DocumentEmitter {
     current_doctype: SmallVec<[u8; 16]>,
     last_start_tag: InternedString,
     emitted_tokens: VecDeque<Token>,
+    options: ParseOptions,
 }
 impl DocumentEmitter {
-    pub fn new() -> Self {
+    pub fn new(options: ParseOptions) -> Self {
         Self {
             current_characters: Default::default(),
             current_token: None,
@@ -149,6 +341,14 @@ impl DocumentEmitter {
             current_doctype: Default::default(),
             last_start_tag: symbols::Empty.into(),
             emitted_tokens: VecDeque::new(),
+            options,
+        }
+    }
+
+    fn normalize_tag<'a>(&self, tag: &'a str) -> Cow<'a, str> {
+        match self.options.normalize_tag {
+            Some(normalize) => normalize(tag),
+            None => Cow::Borrowed(tag),
         }
     }
 
@@ -167,7 +367,7 @@ impl DocumentEmitter {
                 }) => {
                     let k = smallvec_to_smallstr(k);
                     let v = smallvec_to_smallstr(v);
-                    if k.as_str() == "id" {
+                    if k == self.options.id_attribute {
                         ids.push(v.clone());
                     }
                     element.set_attribute(k.as_str().into(), Some(v.into_string()));
@@ -199,7 +399,8 @@ impl Emitter for DocumentEmitter {
                 self.last_start_tag = symbols::Empty.into();
             }
             Some(bytes) => {
-                self.last_start_tag = String::from_utf8_lossy(bytes).into();
+                let tag = String::from_utf8_lossy(bytes);
+                self.last_start_tag = self.normalize_tag(&tag).as_ref().into();
             }
         }
     }
@@ -253,7 +454,8 @@ impl Emitter for DocumentEmitter {
             }) => {
                 assert!(!self.current_tag.is_empty());
                 let tag = smallvec_to_smallstr(mem::take(&mut self.current_tag));
-                element.name = tag.as_str().into();
+                element.name = self.normalize_tag(tag.as_str()).as_ref().into();
+                element.self_closing = self_closing;
                 if self_closing {
                     let end_tag = element.name.clone();
                     self.emit_token(Token::Start(StartToken {
@@ -276,7 +478,8 @@ impl Emitter for DocumentEmitter {
             Token::End(_) => {
                 assert!(!self.current_tag.is_empty());
                 let t = smallvec_to_smallstr(mem::take(&mut self.current_tag));
-                self.emit_token(Token::End(t.as_str().into()));
+                let name = self.normalize_tag(t.as_str()).as_ref().into();
+                self.emit_token(Token::End(name));
                 None
             }
             other => invalid_state("invalid state in which to emit tag", Some(&other)),
@@ -281,6 +281,123 @@ fn diff_live_form() -> Result<(), Error> {
     )
 }
 
+#[test]
+fn diff_stats_counts() -> Result<(), Error> {
+    let prev = Document::parse(r#"<a><b class="x"></b><c></c><d id="d"></d><f /></a><g />"#)?;
+    let next = Document::parse(r#"<a><b class="y"></b><d id="d"></d><e></e><span>x</span></a>"#)?;
+
+    let stats = diff::diff_stats(&prev, &next);
+    assert_eq!(
+        stats,
+        diff::DiffStats {
+            added: 1,
+            removed: 1,
+            changed: 1,
+            replaced: 2,
+            moved: 1,
+        }
+    );
+
+    assert!(diff::diff_stats(&prev, &prev).is_empty());
+    Ok(())
+}
+
+#[test]
+fn diff_three_way_merge() -> Result<(), Error> {
+    let base = Document::parse(r#"<a><b class="x"></b><c>hello</c></a>"#)?;
+    let b = NodeRef::from_u32(2);
+
+    let mut ours = base.clone();
+    ours.set_attribute(b, "class", Some("y".to_string()));
+    let theirs = Document::parse(r#"<a><b class="x"></b><c>goodbye</c></a>"#)?;
+
+    let (merged, conflicts) = Document::three_way_merge(&base, &ours, &theirs);
+    assert!(conflicts.is_empty());
+    assert_eq!(
+        merged.to_string(),
+        Document::parse(r#"<a><b class="y"></b><c>goodbye</c></a>"#)?.to_string()
+    );
+    Ok(())
+}
+
+#[test]
+fn diff_three_way_merge_conflict() -> Result<(), Error> {
+    let base = Document::parse(r#"<a><b class="a"></b></a>"#)?;
+    let b = NodeRef::from_u32(2);
+
+    let mut ours = base.clone();
+    ours.set_attribute(b, "class", Some("b".to_string()));
+    let theirs = Document::parse(r#"<a><b class="c"></b></a>"#)?;
+
+    let (merged, conflicts) = Document::three_way_merge(&base, &ours, &theirs);
+    assert_eq!(conflicts.len(), 1);
+    assert_eq!(conflicts[0].node, b);
+    assert_eq!(merged.to_string(), ours.to_string());
+    Ok(())
+}
+
+#[test]
+fn diff_three_way_merge_moved_node_removed() -> Result<(), Error> {
+    let base = Document::parse(r#"<a><b></b><c id="c"></c><d id="d"></d></a>"#)?;
+    let d = NodeRef::from_u32(4);
+
+    let mut ours = base.clone();
+    ours.detach(d);
+    let theirs = Document::parse(r#"<a><d id="d"></d><b></b><c id="c"></c></a>"#)?;
+
+    // the move is dropped as a whole instead of attaching the removed node again
+    let (merged, conflicts) = Document::three_way_merge(&base, &ours, &theirs);
+    assert_eq!(conflicts.len(), 1);
+    assert_eq!(conflicts[0].node, d);
+    assert_eq!(merged.to_string(), ours.to_string());
+
+    // the same when the removed node is moved by being appended to its parent
+    let base = Document::parse(r#"<a><b id="b"></b><c id="c"></c></a>"#)?;
+    let b = NodeRef::from_u32(2);
+
+    let mut ours = base.clone();
+    ours.detach(b);
+    let theirs = Document::parse(r#"<a><c id="c"></c><b id="b"></b></a>"#)?;
+
+    let (merged, conflicts) = Document::three_way_merge(&base, &ours, &theirs);
+    assert_eq!(conflicts.len(), 1);
+    assert_eq!(conflicts[0].node, b);
+    assert_eq!(merged.to_string(), ours.to_string());
+    Ok(())
+}
+
+#[test]
+fn diff_ignoring_attributes() -> Result<(), Error> {
+    let prev = Document::parse(r#"<a><b data-phx-ref="1" class="x"></b></a>"#)?;
+    let next = Document::parse(r#"<a><b data-phx-ref="2" class="x"></b></a>"#)?;
+    let options = diff::DiffOptions {
+        ignore_attributes: ["data-phx-ref".to_string()].into(),
+    };
+
+    assert!(!diff::diff(&prev, &next).is_empty());
+    assert!(diff::diff_with_options(&prev, &next, options.clone()).is_empty());
+
+    // ignored attributes keep their current value when other attributes change
+    let next = Document::parse(r#"<a><b data-phx-ref="3" class="y"></b></a>"#)?;
+    let b = NodeRef::from_u32(2);
+    let patches = diff::diff_with_options(&prev, &next, options);
+    let [Patch::SetAttributes { node, attributes }] = patches.as_slice() else {
+        panic!("expected a single attribute change, got {patches:?}");
+    };
+    assert_eq!(*node, b);
+    assert_eq!(
+        attributes
+            .iter()
+            .map(|attr| (attr.name.to_string(), attr.value.clone()))
+            .collect::<Vec<_>>(),
+        vec![
+            ("class".to_string(), Some("y".to_string())),
+            ("data-phx-ref".to_string(), Some("1".to_string())),
+        ]
+    );
+    Ok(())
+}
+
 test_fixture!("attr-value-empty-string");
 test_fixture!("change-tagname");
 test_fixture!("change-tagname-ids");
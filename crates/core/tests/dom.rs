@@ -1,3 +1,4 @@
+use liveview_native_core::diff::fragment::RootDiff;
 use liveview_native_core::dom::*;
 
 #[test]
@@ -74,6 +75,1036 @@ fn iframe_closing_tag_roundtrip() {
     pretty_assertions::assert_eq!(min_body, doc.to_string());
 }
 
+#[test]
+fn dom_child_accessors() {
+    let doc =
+        Document::parse(r#"<list><a /><b /><c /></list><empty />"#).expect("invalid document");
+    let root = doc.root();
+    let list = doc.first_child(root).expect("missing list");
+    let empty = doc.last_child(root).expect("missing empty");
+
+    let children = doc.children(list);
+    assert_eq!(doc.first_child(list), Some(children[0]));
+    assert_eq!(doc.last_child(list), Some(children[2]));
+    assert_eq!(doc.nth_child(list, 1), Some(children[1]));
+    assert_eq!(doc.nth_child(list, 3), None);
+
+    assert_eq!(doc.first_child(empty), None);
+    assert_eq!(doc.last_child(empty), None);
+    assert_eq!(doc.nth_child(empty, 0), None);
+}
+
+#[test]
+fn dom_replace_children() {
+    let mut doc = Document::parse(r#"<list><a /><b /><c /></list>"#).expect("invalid document");
+    let list = doc.first_child(doc.root()).expect("missing list");
+    let old_children = doc.children(list).to_vec();
+
+    let d = doc.push_node(NodeData::new("d"));
+    let e = doc.push_node(NodeData::new("e"));
+    doc.replace_children(list, vec![d, e])
+        .expect("failed to replace children");
+
+    assert_eq!(doc.children(list), &[d, e]);
+    assert_eq!(doc.parent(d), Some(list));
+    assert_eq!(doc.parent(e), Some(list));
+    for child in old_children {
+        assert_eq!(doc.parent(child), None);
+    }
+    assert_eq!(doc.to_string(), "<list>\n    <d />\n    <e />\n</list>");
+}
+
+#[test]
+fn dom_replace_children_rejects_invalid_trees() {
+    let mut doc = Document::parse(r#"<list><a><b /></a></list>"#).expect("invalid document");
+    let list = doc.first_child(doc.root()).expect("missing list");
+    let a = doc.first_child(list).expect("missing a");
+    let b = doc.first_child(a).expect("missing b");
+    let before = doc.to_string();
+
+    let c = doc.push_node(NodeData::new("c"));
+    assert_eq!(
+        doc.replace_children(b, vec![c, c]),
+        Err(ReplaceChildrenError::Duplicate(c))
+    );
+    assert_eq!(
+        doc.replace_children(b, vec![c, a]),
+        Err(ReplaceChildrenError::Ancestor(a))
+    );
+    assert_eq!(
+        doc.replace_children(b, vec![doc.root()]),
+        Err(ReplaceChildrenError::Ancestor(doc.root()))
+    );
+    assert_eq!(
+        doc.replace_children(c, vec![c]),
+        Err(ReplaceChildrenError::Ancestor(c))
+    );
+    assert_eq!(
+        doc.replace_children(c, vec![b]),
+        Err(ReplaceChildrenError::HasParent(b))
+    );
+
+    // a failed replacement leaves the document untouched
+    assert_eq!(doc.to_string(), before);
+    assert_eq!(doc.children(b), &[]);
+    assert_eq!(doc.parent(c), None);
+}
+
+#[test]
+fn dom_closest() {
+    let doc = Document::parse(
+        r#"<div phx-click="outer"><section phx-click="inner"><ul><li><span>Click me</span></li></ul></section></div>"#,
+    )
+    .expect("invalid document");
+    let span = doc
+        .select(Selector::Tag("span".into()))
+        .next()
+        .expect("missing span");
+    let leaf = doc.first_child(span).expect("missing leaf");
+    let section = doc
+        .select(Selector::Tag("section".into()))
+        .next()
+        .expect("missing section");
+
+    assert_eq!(
+        doc.closest(leaf, Selector::Attribute("phx-click".into())),
+        Some(section)
+    );
+    assert_eq!(doc.closest(span, Selector::Tag("span".into())), Some(span));
+    assert_eq!(doc.closest(leaf, Selector::Tag("table".into())), None);
+}
+
+#[test]
+fn dom_is_blank() {
+    assert!(Document::empty().is_blank());
+    assert!(Document::parse("").expect("invalid document").is_blank());
+
+    let flash_only = Document::parse(r#"<Group id="flash-group" />"#).expect("invalid document");
+    assert!(!flash_only.is_empty());
+    assert!(flash_only.is_blank());
+
+    let flash_with_content =
+        Document::parse(r#"<Group id="flash-group"><Text>Saved</Text></Group>"#)
+            .expect("invalid document");
+    assert!(!flash_with_content.is_blank());
+
+    let content =
+        Document::parse(r#"<Group id="flash-group" /><VStack />"#).expect("invalid document");
+    assert!(!content.is_blank());
+}
+
+#[test]
+fn dom_select_or_not() {
+    let doc = Document::parse(
+        r#"<VStack><Text>One</Text><Label>Two</Label><Button disabled>Three</Button><VStack><Button>Four</Button></VStack></VStack>"#,
+    )
+    .expect("invalid document");
+
+    let is_text = Box::new(Selector::Tag("Text".into()));
+    let is_label = Box::new(Selector::Tag("Label".into()));
+    assert_eq!(doc.select(Selector::Or(is_text, is_label)).count(), 2);
+
+    let is_button = Box::new(Selector::Tag("Button".into()));
+    let is_disabled = Box::new(Selector::Attribute("disabled".into()));
+    let enabled_buttons: Vec<_> = doc
+        .select(Selector::And(
+            is_button,
+            Box::new(Selector::Not(is_disabled)),
+        ))
+        .collect();
+    assert_eq!(enabled_buttons.len(), 1);
+    let leaf = doc.first_child(enabled_buttons[0]).expect("missing leaf");
+    assert_eq!(
+        doc.get(leaf),
+        &NodeData::Leaf {
+            value: "Four".into()
+        }
+    );
+
+    // elements that don't match still have their children visited
+    let not_vstack = Selector::Not(Box::new(Selector::Tag("VStack".into())));
+    assert_eq!(doc.select(not_vstack).count(), 4);
+}
+
+#[test]
+fn dom_select_tag() {
+    // rendered from the SwiftUI thermostat in the test server
+    let doc = Document::parse(
+        r#"<VStack>
+  <Text>
+    Current temperature: 70°F
+  </Text>
+  <Button phx-click="inc_temperature">+</Button>
+  <ui:Button phx-click="dec_temperature">-</ui:Button>
+</VStack>"#,
+    )
+    .expect("invalid document");
+
+    let buttons: Vec<_> = doc.select(Selector::tag("Button")).collect();
+    assert_eq!(buttons.len(), 1);
+    assert_eq!(
+        doc.get_attribute_by_name(buttons[0], "phx-click")
+            .and_then(|attr| attr.value),
+        Some("inc_temperature".into())
+    );
+    assert_eq!(doc.select(Selector::tag("ui:Button")).count(), 1);
+    assert_eq!(doc.select(Selector::TagLocal("Button")).count(), 2);
+    assert_eq!(doc.select(Selector::tag("Slider")).count(), 0);
+}
+
+#[test]
+fn dom_attributes_ref() {
+    let doc = Document::parse(r#"<Button phx-click="inc" disabled>+</Button>"#)
+        .expect("invalid document");
+    let button = doc.first_child(doc.root()).expect("missing button");
+    let leaf = doc.first_child(button).expect("missing leaf");
+
+    let borrowed = doc.attributes_ref(button);
+    assert_eq!(borrowed, doc.attributes(button).as_slice());
+    assert_eq!(borrowed.len(), 2);
+
+    // the slice points directly into the element's storage
+    match doc.get(button) {
+        NodeData::NodeElement { element } => {
+            assert!(std::ptr::eq(borrowed, element.attributes.as_slice()))
+        }
+        other => panic!("expected element, got {other:?}"),
+    }
+
+    assert!(doc.attributes_ref(leaf).is_empty());
+    assert!(doc.attributes_ref(doc.root()).is_empty());
+}
+
+#[test]
+fn dom_is_text_only() {
+    let doc = Document::parse(
+        r#"<VStack><Text>Hello</Text><Text>Hello <Bold>world</Bold></Text><Spacer /></VStack>"#,
+    )
+    .expect("invalid document");
+    let stack = doc.first_child(doc.root()).expect("missing stack");
+
+    let plain = doc.nth_child(stack, 0).expect("missing text");
+    let mixed = doc.nth_child(stack, 1).expect("missing text");
+    let spacer = doc.nth_child(stack, 2).expect("missing spacer");
+
+    assert!(doc.is_text_only(plain));
+    assert!(!doc.is_text_only(mixed));
+    assert!(!doc.is_text_only(spacer));
+    assert!(!doc.is_text_only(stack));
+}
+
+#[test]
+fn dom_merge_preserves_client_attributes() {
+    let mut doc = Document::parse_fragment_json(
+        r#"{"0": "off", "s": ["<Toggle id=\"t\" state=\"", "\" />"]}"#.to_owned(),
+    )
+    .expect("invalid fragment");
+    doc.set_merge_options(MergeOptions {
+        preserve_attributes: vec!["data-client-*".to_owned()],
+        ..Default::default()
+    });
+
+    let toggle = doc.get_by_id("t").expect("missing toggle");
+    doc.set_attribute(toggle, "data-client-foo", "animating".to_owned());
+    doc.set_attribute(toggle, "local", "dropped".to_owned());
+
+    let results = doc
+        .merge_fragment_json(serde_json::json!({"0": "on"}))
+        .expect("merge failed");
+    assert!(!results.is_empty());
+
+    assert_eq!(
+        doc.get_attribute_by_name(toggle, "state")
+            .and_then(|attr| attr.value),
+        Some("on".to_owned())
+    );
+    assert_eq!(
+        doc.get_attribute_by_name(toggle, "data-client-foo")
+            .and_then(|attr| attr.value),
+        Some("animating".to_owned())
+    );
+    assert_eq!(doc.get_attribute_by_name(toggle, "local"), None);
+}
+
+#[test]
+fn dom_delete_prunes_ids() {
+    let mut doc = Document::parse(
+        r#"<VStack><List id="list"><Text id="row-1">One</Text></List><Text id="footer">Footer</Text></VStack>"#,
+    )
+    .expect("invalid document");
+    let list = doc.get_by_id("list").expect("missing list");
+    let footer = doc.get_by_id("footer").expect("missing footer");
+
+    // detached nodes may be re-attached, so they stay identified
+    doc.detach(footer);
+    assert_eq!(doc.get_by_id("footer"), Some(footer));
+
+    doc.delete(list);
+    assert_eq!(doc.get_by_id("list"), None);
+    assert_eq!(doc.get_by_id("row-1"), None);
+    assert_eq!(doc.get_by_id("footer"), Some(footer));
+}
+
+#[test]
+fn dom_phx_values() {
+    let doc = Document::parse(
+        r#"<Button phx-click="delete" phx-value-id="songs-1" phx-value-kind="song" class="row">x</Button>"#,
+    )
+    .expect("invalid document");
+    let button = doc.first_child(doc.root()).expect("missing button");
+
+    let values = doc.phx_values(button);
+    assert_eq!(values.len(), 2);
+    assert_eq!(values["id"], "songs-1");
+    assert_eq!(values["kind"], "song");
+
+    let leaf = doc.first_child(button).expect("missing leaf");
+    assert!(doc.phx_values(leaf).is_empty());
+}
+
+#[test]
+fn dom_ffi_render_node_options() {
+    let doc = ffi::Document::parse(
+        r#"<VStack><HStack id="row"><Text>Hello</Text><Image name="star" /></HStack></VStack>"#
+            .to_owned(),
+    )
+    .expect("invalid document");
+    let stack = doc.children(doc.root())[0].clone();
+    let row = doc.children(stack)[0].clone();
+
+    assert_eq!(
+        doc.render_node(row.clone(), PrintOptions::Minified),
+        r#"<HStack id="row"><Text>Hello</Text><Image name="star" /></HStack>"#
+    );
+    assert_eq!(
+        doc.render_node(row.clone(), PrintOptions::Pretty),
+        "<HStack id=\"row\">\n    <Text>\n        Hello\n    </Text>\n    <Image name=\"star\" />\n</HStack>"
+    );
+
+    let node = doc.get_node(row);
+    assert_eq!(
+        node.display_with_options(PrintOptions::Pretty),
+        node.display()
+    );
+}
+
+#[derive(Default)]
+struct ChangeRecorder {
+    changes: std::sync::Mutex<Vec<(NodeRef, NodeData)>>,
+}
+
+impl DocumentChangeHandler for ChangeRecorder {
+    fn handle_document_change(
+        &self,
+        change_type: ChangeType,
+        node_ref: std::sync::Arc<NodeRef>,
+        node_data: NodeData,
+        _parent: Option<std::sync::Arc<NodeRef>>,
+    ) {
+        assert!(matches!(change_type, ChangeType::Change));
+        self.changes
+            .lock()
+            .expect("lock poisoned")
+            .push((*node_ref, node_data));
+    }
+
+    fn handle_channel_status(&self, _channel_status: LiveChannelStatus) -> ControlFlow {
+        ControlFlow::ContinueListening
+    }
+}
+
+#[test]
+fn dom_set_attribute_notifying() {
+    let mut doc =
+        Document::parse(r#"<Toggle id="wifi" isOn="false" />"#).expect("invalid document");
+    let toggle = doc.get_by_id("wifi").expect("missing toggle");
+
+    let recorder = std::sync::Arc::new(ChangeRecorder::default());
+    doc.event_callback = Some(recorder.clone());
+
+    assert!(doc.set_attribute_notifying(toggle, "isOn", "true".to_string()));
+    assert!(!doc.set_attribute_notifying(doc.root(), "isOn", "true".to_string()));
+
+    let changes = recorder.changes.lock().expect("lock poisoned");
+    assert_eq!(changes.len(), 1);
+    let (node, data) = &changes[0];
+    assert_eq!(*node, toggle);
+    assert_eq!(data, doc.get(toggle));
+    assert_eq!(
+        doc.get_attribute_by_name(toggle, "isOn")
+            .and_then(|attr| attr.value),
+        Some("true".to_string())
+    );
+}
+
+#[test]
+fn dom_change_handler_set_and_cleared() {
+    let fragment = r#"{"0": "off", "s": ["<Toggle id=\"t\" state=\"", "\" />"]}"#;
+    let recorder = std::sync::Arc::new(ChangeRecorder::default());
+
+    let mut doc = Document::parse_fragment_json(fragment.to_owned()).expect("invalid fragment");
+    doc.set_change_handler(recorder.clone());
+    let doc = ffi::Document::from(doc);
+    doc.merge_fragment_json(r#"{"0": "on"}"#)
+        .expect("merge failed");
+    assert_eq!(recorder.changes.lock().expect("lock poisoned").len(), 1);
+
+    let mut doc = Document::parse_fragment_json(fragment.to_owned()).expect("invalid fragment");
+    doc.set_change_handler(recorder.clone());
+    doc.clear_change_handler();
+    assert!(doc.get_event_callback().is_none());
+    let doc = ffi::Document::from(doc);
+    doc.merge_fragment_json(r#"{"0": "on"}"#)
+        .expect("merge failed");
+    assert_eq!(recorder.changes.lock().expect("lock poisoned").len(), 1);
+}
+
+#[test]
+fn dom_validate_tags() {
+    let allowed: std::collections::HashSet<ElementName> = ["VStack", "Text", "Button"]
+        .into_iter()
+        .map(ElementName::from)
+        .collect();
+
+    let doc = Document::parse(r#"<VStack><Text>Hi</Text><Button>Go</Button></VStack>"#)
+        .expect("invalid document");
+    assert_eq!(doc.validate_tags(&allowed), Ok(()));
+
+    let doc = Document::parse(
+        r#"<VStack><Text>Hi</Text><WebView url="https://example.com" /><svg:path /></VStack>"#,
+    )
+    .expect("invalid document");
+    let stack = doc.first_child(doc.root()).expect("missing stack");
+    assert_eq!(
+        doc.validate_tags(&allowed),
+        Err(vec![
+            doc.nth_child(stack, 1).expect("missing web view"),
+            doc.nth_child(stack, 2).expect("missing path"),
+        ])
+    );
+}
+
+#[test]
+fn dom_node_counts() {
+    use petgraph::visit::NodeCount;
+
+    let mut doc =
+        Document::parse(r#"<VStack><Text>Hello</Text><Text>World</Text><Spacer /></VStack>"#)
+            .expect("invalid document");
+
+    // root, the stack, its three children and two text nodes
+    assert_eq!(doc.attached_node_count(), 7);
+    assert_eq!(doc.element_count(), 4);
+    assert_eq!(doc.leaf_count(), 2);
+    assert_eq!(doc.node_count(), 7);
+
+    let stack = doc.first_child(doc.root()).expect("missing stack");
+    let text = doc.first_child(stack).expect("missing text");
+    doc.detach(text);
+
+    assert_eq!(doc.attached_node_count(), 5);
+    assert_eq!(doc.element_count(), 3);
+    assert_eq!(doc.leaf_count(), 1);
+    // detached nodes are still allocated
+    assert_eq!(doc.node_count(), 7);
+}
+
+#[test]
+fn dom_move_child() {
+    let mut doc = Document::parse(
+        r#"<VStack><Text id="a">A</Text><Text id="b">B</Text><Text id="c">C</Text></VStack>"#,
+    )
+    .expect("invalid document");
+    let stack = doc.first_child(doc.root()).expect("missing stack");
+    let [a, b, c] = ["a", "b", "c"].map(|id| doc.get_by_id(id).expect("missing text"));
+
+    doc.move_child(b, 0);
+    assert_eq!(doc.children(stack), &[b, a, c]);
+
+    doc.move_child(a, 2);
+    assert_eq!(doc.children(stack), &[b, c, a]);
+
+    // the moved node keeps its parent and subtree
+    assert_eq!(doc.parent(b), Some(stack));
+    assert_eq!(
+        doc.get(doc.first_child(b).expect("missing text")),
+        &NodeData::Leaf { value: "B".into() }
+    );
+}
+
+#[test]
+#[should_panic]
+fn dom_move_child_out_of_bounds() {
+    let mut doc = Document::parse(r#"<VStack><Text>A</Text><Text>B</Text></VStack>"#)
+        .expect("invalid document");
+    let stack = doc.first_child(doc.root()).expect("missing stack");
+    let a = doc.first_child(stack).expect("missing text");
+
+    doc.move_child(a, 2);
+}
+
+#[test]
+fn dom_failed_merge_keeps_template() {
+    let fragment = r#"{"0": "off", "s": ["<Toggle id=\"t\" state=\"", "\" />"]}"#;
+    let mut doc = Document::parse_fragment_json(fragment.to_owned()).expect("invalid fragment");
+    let template = doc.fragment_template.clone();
+
+    // the fragment merges before the update of the unknown component fails
+    doc.merge_fragment_json(serde_json::json!({"0": "on", "c": {"1": {"0": "x"}}}))
+        .expect_err("merged an update of an unknown component");
+    assert_eq!(doc.fragment_template, template);
+
+    let results = doc
+        .merge_fragment_json(serde_json::json!({}))
+        .expect("merge failed");
+    assert!(results.is_empty());
+    assert!(doc.to_string().contains(r#"state="off""#));
+}
+
+#[test]
+fn dom_replace_fragment_patches_document() {
+    let fragment = r#"{"0": "off", "s": ["<Toggle id=\"t\" state=\"", "\" />"]}"#;
+    let mut doc = Document::parse_fragment_json(fragment.to_owned()).expect("invalid fragment");
+    doc.set_entity_encoding(EntityEncoding::Markup);
+
+    let rendered: RootDiff = serde_json::from_value(
+        serde_json::json!({"0": "on", "s": ["<Toggle id=\"t\" state=\"", "\" />"]}),
+    )
+    .expect("invalid fragment");
+    let results = doc.replace_fragment(rendered).expect("replace failed");
+
+    assert!(!results.is_empty());
+    assert!(doc.to_string().contains(r#"state="on""#));
+    assert_eq!(doc.entity_encoding(), EntityEncoding::Markup);
+}
+
+#[test]
+fn dom_render_hash() {
+    let fragment = r#"{"0": "off", "s": ["<Toggle id=\"t\" state=\"", "\" />"]}"#;
+    let mut doc = Document::parse_fragment_json(fragment.to_owned()).expect("invalid fragment");
+    let hash = doc.render_hash();
+
+    doc.merge_fragment_json(serde_json::json!({"0": "off"}))
+        .expect("merge failed");
+    assert_eq!(doc.render_hash(), hash);
+
+    doc.merge_fragment_json(serde_json::json!({"0": "on"}))
+        .expect("merge failed");
+    assert_ne!(doc.render_hash(), hash);
+
+    let same = Document::parse(doc.to_string()).expect("invalid document");
+    assert_eq!(same.render_hash(), doc.render_hash());
+}
+
+#[test]
+fn dom_serialize_subtree() {
+    let doc = Document::parse(
+        r##"<VStack><Group id="icons"><svg:use xlink:href="#star" class="icon" /><Text>Star</Text></Group></VStack>"##,
+    )
+    .expect("invalid document");
+    let group = doc.get_by_id("icons").expect("missing group");
+
+    let bytes = doc.serialize_subtree(group);
+    let subtree = Document::deserialize_subtree(&bytes).expect("invalid subtree");
+
+    let copy = subtree.get_by_id("icons").expect("missing id");
+    assert_eq!(subtree.first_child(subtree.root()), Some(copy));
+    assert_eq!(subtree.get(copy), doc.get(group));
+
+    let icon = doc.first_child(group).expect("missing icon");
+    let icon_copy = subtree.first_child(copy).expect("missing icon");
+    assert_eq!(subtree.get(icon_copy), doc.get(icon));
+    assert_eq!(
+        subtree.attributes(icon_copy)[0].name,
+        AttributeName {
+            namespace: Some("xlink".into()),
+            name: "href".into(),
+        }
+    );
+
+    let mut printed = String::new();
+    subtree
+        .print_node(copy, &mut printed, PrintOptions::Minified)
+        .expect("print failed");
+    let mut expected = String::new();
+    doc.print_node(group, &mut expected, PrintOptions::Minified)
+        .expect("print failed");
+    assert_eq!(printed, expected);
+
+    // the whole document round trips as well
+    let whole =
+        Document::deserialize_subtree(&doc.serialize_subtree(doc.root())).expect("invalid subtree");
+    assert_eq!(whole.to_string(), doc.to_string());
+
+    assert!(Document::deserialize_subtree(&bytes[..bytes.len() - 1]).is_err());
+}
+
+#[test]
+fn dom_serialize_empty_subtree() {
+    let empty = Document::empty();
+    let copy = Document::deserialize_subtree(&empty.serialize_subtree(empty.root()))
+        .expect("invalid subtree");
+    assert_eq!(copy.children(copy.root()).len(), 0);
+    assert_eq!(copy.to_string(), empty.to_string());
+
+    let doc = Document::parse(r#"<VStack><Spacer id="gap" /></VStack>"#).expect("invalid document");
+    let gap = doc.get_by_id("gap").expect("missing spacer");
+    let copy = Document::deserialize_subtree(&doc.serialize_subtree(gap)).expect("invalid subtree");
+    let gap_copy = copy.get_by_id("gap").expect("missing id");
+    assert_eq!(copy.children(copy.root()), &[gap_copy]);
+    assert!(copy.children(gap_copy).is_empty());
+}
+
+#[test]
+fn dom_outer_and_inner_markup() {
+    let doc = Document::parse(
+        r#"<VStack id="stack"><Text class="title">Hello</Text>World<Image name="star" /></VStack>"#,
+    )
+    .expect("invalid document");
+    let stack = doc.get_by_id("stack").expect("missing stack");
+
+    assert_eq!(
+        doc.outer_markup(stack, PrintOptions::Minified),
+        r#"<VStack id="stack"><Text class="title">Hello</Text>World<Image name="star" /></VStack>"#
+    );
+
+    for options in [PrintOptions::Minified, PrintOptions::Pretty] {
+        let children: Vec<String> = doc
+            .children(stack)
+            .iter()
+            .map(|child| doc.outer_markup(*child, options))
+            .collect();
+        let separator = if options.pretty() { "\n" } else { "" };
+        assert_eq!(doc.inner_markup(stack, options), children.join(separator));
+    }
+    assert_eq!(
+        doc.inner_markup(stack, PrintOptions::Minified),
+        r#"<Text class="title">Hello</Text>World<Image name="star" />"#
+    );
+
+    let image = doc.last_child(stack).expect("missing image");
+    assert_eq!(doc.inner_markup(image, PrintOptions::Pretty), "");
+}
+
+#[test]
+fn dom_class_tokens() {
+    let mut doc =
+        Document::parse(r#"<VStack><Text class="bold  title bold">Hi</Text><Spacer /></VStack>"#)
+            .expect("invalid document");
+    let stack = doc.first_child(doc.root()).expect("missing stack");
+    let text = doc.first_child(stack).expect("missing text");
+    let spacer = doc.last_child(stack).expect("missing spacer");
+
+    assert_eq!(doc.tokens(text, "class"), ["bold", "title"]);
+    assert!(doc.has_class(text, "title"));
+    assert!(!doc.has_class(text, "red"));
+
+    // adding dedupes the existing value
+    assert!(doc.add_class(text, "red"));
+    assert!(!doc.add_class(text, "red"));
+    assert!(!doc.add_class(text, "two words"));
+    let class = |doc: &Document, node| {
+        doc.get_attribute_by_name(node, "class")
+            .and_then(|attr| attr.value)
+    };
+    assert_eq!(class(&doc, text).as_deref(), Some("bold title red"));
+
+    assert!(doc.remove_class(text, "title"));
+    assert!(!doc.remove_class(text, "title"));
+    assert_eq!(class(&doc, text).as_deref(), Some("bold red"));
+
+    assert!(doc.add_class(spacer, "flexible"));
+    assert_eq!(class(&doc, spacer).as_deref(), Some("flexible"));
+    assert!(doc.remove_class(spacer, "flexible"));
+    assert_eq!(class(&doc, spacer).as_deref(), Some(""));
+
+    // any attribute can hold tokens
+    assert!(doc.add_token(spacer, "modifiers", "padding"));
+    assert!(doc.has_token(spacer, "modifiers", "padding"));
+}
+
+#[test]
+fn dom_print_platform() {
+    let mut doc = Document::parse(
+        r#"<Text swiftui:font="title" jetpack:style="headline" class="bold">Hello</Text>"#,
+    )
+    .expect("invalid document");
+    let text = doc.children(doc.root())[0];
+    let minified = |doc: &Document| doc.outer_markup(text, PrintOptions::Minified);
+
+    // every attribute is printed unless a platform is set
+    assert_eq!(
+        minified(&doc),
+        r#"<Text swiftui:font="title" jetpack:style="headline" class="bold">Hello</Text>"#
+    );
+
+    doc.set_print_platform(Some("swiftui".into()));
+    assert_eq!(
+        minified(&doc),
+        r#"<Text swiftui:font="title" class="bold">Hello</Text>"#
+    );
+
+    doc.set_print_platform(Some("jetpack".into()));
+    assert_eq!(
+        minified(&doc),
+        r#"<Text jetpack:style="headline" class="bold">Hello</Text>"#
+    );
+
+    // the attributes are kept in the document
+    assert!(doc.get_attribute_by_name(text, "swiftui:font").is_some());
+}
+
+#[test]
+fn dom_element_name_constants() {
+    use liveview_native_core::{element_names, symbols, InternedString};
+
+    let doc = Document::parse(r#"<VStack><Text>Hello</Text><Button>Tap</Button></VStack>"#)
+        .expect("invalid document");
+    let stack = doc.children(doc.root())[0];
+    let text = doc.children(stack)[0];
+
+    assert_eq!(
+        doc.select(Selector::Tag(element_names::Text.into()))
+            .collect::<Vec<_>>(),
+        [text]
+    );
+    assert_eq!(
+        doc.select(Selector::Tag(element_names::VStack.into()))
+            .collect::<Vec<_>>(),
+        [stack]
+    );
+
+    // the constants are what interning the tag yields
+    assert_eq!(
+        InternedString::from("Button"),
+        element_names::Button.as_str()
+    );
+    // and are distinct from the html tag of the same name
+    assert_ne!(element_names::Button, symbols::Button);
+    assert_eq!(symbols::Button.as_str(), "button");
+}
+
+#[test]
+fn dom_component_cid_for() {
+    let initial = r#"{
+        "0": 1,
+        "1": 2,
+        "c": {
+            "1": {"0": "one", "s": ["<Text id=\"first\">", "</Text>"]},
+            "2": {"0": 3, "s": ["<HStack id=\"second\"><Text id=\"label\">two</Text>", "</HStack>"]},
+            "3": {"0": "three", "s": ["<Image id=\"nested\" name=\"", "\" />"]}
+        },
+        "s": ["<VStack id=\"root\">", "", "</VStack>"]
+    }"#;
+    let mut doc = Document::parse_fragment_json(initial.to_owned()).expect("invalid fragment");
+
+    let cid = |doc: &Document, id: &str| doc.component_cid_for(doc.get_by_id(id).expect(id));
+    assert_eq!(cid(&doc, "root"), None);
+    assert_eq!(cid(&doc, "first"), Some(1));
+    assert_eq!(cid(&doc, "second"), Some(2));
+    assert_eq!(cid(&doc, "label"), Some(2));
+    assert_eq!(cid(&doc, "nested"), Some(3));
+
+    let text = doc
+        .first_child(doc.get_by_id("first").expect("first"))
+        .expect("missing text");
+    assert_eq!(doc.component_cid_for(text), Some(1));
+
+    // the markers are not part of the document
+    assert!(!doc.to_string().contains("data-phx-component"));
+
+    // the components swap places
+    doc.merge_fragment_json(serde_json::json!({"0": 2, "1": 1}))
+        .expect("merge failed");
+    assert_eq!(cid(&doc, "first"), Some(1));
+    assert_eq!(cid(&doc, "second"), Some(2));
+    assert_eq!(cid(&doc, "nested"), Some(3));
+    assert!(!doc.to_string().contains("data-phx-component"));
+}
+
+#[test]
+fn dom_component_cid_for_keeps_app_attributes() {
+    let initial = r#"{
+        "0": 1,
+        "1": 2,
+        "c": {
+            "1": {"0": "text only", "s": ["<!-- no root -->", ""]},
+            "2": {"0": "two", "s": ["<!-- root --><Text id=\"second\" data-phx-component=\"app\">", "</Text>"]}
+        },
+        "s": ["<VStack id=\"root\" data-phx-component=\"7\"><Text id=\"plain\">", "</Text>", "</VStack>"]
+    }"#;
+    let doc = Document::parse_fragment_json(initial.to_owned()).expect("invalid fragment");
+
+    let cid = |id: &str| doc.component_cid_for(doc.get_by_id(id).expect(id));
+    // attributes written by the app are neither markers nor removed
+    assert_eq!(cid("root"), None);
+    assert_eq!(cid("plain"), None);
+    assert_eq!(cid("second"), Some(2));
+    assert_eq!(
+        doc.to_string(),
+        r#"<VStack id="root" data-phx-component="7">
+    <Text id="plain">
+        text only
+    </Text>
+    <Text id="second" data-phx-component="app">
+        two
+    </Text>
+</VStack>"#
+    );
+}
+
+#[test]
+fn dom_event_bindings() {
+    let thermostat = r##"{
+        "0": "cooling",
+        "1": "cooling",
+        "2": "07:15:03 PM",
+        "s": [
+            "<div class=\"thermostat\">\n  <div class=\"bar ",
+            "\">\n    <a href=\"#\" phx-click=\"toggle-mode\">",
+            "</a>\n    <span>",
+            "</span>\n  </div>\n</div>\n"
+        ]
+    }"##;
+    let doc = Document::parse_fragment_json(thermostat.to_owned()).expect("invalid fragment");
+
+    let link = doc
+        .select(Selector::Tag(ElementName::from("a")))
+        .next()
+        .expect("missing link");
+    assert_eq!(
+        doc.event_bindings(),
+        vec![(link, PhxEvent::Click, "toggle-mode".to_owned())]
+    );
+
+    let doc = Document::parse(
+        r#"<Form phx-change="validate" phx-submit="save"><TextField phx-keyup="search" phx-debounce="300" /></Form>"#,
+    )
+    .expect("parse failed");
+    let events: Vec<_> = doc
+        .event_bindings()
+        .into_iter()
+        .map(|(_, event, value)| (event, value))
+        .collect();
+    assert_eq!(
+        events,
+        vec![
+            (PhxEvent::Change, "validate".to_owned()),
+            (PhxEvent::Submit, "save".to_owned()),
+            (PhxEvent::KeyUp, "search".to_owned()),
+        ]
+    );
+    assert_eq!(PhxEvent::from_attribute("phx-keyup"), Some(PhxEvent::KeyUp));
+    assert_eq!(PhxEvent::from_attribute("phx-debounce"), None);
+}
+
+#[test]
+fn dom_merge_iter_matches_merge() {
+    let initial = r#"{
+        "0": "Counter",
+        "1": {"d": [["1", "a"], ["2", "b"]], "s": ["<Text id=\"row-", "\">", "</Text>"]},
+        "s": ["<VStack><Text>", "</Text><List>", "</List></VStack>"]
+    }"#;
+    let diff =
+        serde_json::json!({"0": "Counter!", "1": {"d": [["1", "z"], ["3", "c"], ["4", "d"]]}});
+
+    let mut expected = Document::parse_fragment_json(initial.to_owned()).expect("invalid fragment");
+    let expected_results = expected
+        .merge_fragment_json(diff.clone())
+        .expect("merge failed");
+
+    let mut lazy = Document::parse_fragment_json(initial.to_owned()).expect("invalid fragment");
+    let mut results = vec![];
+    for result in lazy
+        .merge_fragment_json_iter(diff.clone())
+        .expect("merge failed")
+    {
+        results.push(result);
+    }
+    assert!(!results.is_empty());
+    assert_eq!(format!("{results:?}"), format!("{expected_results:?}"));
+    assert_eq!(lazy.to_string(), expected.to_string());
+
+    // dropping the iterator early still applies the remaining patches
+    let mut dropped = Document::parse_fragment_json(initial.to_owned()).expect("invalid fragment");
+    let mut patches = dropped
+        .merge_fragment_json_iter(diff)
+        .expect("merge failed");
+    assert!(patches.next().is_some());
+    drop(patches);
+    assert_eq!(dropped.to_string(), expected.to_string());
+}
+
+#[test]
+fn dom_ffi_node_self_closing() {
+    let doc = ffi::Document::parse(r#"<VStack><Icon name="star"/><Group></Group></VStack>"#.into())
+        .expect("parse failed");
+    let stack = doc.children(doc.root())[0].clone();
+    let [icon, group] = <[_; 2]>::try_from(doc.children(stack)).expect("expected two children");
+
+    let icon = doc.get_node(icon);
+    assert!(icon.self_closing());
+    assert!(matches!(
+        icon.data(),
+        NodeData::NodeElement { element } if element.self_closing
+    ));
+    assert!(!doc.get_node(group).self_closing());
+
+    // self-closing does not change the document
+    let expanded = Document::parse(r#"<VStack><Icon name="star"></Icon><Group /></VStack>"#)
+        .expect("parse failed");
+    let doc = Document::parse(r#"<VStack><Icon name="star"/><Group></Group></VStack>"#)
+        .expect("parse failed");
+    assert_eq!(doc.render_hash(), expanded.render_hash());
+}
+
+#[test]
+fn dom_normalize_text() {
+    let mut spaced =
+        Document::parse("<VStack><Text>Hello\n        world</Text><Text>  !</Text></VStack>")
+            .expect("parse failed");
+    let stack = spaced.children(spaced.root())[0];
+    // leaves built outside the parser are not trimmed
+    let blank = spaced.push_node(NodeData::Leaf {
+        value: " \n\t ".into(),
+    });
+    spaced.append_child(stack, blank);
+
+    let mut compact = Document::parse("<VStack><Text>Hello world</Text><Text>!</Text></VStack>")
+        .expect("parse failed");
+    assert_ne!(spaced.render_hash(), compact.render_hash());
+
+    spaced.normalize_text();
+    compact.normalize_text();
+    assert_eq!(spaced.render_hash(), compact.render_hash());
+    assert_eq!(spaced.to_string(), compact.to_string());
+    assert_eq!(spaced.children(stack).len(), 2);
+    assert!(spaced.parent(blank).is_none());
+}
+
+#[test]
+fn dom_merge_preserves_local_edits() {
+    let fragment = r#"{
+        "0": "Alice",
+        "1": "alice@example.com",
+        "2": "0",
+        "s": [
+            "<VStack><TextField id=\"name\" data-local-edit value=\"",
+            "\" /><TextField id=\"email\" data-local-edit value=\"",
+            "\" /><Text id=\"count\">",
+            "</Text></VStack>"
+        ]
+    }"#;
+    let value = |doc: &Document, id: &str| {
+        let node = doc.get_by_id(id).expect("missing node");
+        doc.get_attribute_by_name(node, "value")
+            .and_then(|attr| attr.value)
+    };
+    let edit = |doc: &mut Document| {
+        let name = doc.get_by_id("name").expect("missing name");
+        let email = doc.get_by_id("email").expect("missing email");
+        doc.set_attribute(name, "value", "Alicia".to_owned());
+        doc.set_attribute(email, "value", "alicia@example.com".to_owned());
+    };
+    let diff = serde_json::json!({"1": "alice@example.org", "2": "1"});
+
+    let mut doc = Document::parse_fragment_json(fragment.to_owned()).expect("invalid fragment");
+    doc.set_merge_options(MergeOptions {
+        preserve_local_edits: true,
+        ..Default::default()
+    });
+    edit(&mut doc);
+    doc.merge_fragment_json(diff.clone()).expect("merge failed");
+
+    // the server did not change the name, so the local edit is kept
+    assert_eq!(value(&doc, "name"), Some("Alicia".to_owned()));
+    // both sides changed the email, the server wins
+    assert_eq!(value(&doc, "email"), Some("alice@example.org".to_owned()));
+    let count = doc.get_by_id("count").expect("missing count");
+    let text = doc.first_child(count).expect("missing text");
+    assert_eq!(
+        doc.get(text),
+        &NodeData::Leaf {
+            value: "1".to_owned()
+        }
+    );
+    let name = doc.get_by_id("name").expect("missing name");
+    assert!(doc.get_attribute_by_name(name, "data-local-edit").is_some());
+
+    // by default the server render replaces local edits
+    let mut doc = Document::parse_fragment_json(fragment.to_owned()).expect("invalid fragment");
+    edit(&mut doc);
+    doc.merge_fragment_json(diff).expect("merge failed");
+    assert_eq!(value(&doc, "name"), Some("Alice".to_owned()));
+    assert_eq!(value(&doc, "email"), Some("alice@example.org".to_owned()));
+}
+
+#[test]
+fn dom_gc_frees_unreachable_nodes() {
+    let mut doc = Document::parse(
+        r#"<List><Text id="a">One</Text><Text id="b">Two</Text><Text id="c">Three</Text><Text id="d">Four</Text></List>"#,
+    )
+    .expect("invalid document");
+    let expected =
+        Document::parse(r#"<List><Text id="d">Four</Text></List>"#).expect("invalid document");
+    let list = doc.first_child(doc.root()).expect("missing list");
+    let [a, b, c, d] = ["a", "b", "c", "d"].map(|id| doc.get_by_id(id).expect("missing node"));
+
+    // nothing is unreachable yet
+    assert_eq!(doc.gc(), 0);
+
+    doc.detach(a);
+    doc.detach(b);
+    doc.delete(c);
+    assert_eq!(doc.gc(), 6);
+    assert_eq!(doc.gc(), 0);
+    assert_eq!(doc.to_string(), expected.to_string());
+
+    // the freed nodes are still valid references
+    assert_eq!(
+        doc.get(a),
+        &NodeData::Leaf {
+            value: String::new()
+        }
+    );
+    assert!(doc.children(a).is_empty());
+    assert_eq!(doc.parent(a), None);
+    assert_eq!(doc.get_by_id("b"), None);
+    assert_eq!(doc.get_by_id("d"), Some(d));
+
+    let text = doc.push_node(NodeData::Leaf {
+        value: "Five".to_owned(),
+    });
+    doc.append_child(list, text);
+    assert_eq!(doc.children(list), &[d, text]);
+}
+
+#[test]
+fn dom_all_attributes() {
+    let doc =
+        Document::parse(include_str!("fixtures/select-element/from.html")).expect("parse failed");
+
+    let names: std::collections::BTreeSet<_> = doc
+        .all_attributes()
+        .map(|(_, attr)| attr.name.to_string())
+        .collect();
+    assert_eq!(
+        names,
+        std::collections::BTreeSet::from(["name", "selected", "value"].map(String::from))
+    );
+    assert_eq!(doc.all_attributes().count(), 5);
+
+    let (selected, _) = doc
+        .all_attributes()
+        .find(|(_, attr)| attr.name.name == "selected")
+        .expect("missing selected");
+    assert_eq!(
+        doc.get_attribute_by_name(selected, "value")
+            .and_then(|attr| attr.value),
+        Some("value2".to_owned())
+    );
+}
+
 /*
  * TODO: https://github.com/liveview-native/liveview-native-core/issues/58
 #[test]
@@ -1,6 +1,10 @@
+use std::borrow::Cow;
+
 use liveview_native_core::{
-    dom::{AttributeName, NodeData},
-    parser, InternedString,
+    dom::{AttributeName, Document, EntityEncoding, MultipleRootsError, NodeData, Selector},
+    is_interned,
+    parser::{self, ParseError, ParseOptions},
+    InternedString,
 };
 
 #[test]
@@ -67,3 +71,189 @@ fn parser_preserve_upcase() {
     let expected_name: InternedString = "Component".into();
     assert_eq!(element.name, expected_name);
 }
+
+#[test]
+fn parser_normalize_tag() {
+    fn lowercase(tag: &str) -> Cow<'_, str> {
+        Cow::Owned(tag.to_lowercase())
+    }
+
+    let options = ParseOptions {
+        normalize_tag: Some(lowercase),
+        ..Default::default()
+    };
+    let document =
+        Document::parse_with_options("<VStack><Text>Hello</TEXT><IMAGE /></vstack>", options)
+            .expect("failed to parse");
+
+    assert_eq!(
+        document.to_string(),
+        "<vstack>\n    <text>\n        Hello\n    </text>\n    <image />\n</vstack>"
+    );
+}
+
+#[test]
+fn parser_with_capacity() {
+    let input = include_str!("fixtures/large/from.html");
+    let expected = Document::parse(input).expect("failed to parse");
+
+    // capacity is only a hint, so both under- and over-estimates must parse identically
+    for cap in [1, 64, 4096] {
+        let document = Document::parse_with_capacity(input, cap).expect("failed to parse");
+        assert_eq!(document.to_string(), expected.to_string());
+        assert_eq!(
+            document.select(Selector::All).count(),
+            expected.select(Selector::All).count()
+        );
+    }
+}
+
+#[test]
+fn parser_custom_id_attribute() {
+    let input =
+        r#"<VStack nativeID="stack"><Text id="title" nativeID="greeting">Hello</Text></VStack>"#;
+
+    let options = ParseOptions {
+        id_attribute: "nativeID".into(),
+        ..Default::default()
+    };
+    let document = Document::parse_with_options(input, options).expect("failed to parse");
+    let stack = document.get_by_id("stack").expect("missing stack");
+    let text = document.get_by_id("greeting").expect("missing text");
+    assert_eq!(document.parent(text), Some(stack));
+    assert_eq!(document.get_by_id("title"), None);
+
+    // the default still registers `id`
+    let document = Document::parse(input).expect("failed to parse");
+    assert!(document.get_by_id("title").is_some());
+    assert_eq!(document.get_by_id("greeting"), None);
+}
+
+#[test]
+fn parser_require_single_root() {
+    let options = ParseOptions {
+        require_single_root: true,
+        ..Default::default()
+    };
+
+    let input = "<VStack><Text>One</Text></VStack>\n<Text>Two</Text>";
+    let document = Document::parse(input).expect("failed to parse");
+    let err = document.single_root().expect_err("expected multiple roots");
+    assert_eq!(err, MultipleRootsError { count: 2 });
+    assert!(matches!(
+        Document::parse_with_options(input, options.clone()),
+        Err(ParseError::MultipleRoots(MultipleRootsError { count: 2 }))
+    ));
+
+    let document = Document::parse("").expect("failed to parse");
+    assert_eq!(document.single_root(), Err(MultipleRootsError { count: 0 }));
+
+    // surrounding whitespace does not count as a root
+    let input = "\n  <VStack><Text>One</Text></VStack>\n";
+    let document = Document::parse_with_options(input, options).expect("failed to parse");
+    let root = document.single_root().expect("expected a single root");
+    assert_eq!(document.parent(root), Some(document.root()));
+    assert!(matches!(document.get(root), NodeData::NodeElement { .. }));
+}
+
+#[test]
+fn parser_from_reader() {
+    let input = include_bytes!("fixtures/large/from.html");
+    let expected = Document::parse_bytes(input).expect("failed to parse");
+
+    let document =
+        Document::parse_reader(std::io::Cursor::new(input.to_vec())).expect("failed to parse");
+    assert_eq!(document.to_string(), expected.to_string());
+}
+
+#[test]
+fn parser_retains_doctype() {
+    let document = Document::parse("<!DOCTYPE swiftui><VStack><Text>Hi</Text></VStack>")
+        .expect("failed to parse");
+    assert_eq!(document.doctype(), Some(InternedString::from("swiftui")));
+    assert_eq!(
+        document.to_string(),
+        "<VStack>\n    <Text>\n        Hi\n    </Text>\n</VStack>"
+    );
+
+    let document = Document::parse("<VStack></VStack>").expect("failed to parse");
+    assert_eq!(document.doctype(), None);
+}
+
+#[test]
+fn parser_character_references() {
+    let input = r#"<Text title="&quot;fish&quot; &amp; chips">Fish &amp; chips &#35;1 &#x23;2 &bogus;</Text>"#;
+    let mut document = parser::parse(input).expect("invalid document");
+    let text = document.children(document.root())[0];
+    let leaf = document.children(text)[0];
+
+    assert_eq!(
+        document.get(leaf),
+        &NodeData::Leaf {
+            value: "Fish & chips #1 #2 &bogus;".to_owned()
+        }
+    );
+    assert_eq!(
+        document
+            .get_attribute_by_name(text, "title")
+            .and_then(|attr| attr.value),
+        Some(r#""fish" & chips"#.to_owned())
+    );
+
+    // by default values are printed as they are stored
+    assert_eq!(
+        document.to_string(),
+        "<Text title=\"\"fish\" & chips\">\n    Fish & chips #1 #2 &bogus;\n</Text>"
+    );
+
+    document.set_entity_encoding(EntityEncoding::Markup);
+    let printed = document.to_string();
+    assert_eq!(
+        printed,
+        "<Text title=\"&quot;fish&quot; &amp; chips\">\n    Fish &amp; chips #1 #2 &amp;bogus;\n</Text>"
+    );
+
+    let reparsed = parser::parse(printed.as_str()).expect("invalid document");
+    let reparsed_text = reparsed.children(reparsed.root())[0];
+    assert_eq!(
+        reparsed.get(reparsed.children(reparsed_text)[0]),
+        document.get(leaf)
+    );
+    assert_eq!(reparsed.get(reparsed_text), document.get(text));
+}
+
+#[test]
+fn parser_error_position() {
+    let input = "<VStack>\n  <Text =oops>Hello</Text>\n</VStack>";
+    let err = parser::parse(input).expect_err("malformed template parsed");
+
+    // points at the `=` on the second line
+    assert_eq!(err.position(), Some((2, 9)));
+    let ParseError::Tokenizer(tokenizer_error) = &err else {
+        panic!("expected a tokenizer error, got {err:?}");
+    };
+    assert_eq!(
+        tokenizer_error.error().as_str(),
+        "unexpected-equals-sign-before-attribute-name"
+    );
+    assert!(err.to_string().contains("2:9"));
+
+    // columns count characters rather than bytes
+    let input = "<Text>héllo</Text>\n<Text =oops />";
+    let err = parser::parse(input).expect_err("malformed template parsed");
+    assert_eq!(err.position(), Some((2, 7)));
+}
+
+#[test]
+fn parser_does_not_intern_attribute_names() {
+    let doc =
+        parser::parse(r#"<Text data-generated-4f9a2c="1">Hello</Text>"#).expect("parse failed");
+    let text = doc.children(doc.root())[0];
+    assert!(doc
+        .get_attribute_by_name(text, "data-generated-4f9a2c")
+        .is_some());
+
+    // one-off attribute names must not grow the global interner, which never frees strings
+    assert!(!is_interned("data-generated-4f9a2c"));
+    assert!(is_interned("Text"));
+}
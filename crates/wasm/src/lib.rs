@@ -1,9 +1,15 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 
-use liveview_native_core::diff::fragment::{FragmentMerge, Root, RootDiff};
+use liveview_native_core::{
+    diff::fragment::{FragmentMerge, Root, RootDiff},
+    dom::{Document, NodeData, NodeRef},
+};
 use serde::Serialize;
 use wasm_bindgen::prelude::*;
 
+#[cfg(test)]
+mod tests;
+
 #[wasm_bindgen]
 pub struct Rendered {
     inner: Root,
@@ -74,6 +80,7 @@ impl Rendered {
         log::debug!("RAW MERGE DIFF: {diff:#?}");
         let diff: RootDiff = serde_wasm_bindgen::from_value(diff)?;
         log::debug!("DIFF: {diff:#?}");
+        // merge a copy so a failed merge leaves the previous render intact
         self.inner = self.inner.clone().merge(diff)?;
         log::debug!("MERGED: {:#?}", self.inner);
 
@@ -141,6 +148,11 @@ impl Rendered {
             .expect("Failed to serialize");
         Ok(map)
     }
+    /// Renders the tree to a markup string, unlike `toString` which wraps it for the JS client
+    pub fn render(&self) -> Result<String, JsError> {
+        Ok(self.inner.render()?)
+    }
+
     #[wasm_bindgen(js_name = "toString")]
     pub fn to_string(&self) -> Result<JsValue, JsError> {
         let out = js_sys::Array::new();
@@ -166,3 +178,58 @@ impl Rendered {
         Ok(out)
     }
 }
+
+/// A JSON friendly representation of a parsed `Document`
+#[derive(serde::Serialize)]
+#[serde(untagged)]
+enum MarkupNode {
+    Element {
+        tag: String,
+        attributes: BTreeMap<String, Option<String>>,
+        children: Vec<MarkupNode>,
+    },
+    Text(String),
+}
+
+impl MarkupNode {
+    fn children(doc: &Document, node: NodeRef) -> Vec<Self> {
+        doc.children(node)
+            .iter()
+            .filter_map(|child| Self::new(doc, *child))
+            .collect()
+    }
+
+    fn new(doc: &Document, node: NodeRef) -> Option<Self> {
+        match doc.get(node) {
+            NodeData::Root => None,
+            NodeData::Leaf { value } => Some(Self::Text(value.clone())),
+            NodeData::NodeElement { element } => Some(Self::Element {
+                tag: element.name.to_string(),
+                attributes: element
+                    .attributes
+                    .iter()
+                    .map(|attr| (attr.name.to_string(), attr.value.clone()))
+                    .collect(),
+                children: Self::children(doc, node),
+            }),
+        }
+    }
+}
+
+/// Parses platform markup into a JSON tree of `{tag, attributes, children}` elements and text strings
+#[wasm_bindgen(js_name = "parseMarkup")]
+pub fn parse_markup(markup: &str) -> Result<JsValue, JsError> {
+    let doc = Document::parse(markup)?;
+    let serializer = serde_wasm_bindgen::Serializer::json_compatible();
+
+    Ok(MarkupNode::children(&doc, doc.root()).serialize(&serializer)?)
+}
+
+/// Returns `{symbols, stringBytes, arenaBytes}` describing the global string interner.
+/// Interned strings are never freed, so steady growth over a session points at a leak.
+#[wasm_bindgen(js_name = "internerStats")]
+pub fn interner_stats() -> Result<JsValue, JsError> {
+    let serializer = serde_wasm_bindgen::Serializer::json_compatible();
+
+    Ok(liveview_native_core::interner_stats().serialize(&serializer)?)
+}
@@ -0,0 +1,80 @@
+use wasm_bindgen_test::*;
+
+use super::*;
+
+fn js(json: &str) -> JsValue {
+    js_sys::JSON::parse(json).expect("invalid json")
+}
+
+fn stringify(value: &JsValue) -> String {
+    js_sys::JSON::stringify(value)
+        .expect("failed to stringify")
+        .into()
+}
+
+#[wasm_bindgen_test]
+fn render_without_wrapper() {
+    let mut rendered = Rendered::new(
+        0,
+        js(r#"{"0": "1", "s": ["<Text count=\"", "\">Count</Text>"]}"#),
+    )
+    .expect("invalid rendered");
+    assert_eq!(
+        rendered.render().expect("render failed"),
+        r#"<Text count="1">Count</Text>"#
+    );
+
+    rendered
+        .merge_diff(js(r#"{"0": "2"}"#))
+        .expect("merge failed");
+    assert_eq!(
+        rendered.render().expect("render failed"),
+        r#"<Text count="2">Count</Text>"#
+    );
+}
+
+#[wasm_bindgen_test]
+fn failed_merge_keeps_previous_render() {
+    let mut rendered = Rendered::new(
+        0,
+        js(r#"{"0": "1", "s": ["<Text count=\"", "\">Count</Text>"]}"#),
+    )
+    .expect("invalid rendered");
+
+    // the fragment merges before the update to the missing component fails
+    assert!(rendered
+        .merge_diff(js(r#"{"0": "2", "c": {"5": {"0": "missing"}}}"#))
+        .is_err());
+    assert_eq!(
+        rendered.render().expect("render failed"),
+        r#"<Text count="1">Count</Text>"#
+    );
+}
+
+#[wasm_bindgen_test]
+fn parse_markup_tree() {
+    let tree = parse_markup(r#"<VStack spacing="4"><Text>Hello</Text><Spacer /></VStack>"#)
+        .expect("parse failed");
+
+    assert_eq!(
+        stringify(&tree),
+        r#"[{"tag":"VStack","attributes":{"spacing":"4"},"children":[{"tag":"Text","attributes":{},"children":["Hello"]},{"tag":"Spacer","attributes":{},"children":[]}]}]"#
+    );
+}
+
+#[wasm_bindgen_test]
+fn interner_stats_grow_with_novel_tags() {
+    let symbols = |stats: &JsValue| {
+        js_sys::Reflect::get(stats, &"symbols".into())
+            .expect("missing symbols")
+            .as_f64()
+            .expect("symbols is not a number")
+    };
+
+    let before = interner_stats().expect("stats failed");
+    parse_markup("<InternerStatsProbe><InternerStatsProbeChild /></InternerStatsProbe>")
+        .expect("parse failed");
+    let after = interner_stats().expect("stats failed");
+
+    assert_eq!(symbols(&after), symbols(&before) + 2.0);
+}
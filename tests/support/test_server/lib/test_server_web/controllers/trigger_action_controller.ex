@@ -0,0 +1,8 @@
+defmodule TestServerWeb.TriggerActionController do
+  use TestServerWeb, :controller
+
+  # The target of the form submitted by `phx-trigger-action` in TriggerActionLive
+  def create(conn, %{"name" => name}) do
+    redirect(conn, to: ~p"/nav/#{name}")
+  end
+end
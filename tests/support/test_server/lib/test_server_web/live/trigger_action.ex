@@ -0,0 +1,65 @@
+defmodule TestServerWeb.TriggerActionLive do
+  use TestServerWeb, :live_view
+  use TestServerNative, :live_view
+
+  def mount(_params, _session, socket) do
+    {:ok, assign(socket, trigger_submit: false)}
+  end
+
+  def handle_event("save", _params, socket) do
+    {:noreply, assign(socket, trigger_submit: true)}
+  end
+
+  def render(assigns) do
+    ~H"""
+    <form
+      id="trigger"
+      action={~p"/trigger_action/submit"}
+      method="post"
+      phx-submit="save"
+      phx-trigger-action={@trigger_submit}
+    >
+      <input type="hidden" name="name" value="triggered" />
+      <button type="submit">Save</button>
+    </form>
+    """
+  end
+end
+
+defmodule TestServerWeb.TriggerActionLive.SwiftUI do
+  use TestServerNative, [:render_component, format: :swiftui]
+
+  def render(assigns, _interface) do
+    ~LVN"""
+    <LiveForm
+      id="trigger"
+      action="/trigger_action/submit"
+      method="post"
+      phx-submit="save"
+      phx-trigger-action={@trigger_submit}
+    >
+      <TextField name="name" value="triggered">Name</TextField>
+      <LiveSubmitButton>Save</LiveSubmitButton>
+    </LiveForm>
+    """
+  end
+end
+
+defmodule TestServerWeb.TriggerActionLive.Jetpack do
+  use TestServerNative, [:render_component, format: :jetpack]
+
+  def render(assigns, _) do
+    ~LVN"""
+    <LiveForm
+      id="trigger"
+      action="/trigger_action/submit"
+      method="post"
+      phx-submit="save"
+      phx-trigger-action={@trigger_submit}
+    >
+      <TextField name="name" value="triggered">Name</TextField>
+      <LiveSubmitButton>Save</LiveSubmitButton>
+    </LiveForm>
+    """
+  end
+end
@@ -41,6 +41,8 @@ defmodule TestServerWeb.Router do
     live("/nav/:dynamic", NavLive)
     live("/upload", SimpleLiveUpload)
     live("/stream", SimpleLiveStream)
+    live("/trigger_action", TriggerActionLive)
+    post("/trigger_action/submit", TriggerActionController, :create)
   end
 
   # Other scopes may use custom stacks.